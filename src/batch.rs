@@ -0,0 +1,283 @@
+use crate::error::{Error, Result};
+use crate::format::FormatRegistry;
+use crate::pipeline::BuildingReport;
+use crate::{convert_lod, ConvertOptions};
+use rayon::prelude::*;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+/// Outcome of converting a single file in a batch.
+///
+/// `success` covers reading and writing the file; the outcome of each building
+/// in it is recorded in `buildings`.
+#[derive(Debug, Clone, Serialize)]
+pub struct FileReport {
+    pub input: PathBuf,
+    pub output: PathBuf,
+    pub success: bool,
+    pub error_code: Option<&'static str>,
+    pub error: Option<String>,
+    pub duration_ms: u128,
+    pub buildings: Vec<BuildingReport>,
+}
+
+/// Collect the input files of a batch.
+///
+/// `input` is either a directory, in which case all files directly inside it
+/// with the extension of a format that `FormatRegistry::default()` can read are
+/// used, or a glob pattern such as `data/**/*.obj`. The result is sorted so
+/// batches are reproducible.
+pub fn collect_inputs(input: &str) -> Result<Vec<PathBuf>> {
+    let path = Path::new(input);
+    let mut inputs = Vec::new();
+
+    if path.is_dir() {
+        let extensions = FormatRegistry::default().input_extensions();
+        for entry in std::fs::read_dir(path)? {
+            let entry_path = entry?.path();
+            let is_model = entry_path.extension().is_some_and(|ext| {
+                extensions
+                    .iter()
+                    .any(|model_ext| ext.eq_ignore_ascii_case(model_ext))
+            });
+            if entry_path.is_file() && is_model {
+                inputs.push(entry_path);
+            }
+        }
+    } else {
+        for entry in glob::glob(input)? {
+            let entry_path = entry.map_err(|e| Error::Io(e.into_error()))?;
+            if entry_path.is_file() {
+                inputs.push(entry_path);
+            }
+        }
+    }
+
+    inputs.sort();
+    Ok(inputs)
+}
+
+/// Convert every input file into `output_dir` in parallel.
+///
+/// Failures don't stop the batch; they are recorded in the returned reports,
+/// which keep the order of `inputs`. Output files keep the input paths relative
+/// to the deepest directory holding all inputs, so inputs with the same file
+/// name in different directories don't overwrite each other, with the extension
+/// of `options.output_format` if given. Inputs that would still share an output
+/// path are an error. `jobs` limits the number of worker threads, defaulting to
+/// one per core.
+pub fn convert_batch(
+    inputs: &[PathBuf],
+    output_dir: &Path,
+    options: &ConvertOptions,
+    jobs: Option<usize>,
+) -> Result<Vec<FileReport>> {
+    std::fs::create_dir_all(output_dir)?;
+    let output_dir = output_dir.canonicalize()?;
+
+    let mut pool = rayon::ThreadPoolBuilder::new();
+    if let Some(jobs) = jobs {
+        pool = pool.num_threads(jobs);
+    }
+    let pool = pool.build()?;
+
+    // An explicit output format also sets the extension of the output files
+    let output_extension = match &options.output_format {
+        Some(name) => Some(FormatRegistry::default().by_name(name)?.extensions[0]),
+        None => None,
+    };
+
+    let root = common_root(inputs);
+    let outputs: Vec<PathBuf> = inputs
+        .iter()
+        .map(|input| {
+            let mut output = output_dir.join(input.strip_prefix(&root).unwrap_or(input));
+            if let Some(extension) = &output_extension {
+                output.set_extension(extension);
+            }
+            output
+        })
+        .collect();
+    let mut seen = HashMap::new();
+    for (input, output) in inputs.iter().zip(&outputs) {
+        if let Some(other) = seen.insert(output, input) {
+            return Err(Error::Io(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!(
+                    "{} and {} would both be written to {}",
+                    other.display(),
+                    input.display(),
+                    output.display()
+                ),
+            )));
+        }
+        if let Some(parent) = output.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+    }
+
+    let reports = pool.install(|| {
+        inputs
+            .par_iter()
+            .zip(outputs)
+            .map(|(input, output)| {
+                let start = Instant::now();
+                let result = convert_lod(input, &output, options);
+                let duration_ms = start.elapsed().as_millis();
+
+                match result {
+                    Ok(buildings) => FileReport {
+                        input: input.clone(),
+                        output,
+                        success: true,
+                        error_code: None,
+                        error: None,
+                        duration_ms,
+                        buildings,
+                    },
+                    Err(e) => FileReport {
+                        input: input.clone(),
+                        output,
+                        success: false,
+                        error_code: Some(e.code()),
+                        error: Some(e.to_string()),
+                        duration_ms,
+                        buildings: Vec::new(),
+                    },
+                }
+            })
+            .collect()
+    });
+
+    Ok(reports)
+}
+
+/// Deepest directory holding all `inputs`, the root of the output tree
+fn common_root(inputs: &[PathBuf]) -> PathBuf {
+    let mut root: Option<PathBuf> = None;
+    for input in inputs {
+        let parent = input.parent().unwrap_or(Path::new(""));
+        root = Some(match root {
+            None => parent.to_path_buf(),
+            Some(root) => root
+                .components()
+                .zip(parent.components())
+                .take_while(|(a, b)| a == b)
+                .map(|(a, _)| a)
+                .collect(),
+        });
+    }
+    root.unwrap_or_default()
+}
+
+/// Write a batch summary, as JSON if the path ends in `.json` and as CSV otherwise
+pub fn write_summary(reports: &[FileReport], path: &Path) -> Result<()> {
+    let file = File::create(path).map_err(|e| {
+        Error::Io(std::io::Error::new(
+            e.kind(),
+            format!("Failed to create file {}: {}", path.display(), e),
+        ))
+    })?;
+    let mut writer = BufWriter::new(file);
+
+    let is_json = path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("json"));
+    if is_json {
+        serde_json::to_writer_pretty(&mut writer, reports)?;
+        writeln!(writer)?;
+    } else {
+        write_summary_csv(reports, &mut writer)?;
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+/// Write one CSV row per building, or a single row for files that failed as a
+/// whole or held no buildings.
+///
+/// `success`, `error_code` and `error` describe the building, or the file when
+/// the `building` column is empty.
+fn write_summary_csv(reports: &[FileReport], writer: &mut impl Write) -> Result<()> {
+    writeln!(
+        writer,
+        "input,output,building,success,error_code,error,duration_ms,\
+         input_vertices,input_faces,ground_faces,wall_faces,roof_faces,\
+         ground_z,roof_z,height,output_vertices,output_faces"
+    )?;
+
+    for report in reports {
+        let file_columns = format!(
+            "{},{}",
+            csv_field(&report.input.display().to_string()),
+            csv_field(&report.output.display().to_string()),
+        );
+
+        if !report.success {
+            writeln!(
+                writer,
+                "{},,false,{},{},{},,,,,,,,,,",
+                file_columns,
+                report.error_code.unwrap_or(""),
+                csv_field(report.error.as_deref().unwrap_or("")),
+                report.duration_ms,
+            )?;
+            continue;
+        }
+        if report.buildings.is_empty() {
+            writeln!(
+                writer,
+                "{},,true,,,{},,,,,,,,,,",
+                file_columns, report.duration_ms,
+            )?;
+            continue;
+        }
+
+        for building in &report.buildings {
+            write!(
+                writer,
+                "{},{},{},{},{},{}",
+                file_columns,
+                csv_field(building.id.as_deref().unwrap_or("")),
+                building.success,
+                building.error_code.unwrap_or(""),
+                csv_field(building.error.as_deref().unwrap_or("")),
+                report.duration_ms,
+            )?;
+
+            match &building.stats {
+                Some(stats) => writeln!(
+                    writer,
+                    ",{},{},{},{},{},{},{},{},{},{}",
+                    stats.input_vertices,
+                    stats.input_faces,
+                    stats.ground_faces,
+                    stats.wall_faces,
+                    stats.roof_faces,
+                    stats.ground_z,
+                    stats.roof_z,
+                    stats.height,
+                    stats.output_vertices,
+                    stats.output_faces,
+                )?,
+                None => writeln!(writer, ",,,,,,,,,,")?,
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Quote a CSV field if it contains separators, quotes or line breaks
+pub(crate) fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
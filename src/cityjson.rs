@@ -0,0 +1,593 @@
+use crate::error::{Error, Result};
+use crate::geodesy::units_per_metre;
+use crate::model::Model;
+use crate::primitives::{Face, SurfaceType, Vertex};
+use crate::transform::{epsg_uri, parse_epsg};
+use crate::triangulate::{bridge_holes, newell_normal, projection_axes, split_keyhole};
+use crate::weld::weld_model;
+use crate::EPSILON;
+use serde_json::{json, Map, Value};
+use std::io::{BufRead, Write};
+
+/// Scale of the quantized vertices written to CityJSON, i.e. millimetres
+const CITYJSON_SCALE: f64 = 0.001;
+const CITYJSON_VERSION: &str = "2.0";
+
+/// Semantic surface types written for Ground, Wall, Roof and Floor faces
+const SEMANTIC_SURFACES: [(SurfaceType, &str); 4] = [
+    (SurfaceType::Ground, "GroundSurface"),
+    (SurfaceType::Wall, "WallSurface"),
+    (SurfaceType::Roof, "RoofSurface"),
+    (SurfaceType::Floor, "FloorSurface"),
+];
+/// Semantic surface type of a storey floor seen from the storey below
+const CEILING_SURFACE: &str = "CeilingSurface";
+
+/// Streaming reader for CityJSON Text Sequences (CityJSONSeq) and CityJSON files.
+///
+/// The input is a stream of JSON values: a `CityJSON` object holding the
+/// vertex transform, followed by `CityJSONFeature` objects, one per line in a
+/// CityJSONSeq. Every feature becomes one model named after the feature ID,
+/// holding the surfaces of the feature's city object and of its children. For
+/// a plain CityJSON file, every city object without parents becomes a model.
+/// Only the geometry with the highest LoD of every city object is read. The
+/// interior rings of a surface are joined to its exterior by bridge edges, see
+/// `bridge_holes`, so every surface becomes a single face. The EPSG code of the
+/// `metadata.referenceSystem` becomes the CRS of the models.
+pub struct CityJsonReader<R: BufRead> {
+    values: serde_json::StreamDeserializer<'static, serde_json::de::IoRead<R>, Value>,
+    transform: Option<Transform>,
+    crs: Option<u32>,
+    pending: std::vec::IntoIter<Model>,
+    finished: bool,
+}
+
+/// Transform from quantized to real coordinates
+#[derive(Debug, Clone, Copy)]
+struct Transform {
+    scale: [f64; 3],
+    translate: [f64; 3],
+}
+
+impl Transform {
+    fn parse(value: &Value) -> Option<Self> {
+        let vector = |key: &str| -> Option<[f64; 3]> {
+            let array = value.get(key)?.as_array()?;
+            Some([
+                array.first()?.as_f64()?,
+                array.get(1)?.as_f64()?,
+                array.get(2)?.as_f64()?,
+            ])
+        };
+        Some(Transform {
+            scale: vector("scale")?,
+            translate: vector("translate")?,
+        })
+    }
+
+    fn apply(&self, vertex: &Value) -> Result<cgmath::Point3<f64>> {
+        let coordinate = |axis: usize| {
+            vertex
+                .get(axis)
+                .and_then(Value::as_f64)
+                .map(|c| c * self.scale[axis] + self.translate[axis])
+                .ok_or_else(|| invalid_data("Invalid CityJSON vertex"))
+        };
+        Ok(cgmath::Point3::new(
+            coordinate(0)?,
+            coordinate(1)?,
+            coordinate(2)?,
+        ))
+    }
+}
+
+impl<R: BufRead> CityJsonReader<R> {
+    /// Create a reader over buffered CityJSON or CityJSONSeq content
+    pub fn new(reader: R) -> Self {
+        CityJsonReader {
+            values: serde_json::Deserializer::from_reader(reader).into_iter(),
+            transform: None,
+            crs: None,
+            pending: Vec::new().into_iter(),
+            finished: false,
+        }
+    }
+
+    /// Turn the next JSON value into models
+    fn read_value(&mut self, value: Value) -> Result<Vec<Model>> {
+        match value.get("type").and_then(Value::as_str) {
+            Some("CityJSON") => {
+                // Vertices of version 1.0 files may not be quantized
+                self.transform = Some(Transform::parse(&value["transform"]).unwrap_or(Transform {
+                    scale: [1.0; 3],
+                    translate: [0.0; 3],
+                }));
+                self.crs = value["metadata"]["referenceSystem"]
+                    .as_str()
+                    .and_then(parse_epsg);
+                let objects = value["CityObjects"]
+                    .as_object()
+                    .cloned()
+                    .unwrap_or_default();
+                let roots: Vec<&String> = objects
+                    .iter()
+                    .filter(|(_, object)| {
+                        object["parents"]
+                            .as_array()
+                            .is_none_or(|parents| parents.is_empty())
+                    })
+                    .map(|(id, _)| id)
+                    .collect();
+                roots
+                    .into_iter()
+                    .map(|id| self.build_model(id, &objects, &value["vertices"]))
+                    .collect()
+            }
+            Some("CityJSONFeature") => {
+                let id = value["id"]
+                    .as_str()
+                    .ok_or_else(|| invalid_data("CityJSONFeature has no id"))?;
+                let objects = value["CityObjects"]
+                    .as_object()
+                    .ok_or_else(|| invalid_data("CityJSONFeature has no CityObjects"))?;
+                Ok(vec![self.build_model(id, objects, &value["vertices"])?])
+            }
+            Some(other) => Err(invalid_data(&format!(
+                "Unexpected CityJSON object type: {}",
+                other
+            ))),
+            None => Err(invalid_data("CityJSON object has no type")),
+        }
+    }
+
+    /// Build a model from a city object and its children
+    fn build_model(
+        &self,
+        id: &str,
+        objects: &Map<String, Value>,
+        vertices: &Value,
+    ) -> Result<Model> {
+        let transform = self.transform.ok_or_else(|| {
+            invalid_data("CityJSONFeature before the CityJSON object with the transform")
+        })?;
+        let vertices = vertices
+            .as_array()
+            .ok_or_else(|| invalid_data("CityJSON object has no vertices"))?;
+
+        let mut faces = Vec::new();
+        let mut stack = vec![id.to_string()];
+        while let Some(object_id) = stack.pop() {
+            let Some(object) = objects.get(&object_id) else {
+                continue;
+            };
+            if let Some(geometry) = highest_lod_geometry(object) {
+                read_surfaces(geometry, &mut faces)?;
+            }
+            if let Some(children) = object["children"].as_array() {
+                stack.extend(
+                    children
+                        .iter()
+                        .rev()
+                        .filter_map(|c| c.as_str().map(String::from)),
+                );
+            }
+        }
+
+        // Keep only the vertices used by the faces, in their original order
+        let mut local_ids = vec![usize::MAX; vertices.len()];
+        let mut used: Vec<usize> = faces
+            .iter()
+            .flat_map(|(face, holes): &(Face, Vec<Vec<usize>>)| {
+                face.vertex_ids
+                    .iter()
+                    .chain(holes.iter().flatten())
+                    .copied()
+            })
+            .collect();
+        used.sort_unstable();
+        used.dedup();
+        let mut model_vertices = Vec::with_capacity(used.len());
+        for &index in &used {
+            let vertex = vertices
+                .get(index)
+                .ok_or_else(|| invalid_data("CityJSON surface references a missing vertex"))?;
+            local_ids[index] = model_vertices.len();
+            model_vertices.push(Vertex {
+                point: transform.apply(vertex)?,
+                id: model_vertices.len(),
+            });
+        }
+        let faces: Vec<Face> = faces
+            .into_iter()
+            .map(|(mut face, mut holes)| {
+                for vertex_id in face.vertex_ids.iter_mut().chain(holes.iter_mut().flatten()) {
+                    *vertex_id = local_ids[*vertex_id];
+                }
+                // Faces have a single ring, so holes are joined to the exterior by bridge edges
+                if !holes.is_empty() {
+                    let normal = newell_normal(&face.vertex_ids, &model_vertices);
+                    let axes = projection_axes(normal);
+                    let mut ring = bridge_holes(&face.vertex_ids, &holes, axes, &model_vertices);
+                    // The ring runs counter-clockwise seen from the positive side of the dropped axis
+                    if normal[3 - axes[0] - axes[1]] < 0.0 {
+                        ring.reverse();
+                    }
+                    face.vertex_ids = ring;
+                }
+                face
+            })
+            .collect();
+
+        let mut model = Model::new(model_vertices, faces);
+        model.id = Some(id.to_string());
+        model.crs = self.crs;
+        Ok(model)
+    }
+}
+
+impl<R: BufRead> Iterator for CityJsonReader<R> {
+    type Item = Result<Model>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(model) = self.pending.next() {
+                return Some(Ok(model));
+            }
+            if self.finished {
+                return None;
+            }
+
+            let value = match self.values.next() {
+                Some(Ok(value)) => value,
+                Some(Err(e)) => {
+                    self.finished = true;
+                    return Some(Err(Error::Json(e)));
+                }
+                None => {
+                    self.finished = true;
+                    return None;
+                }
+            };
+            match self.read_value(value) {
+                Ok(models) => self.pending = models.into_iter(),
+                Err(e) => {
+                    self.finished = true;
+                    return Some(Err(e));
+                }
+            }
+        }
+    }
+}
+
+/// The geometry of a city object with the highest LoD
+fn highest_lod_geometry(object: &Value) -> Option<&Value> {
+    let lod = |geometry: &Value| -> f64 {
+        match &geometry["lod"] {
+            Value::String(lod) => lod.parse().unwrap_or(0.0),
+            Value::Number(lod) => lod.as_f64().unwrap_or(0.0),
+            _ => 0.0,
+        }
+    };
+    object["geometry"]
+        .as_array()?
+        .iter()
+        .filter(|geometry| geometry["boundaries"].is_array())
+        .max_by(|a, b| lod(a).total_cmp(&lod(b)))
+}
+
+/// Append the surfaces of a geometry as faces, with their semantic surface
+/// types, and the holes of their exterior rings
+fn read_surfaces(geometry: &Value, faces: &mut Vec<(Face, Vec<Vec<usize>>)>) -> Result<()> {
+    let surface_types: Vec<SurfaceType> = geometry["semantics"]["surfaces"]
+        .as_array()
+        .map(|surfaces| {
+            surfaces
+                .iter()
+                .map(|surface| match surface["type"].as_str() {
+                    Some("GroundSurface") => SurfaceType::Ground,
+                    Some("WallSurface") => SurfaceType::Wall,
+                    Some("RoofSurface") => SurfaceType::Roof,
+                    // Both sides of a storey floor
+                    Some("FloorSurface") | Some("CeilingSurface") => SurfaceType::Floor,
+                    _ => SurfaceType::Unknown,
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    // Solids nest their surfaces in shells, and MultiSolids in solids
+    let depth = match geometry["type"].as_str() {
+        Some("MultiSurface") | Some("CompositeSurface") => 0,
+        Some("Solid") => 1,
+        Some("MultiSolid") | Some("CompositeSolid") => 2,
+        _ => return Ok(()), // points and lines have no surfaces
+    };
+    collect_surfaces(
+        &geometry["boundaries"],
+        &geometry["semantics"]["values"],
+        depth,
+        &surface_types,
+        faces,
+    )
+}
+
+fn collect_surfaces(
+    boundaries: &Value,
+    values: &Value,
+    depth: usize,
+    surface_types: &[SurfaceType],
+    faces: &mut Vec<(Face, Vec<Vec<usize>>)>,
+) -> Result<()> {
+    let boundaries = boundaries
+        .as_array()
+        .ok_or_else(|| invalid_data("Invalid CityJSON boundaries"))?;
+
+    for (i, boundary) in boundaries.iter().enumerate() {
+        if depth > 0 {
+            collect_surfaces(boundary, &values[i], depth - 1, surface_types, faces)?;
+            continue;
+        }
+
+        // A surface is a list of rings; the first one is the exterior
+        let mut rings = boundary
+            .as_array()
+            .ok_or_else(|| invalid_data("Invalid CityJSON surface"))?
+            .iter()
+            .map(ring_ids)
+            .collect::<Result<Vec<_>>>()?
+            .into_iter();
+        let Some(vertex_ids) = rings.next() else {
+            continue;
+        };
+        if vertex_ids.len() < 3 {
+            continue;
+        }
+
+        let mut face = Face::new(vertex_ids);
+        if let Some(index) = values[i].as_u64() {
+            face.surface_type = surface_types
+                .get(index as usize)
+                .copied()
+                .unwrap_or(SurfaceType::Unknown);
+        }
+        faces.push((face, rings.collect()));
+    }
+    Ok(())
+}
+
+/// Vertex indices of a CityJSON ring
+fn ring_ids(ring: &Value) -> Result<Vec<usize>> {
+    ring.as_array()
+        .ok_or_else(|| invalid_data("Invalid CityJSON ring"))?
+        .iter()
+        .map(|id| {
+            id.as_u64()
+                .map(|id| id as usize)
+                .ok_or_else(|| invalid_data("Invalid CityJSON vertex index"))
+        })
+        .collect()
+}
+
+fn invalid_data(message: &str) -> Error {
+    Error::Io(std::io::Error::new(
+        std::io::ErrorKind::InvalidData,
+        message.to_string(),
+    ))
+}
+
+/// Boundaries and semantic values of the solids of a model split into storeys.
+///
+/// Faces belong to the storey they rise from. Every floor closes the storey
+/// below it as its ceiling and, turned over, the storey above it as its floor.
+fn storey_solids(
+    model: &Model,
+    faces: &[Vec<usize>],
+    semantic: impl Fn(SurfaceType) -> Value,
+) -> (Vec<Value>, Vec<Value>) {
+    let bottom = |i: usize| model.faces[i].z_range(&model.vertices).0;
+    let mut levels: Vec<f64> = (0..model.faces.len())
+        .filter(|&i| model.faces[i].surface_type == SurfaceType::Floor)
+        .map(bottom)
+        .collect();
+    levels.sort_by(|a, b| a.total_cmp(b));
+    levels.dedup_by(|a, b| (*a - *b).abs() < EPSILON);
+    let storey_of = |z: f64| levels.iter().filter(|&&level| level <= z + EPSILON).count();
+
+    let mut shells: Vec<(Vec<Value>, Vec<Value>)> =
+        vec![(Vec::new(), Vec::new()); levels.len() + 1];
+    for (i, face) in model.faces.iter().enumerate() {
+        let storey = storey_of(bottom(i));
+        if face.surface_type == SurfaceType::Floor {
+            shells[storey - 1].0.push(json!(split_keyhole(&faces[i])));
+            shells[storey - 1].1.push(json!(SEMANTIC_SURFACES.len()));
+            let turned: Vec<usize> = faces[i].iter().rev().copied().collect();
+            shells[storey].0.push(json!(split_keyhole(&turned)));
+            shells[storey].1.push(semantic(face.surface_type));
+        } else {
+            shells[storey].0.push(json!(split_keyhole(&faces[i])));
+            shells[storey].1.push(semantic(face.surface_type));
+        }
+    }
+
+    shells
+        .into_iter()
+        .map(|(shell, values)| (json!([shell]), json!([values])))
+        .unzip()
+}
+
+/// Writer for CityJSON Text Sequences (CityJSONSeq).
+///
+/// The first line is a `CityJSON` object with the vertex transform, written
+/// with the first model; its translation is the minimum corner of that model,
+/// rounded down to whole units, its scale millimetres, or about that in degrees
+/// for geographic CRSs, and its `metadata.referenceSystem` the CRS of that
+/// model, if known. Every model is then written as a `CityJSONFeature` line
+/// with a single LoD1.2 Building whose geometry is a Solid with Ground, Wall
+/// and Roof semantics, or a CompositeSolid of one Solid per storey for models
+/// with storey floors. Keyhole faces, such as merged coplanar faces around a
+/// courtyard, are split back into an exterior and its holes. Vertices are
+/// welded per model.
+pub struct CityJsonSeqWriter<W: Write> {
+    writer: W,
+    tolerance: f64,
+    transform: Option<Transform>,
+    unnamed: usize, // number of models written without an ID
+}
+
+impl<W: Write> CityJsonSeqWriter<W> {
+    /// Create a writer welding vertices closer than `tolerance`
+    pub fn new(writer: W, tolerance: f64) -> Self {
+        CityJsonSeqWriter {
+            writer,
+            tolerance,
+            transform: None,
+            unnamed: 0,
+        }
+    }
+
+    /// Append a model as a CityJSONFeature
+    pub fn write_model(&mut self, model: &Model) -> Result<()> {
+        let (points, faces) = weld_model(model, self.tolerance);
+
+        let transform = match self.transform {
+            Some(transform) => transform,
+            None => {
+                let mut min = [f64::MAX; 3];
+                for point in &points {
+                    for (axis, value) in [point.x, point.y, point.z].into_iter().enumerate() {
+                        min[axis] = min[axis].min(value.floor());
+                    }
+                }
+                if points.is_empty() {
+                    min = [0.0; 3];
+                }
+                // Millimetres, also for x and y in degrees
+                let horizontal = CITYJSON_SCALE * units_per_metre(model.crs);
+                let transform = Transform {
+                    scale: [horizontal, horizontal, CITYJSON_SCALE],
+                    translate: min,
+                };
+                self.write_header(transform, model.crs)?;
+                self.transform = Some(transform);
+                transform
+            }
+        };
+
+        let id = match &model.id {
+            Some(id) => id.clone(),
+            None => {
+                self.unnamed += 1;
+                format!("building-{}", self.unnamed)
+            }
+        };
+
+        let vertices: Vec<[i64; 3]> = points
+            .iter()
+            .map(|point| {
+                let quantize = |value: f64, axis: usize| {
+                    ((value - transform.translate[axis]) / transform.scale[axis]).round()
+                };
+                [
+                    quantize(point.x, 0) as i64,
+                    quantize(point.y, 1) as i64,
+                    quantize(point.z, 2) as i64,
+                ]
+            })
+            .collect();
+
+        let semantic = |surface_type: SurfaceType| match SEMANTIC_SURFACES
+            .iter()
+            .position(|(semantic_type, _)| *semantic_type == surface_type)
+        {
+            Some(index) => json!(index),
+            None => Value::Null,
+        };
+        let surfaces: Vec<Value> = SEMANTIC_SURFACES
+            .iter()
+            .map(|(_, name)| *name)
+            .chain(std::iter::once(CEILING_SURFACE))
+            .map(|name| json!({ "type": name }))
+            .collect();
+
+        let geometry = if model
+            .faces
+            .iter()
+            .any(|face| face.surface_type == SurfaceType::Floor)
+        {
+            let (boundaries, values) = storey_solids(model, &faces, semantic);
+            json!({
+                "type": "CompositeSolid",
+                "lod": "1.2",
+                "boundaries": boundaries,
+                "semantics": { "surfaces": surfaces, "values": values },
+            })
+        } else {
+            let shell: Vec<Value> = faces
+                .iter()
+                .map(|face| json!(split_keyhole(face)))
+                .collect();
+            let values: Vec<Value> = model
+                .faces
+                .iter()
+                .map(|face| semantic(face.surface_type))
+                .collect();
+            json!({
+                "type": "Solid",
+                "lod": "1.2",
+                "boundaries": [shell],
+                "semantics": { "surfaces": surfaces, "values": [values] },
+            })
+        };
+        let mut building = json!({
+            "type": "Building",
+            "geometry": [geometry],
+        });
+        if !model.attributes.is_empty() {
+            building["attributes"] = Value::Object(model.attributes.clone());
+        }
+        let mut city_objects = Map::new();
+        city_objects.insert(id.clone(), building);
+        let feature = json!({
+            "type": "CityJSONFeature",
+            "id": id,
+            "CityObjects": city_objects,
+            "vertices": vertices,
+        });
+        serde_json::to_writer(&mut self.writer, &feature)?;
+        writeln!(self.writer).map_err(Error::Io)?;
+        Ok(())
+    }
+
+    fn write_header(&mut self, transform: Transform, crs: Option<u32>) -> Result<()> {
+        let mut header = json!({
+            "type": "CityJSON",
+            "version": CITYJSON_VERSION,
+            "transform": {
+                "scale": transform.scale,
+                "translate": transform.translate,
+            },
+            "CityObjects": {},
+            "vertices": [],
+        });
+        if let Some(crs) = crs {
+            header["metadata"] = json!({ "referenceSystem": epsg_uri(crs) });
+        }
+        serde_json::to_writer(&mut self.writer, &header)?;
+        writeln!(self.writer).map_err(Error::Io)?;
+        Ok(())
+    }
+
+    /// Flush and return the underlying writer
+    pub fn finish(mut self) -> Result<W> {
+        // A sequence without features still starts with the CityJSON object
+        if self.transform.is_none() {
+            let transform = Transform {
+                scale: [CITYJSON_SCALE; 3],
+                translate: [0.0; 3],
+            };
+            self.write_header(transform, None)?;
+        }
+        self.writer.flush().map_err(Error::Io)?;
+        Ok(self.writer)
+    }
+}
@@ -0,0 +1,169 @@
+use crate::distance::{surface_distance, SurfaceDistance};
+use crate::model::Model;
+use crate::primitives::{Face, SurfaceType, Vertex};
+use crate::stats::volume;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt;
+
+/// Properties that a lower LoD should preserve, following Biljecki et al. (2014)
+#[derive(Debug, Clone, Serialize)]
+pub struct LodMetrics {
+    pub triangle_count: usize,
+    pub total_area: f64,
+    pub wall_area: f64,
+    pub volume: f64,
+    pub memory_size: usize, // bytes of vertex and face storage
+}
+
+impl LodMetrics {
+    /// Measure a model. Wall area uses the current surface types, so classify the model first.
+    pub fn from_model(model: &Model) -> Self {
+        let mut triangle_count = 0;
+        let mut total_area = 0.0;
+        let mut wall_area = 0.0;
+        let mut memory_size = model.vertices.len() * std::mem::size_of::<Vertex>();
+
+        for face in &model.faces {
+            // A polygon with n vertices needs n - 2 triangles
+            triangle_count += face.vertex_ids.len().saturating_sub(2);
+
+            let area = face.area(&model.vertices);
+            total_area += area;
+            if face.surface_type == SurfaceType::Wall {
+                wall_area += area;
+            }
+
+            memory_size +=
+                std::mem::size_of::<Face>() + face.vertex_ids.len() * std::mem::size_of::<usize>();
+        }
+
+        LodMetrics {
+            triangle_count,
+            total_area,
+            wall_area,
+            volume: volume(model),
+            memory_size,
+        }
+    }
+}
+
+/// One metric of the input and output side by side
+#[derive(Debug, Clone, Serialize)]
+pub struct MetricComparison {
+    pub metric: &'static str,
+    pub input: f64,
+    pub output: f64,
+    pub absolute_difference: f64,         // output - input
+    pub relative_difference: Option<f64>, // (output - input) / input, None if input is zero
+}
+
+impl MetricComparison {
+    fn new(metric: &'static str, input: f64, output: f64) -> Self {
+        let absolute_difference = output - input;
+        MetricComparison {
+            metric,
+            input,
+            output,
+            absolute_difference,
+            relative_difference: if input != 0.0 {
+                Some(absolute_difference / input)
+            } else {
+                None
+            },
+        }
+    }
+}
+
+/// Comparison of a LoD2.2 building with its LoD1.2 conversion
+#[derive(Debug, Clone, Serialize)]
+pub struct ComparisonReport {
+    pub id: Option<String>,
+    pub metrics: Vec<MetricComparison>,
+    pub distance: Option<SurfaceDistance>, // None if either model has no surface
+}
+
+/// Compare the LoD2.2 `input` with the LoD1.2 `output`.
+///
+/// Both models should be classified so that wall areas can be measured. The
+/// surfaces are sampled every `sample_spacing` metres to measure the geometric error.
+pub fn compare_models(input: &Model, output: &Model, sample_spacing: f64) -> ComparisonReport {
+    let before = LodMetrics::from_model(input);
+    let after = LodMetrics::from_model(output);
+
+    ComparisonReport {
+        id: input.id.clone().or_else(|| output.id.clone()),
+        metrics: vec![
+            MetricComparison::new(
+                "triangle_count",
+                before.triangle_count as f64,
+                after.triangle_count as f64,
+            ),
+            MetricComparison::new("total_area", before.total_area, after.total_area),
+            MetricComparison::new("wall_area", before.wall_area, after.wall_area),
+            MetricComparison::new("volume", before.volume, after.volume),
+            MetricComparison::new(
+                "memory_size",
+                before.memory_size as f64,
+                after.memory_size as f64,
+            ),
+        ],
+        distance: surface_distance(input, output, sample_spacing),
+    }
+}
+
+/// Compare many buildings, pairing them by ID, or by position if IDs are missing.
+///
+/// Input buildings without a counterpart in `outputs` are skipped.
+pub fn compare_buildings(
+    inputs: &[Model],
+    outputs: &[Model],
+    sample_spacing: f64,
+) -> Vec<ComparisonReport> {
+    let all_named = inputs.iter().chain(outputs).all(|model| model.id.is_some());
+    if !all_named {
+        return inputs
+            .iter()
+            .zip(outputs)
+            .map(|(input, output)| compare_models(input, output, sample_spacing))
+            .collect();
+    }
+
+    let outputs_by_id: HashMap<&str, &Model> = outputs
+        .iter()
+        .filter_map(|model| model.id.as_deref().map(|id| (id, model)))
+        .collect();
+    inputs
+        .iter()
+        .filter_map(|input| {
+            let output = outputs_by_id.get(input.id.as_deref()?)?;
+            Some(compare_models(input, output, sample_spacing))
+        })
+        .collect()
+}
+
+impl fmt::Display for ComparisonReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Building: {}", self.id.as_deref().unwrap_or("<unnamed>"))?;
+        write!(
+            f,
+            "  {:<16}{:>14}{:>14}{:>14}{:>10}",
+            "Metric", "LoD2.2", "LoD1.2", "Abs. diff", "Rel. diff"
+        )?;
+        for metric in &self.metrics {
+            let relative = match metric.relative_difference {
+                Some(relative) => format!("{:.1}%", relative * 100.0),
+                None => "-".to_string(),
+            };
+            write!(
+                f,
+                "\n  {:<16}{:>14.3}{:>14.3}{:>14.3}{:>10}",
+                metric.metric, metric.input, metric.output, metric.absolute_difference, relative
+            )?;
+        }
+        if let Some(distance) = &self.distance {
+            write!(f, "\n\n{}", distance)?;
+        }
+        Ok(())
+    }
+}
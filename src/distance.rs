@@ -0,0 +1,416 @@
+use crate::model::Model;
+use crate::triangulate::triangulate_face;
+use cgmath::{EuclideanSpace, InnerSpace, Point3};
+use rayon::prelude::*;
+use serde::Serialize;
+use std::fmt;
+
+/// Distances from the samples of one surface to another surface
+#[derive(Debug, Clone, Serialize)]
+pub struct DistanceStats {
+    pub samples: usize,
+    pub max: f64, // one-sided Hausdorff distance
+    pub mean: f64,
+    pub rms: f64,
+}
+
+/// Geometric error between two models, measured in both directions
+#[derive(Debug, Clone, Serialize)]
+pub struct SurfaceDistance {
+    pub forward: DistanceStats,  // from the first model to the second
+    pub backward: DistanceStats, // from the second model to the first
+    pub hausdorff: f64,          // symmetric Hausdorff distance
+    pub mean: f64,               // mean over the samples of both directions
+    pub rms: f64,                // RMS over the samples of both directions
+}
+
+impl fmt::Display for SurfaceDistance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "  {:<16}{:>10}{:>12}{:>12}{:>12}",
+            "Distance", "Samples", "Max", "Mean", "RMS"
+        )?;
+        for (name, stats) in [
+            ("LoD2.2 -> LoD1.2", &self.forward),
+            ("LoD1.2 -> LoD2.2", &self.backward),
+        ] {
+            write!(
+                f,
+                "\n  {:<16}{:>10}{:>12.3}{:>12.3}{:>12.3}",
+                name, stats.samples, stats.max, stats.mean, stats.rms
+            )?;
+        }
+        write!(
+            f,
+            "\n  {:<16}{:>10}{:>12.3}{:>12.3}{:>12.3}",
+            "Symmetric",
+            self.forward.samples + self.backward.samples,
+            self.hausdorff,
+            self.mean,
+            self.rms
+        )
+    }
+}
+
+/// Sample `from` every `spacing` metres and measure the distance of each sample to `to`.
+///
+/// Returns `None` if either model has no surface.
+pub fn one_sided_distance(from: &Model, to: &Model, spacing: f64) -> Option<DistanceStats> {
+    let origin = to.world_point(to.vertices.first()?.point);
+    let bvh = Bvh::new(triangles(to, origin))?;
+    let samples = sample_surface(&triangles(from, origin), spacing);
+    if samples.is_empty() {
+        return None;
+    }
+
+    let distances: Vec<f64> = samples
+        .par_iter()
+        .map(|sample| bvh.nearest_distance_squared(sample).sqrt())
+        .collect();
+
+    // Accumulate sequentially so the sums don't depend on the thread count
+    let mut max: f64 = 0.0;
+    let mut sum = 0.0;
+    let mut sum_squared = 0.0;
+    for distance in distances {
+        max = max.max(distance);
+        sum += distance;
+        sum_squared += distance * distance;
+    }
+
+    let count = samples.len() as f64;
+    Some(DistanceStats {
+        samples: samples.len(),
+        max,
+        mean: sum / count,
+        rms: (sum_squared / count).sqrt(),
+    })
+}
+
+/// Symmetric surface distance between two models, sampled every `spacing` metres
+pub fn surface_distance(a: &Model, b: &Model, spacing: f64) -> Option<SurfaceDistance> {
+    let forward = one_sided_distance(a, b, spacing)?;
+    let backward = one_sided_distance(b, a, spacing)?;
+
+    let count = (forward.samples + backward.samples) as f64;
+    let mean =
+        (forward.mean * forward.samples as f64 + backward.mean * backward.samples as f64) / count;
+    let mean_squared = (forward.rms.powi(2) * forward.samples as f64
+        + backward.rms.powi(2) * backward.samples as f64)
+        / count;
+
+    Some(SurfaceDistance {
+        hausdorff: forward.max.max(backward.max),
+        mean,
+        rms: mean_squared.sqrt(),
+        forward,
+        backward,
+    })
+}
+
+/// A triangle in coordinates relative to a local origin
+#[derive(Debug, Clone, Copy)]
+struct Triangle {
+    a: Point3<f64>,
+    b: Point3<f64>,
+    c: Point3<f64>,
+}
+
+impl Triangle {
+    fn centroid(&self) -> Point3<f64> {
+        Point3::new(
+            (self.a.x + self.b.x + self.c.x) / 3.0,
+            (self.a.y + self.b.y + self.c.y) / 3.0,
+            (self.a.z + self.b.z + self.c.z) / 3.0,
+        )
+    }
+
+    /// Squared distance from `p` to the closest point of the triangle (Ericson, 2004)
+    fn distance_squared(&self, p: &Point3<f64>) -> f64 {
+        let ab = self.b - self.a;
+        let ac = self.c - self.a;
+        let ap = p - self.a;
+
+        let d1 = ab.dot(ap);
+        let d2 = ac.dot(ap);
+        if d1 <= 0.0 && d2 <= 0.0 {
+            return ap.magnitude2(); // vertex region A
+        }
+
+        let bp = p - self.b;
+        let d3 = ab.dot(bp);
+        let d4 = ac.dot(bp);
+        if d3 >= 0.0 && d4 <= d3 {
+            return bp.magnitude2(); // vertex region B
+        }
+
+        let vc = d1 * d4 - d3 * d2;
+        if vc <= 0.0 && d1 >= 0.0 && d3 <= 0.0 {
+            let v = d1 / (d1 - d3);
+            return (ap - ab * v).magnitude2(); // edge region AB
+        }
+
+        let cp = p - self.c;
+        let d5 = ab.dot(cp);
+        let d6 = ac.dot(cp);
+        if d6 >= 0.0 && d5 <= d6 {
+            return cp.magnitude2(); // vertex region C
+        }
+
+        let vb = d5 * d2 - d1 * d6;
+        if vb <= 0.0 && d2 >= 0.0 && d6 <= 0.0 {
+            let w = d2 / (d2 - d6);
+            return (ap - ac * w).magnitude2(); // edge region AC
+        }
+
+        let va = d3 * d6 - d5 * d4;
+        if va <= 0.0 && (d4 - d3) >= 0.0 && (d5 - d6) >= 0.0 {
+            let w = (d4 - d3) / ((d4 - d3) + (d5 - d6));
+            return (bp - (self.c - self.b) * w).magnitude2(); // edge region BC
+        }
+
+        // Inside the face region
+        let denominator = va + vb + vc;
+        if denominator.abs() < f64::MIN_POSITIVE {
+            return ap.magnitude2(); // degenerate triangle
+        }
+        let v = vb / denominator;
+        let w = vc / denominator;
+        (ap - ab * v - ac * w).magnitude2()
+    }
+}
+
+/// Triangulate the faces of a model relative to `origin`
+fn triangles(model: &Model, origin: Point3<f64>) -> Vec<Triangle> {
+    let local = |id: usize| Point3::from_vec(model.world_point(model.vertices[id].point) - origin);
+
+    model
+        .faces
+        .iter()
+        .flat_map(|face| triangulate_face(face, &model.vertices))
+        .map(|[a, b, c]| Triangle {
+            a: local(a),
+            b: local(b),
+            c: local(c),
+        })
+        .collect()
+}
+
+/// Deterministic samples on the triangles: the vertices plus the centroids of
+/// the pieces left after bisecting the longest edge until it is at most `spacing`.
+///
+/// Bisection keeps the number of samples proportional to the area and edge
+/// lengths, so long slivers aren't oversampled.
+fn sample_surface(triangles: &[Triangle], spacing: f64) -> Vec<Point3<f64>> {
+    let spacing = spacing.max(1e-3);
+    let mut samples = Vec::new();
+    let mut stack = Vec::new();
+
+    for triangle in triangles {
+        samples.extend([triangle.a, triangle.b, triangle.c]);
+
+        stack.push(*triangle);
+        while let Some(t) = stack.pop() {
+            let edges = [
+                (t.b - t.a).magnitude2(),
+                (t.c - t.b).magnitude2(),
+                (t.a - t.c).magnitude2(),
+            ];
+            let longest = edges[0].max(edges[1]).max(edges[2]);
+            if longest <= spacing * spacing {
+                samples.push(t.centroid());
+                continue;
+            }
+
+            // Rotate so that the longest edge is a-b, then split it at its midpoint
+            let (a, b, c) = if longest == edges[0] {
+                (t.a, t.b, t.c)
+            } else if longest == edges[1] {
+                (t.b, t.c, t.a)
+            } else {
+                (t.c, t.a, t.b)
+            };
+            let mid = a.midpoint(b);
+            stack.push(Triangle { a, b: mid, c });
+            stack.push(Triangle { a: mid, b, c });
+        }
+    }
+
+    samples
+}
+
+/// Axis-aligned bounding box
+#[derive(Debug, Clone, Copy)]
+struct Aabb {
+    min: Point3<f64>,
+    max: Point3<f64>,
+}
+
+impl Aabb {
+    fn of_triangle(triangle: &Triangle) -> Self {
+        let mut aabb = Aabb {
+            min: triangle.a,
+            max: triangle.a,
+        };
+        aabb.extend(&triangle.b);
+        aabb.extend(&triangle.c);
+        aabb
+    }
+
+    fn extend(&mut self, p: &Point3<f64>) {
+        self.min = Point3::new(
+            self.min.x.min(p.x),
+            self.min.y.min(p.y),
+            self.min.z.min(p.z),
+        );
+        self.max = Point3::new(
+            self.max.x.max(p.x),
+            self.max.y.max(p.y),
+            self.max.z.max(p.z),
+        );
+    }
+
+    fn merge(&self, other: &Aabb) -> Aabb {
+        let mut merged = *self;
+        merged.extend(&other.min);
+        merged.extend(&other.max);
+        merged
+    }
+
+    fn distance_squared(&self, p: &Point3<f64>) -> f64 {
+        let dx = (self.min.x - p.x).max(0.0).max(p.x - self.max.x);
+        let dy = (self.min.y - p.y).max(0.0).max(p.y - self.max.y);
+        let dz = (self.min.z - p.z).max(0.0).max(p.z - self.max.z);
+        dx * dx + dy * dy + dz * dz
+    }
+}
+
+/// A node of the BVH; leaves reference a range of `Bvh::triangles`
+#[derive(Debug, Clone)]
+enum BvhNode {
+    Leaf {
+        aabb: Aabb,
+        start: usize,
+        end: usize,
+    },
+    Inner {
+        aabb: Aabb,
+        left: usize,
+        right: usize,
+    },
+}
+
+impl BvhNode {
+    fn aabb(&self) -> &Aabb {
+        match self {
+            BvhNode::Leaf { aabb, .. } | BvhNode::Inner { aabb, .. } => aabb,
+        }
+    }
+}
+
+/// Bounding volume hierarchy over triangles for nearest-surface queries
+struct Bvh {
+    triangles: Vec<Triangle>,
+    nodes: Vec<BvhNode>,
+}
+
+const BVH_LEAF_SIZE: usize = 4;
+
+impl Bvh {
+    /// Build the hierarchy by median splits along the longest axis of the centroids
+    fn new(mut triangles: Vec<Triangle>) -> Option<Self> {
+        if triangles.is_empty() {
+            return None;
+        }
+
+        let mut nodes = Vec::new();
+        let len = triangles.len();
+        Self::build(&mut triangles, 0, len, &mut nodes);
+        Some(Bvh { triangles, nodes })
+    }
+
+    /// Build the subtree over `triangles[start..end]` and return its node index
+    fn build(
+        triangles: &mut [Triangle],
+        start: usize,
+        end: usize,
+        nodes: &mut Vec<BvhNode>,
+    ) -> usize {
+        let aabb = triangles[start..end]
+            .iter()
+            .map(Aabb::of_triangle)
+            .reduce(|a, b| a.merge(&b))
+            .expect("BVH nodes are never empty");
+
+        if end - start <= BVH_LEAF_SIZE {
+            nodes.push(BvhNode::Leaf { aabb, start, end });
+            return nodes.len() - 1;
+        }
+
+        // Split at the median centroid along the longest axis
+        let mut centroid_box = Aabb {
+            min: triangles[start].centroid(),
+            max: triangles[start].centroid(),
+        };
+        for triangle in &triangles[start..end] {
+            centroid_box.extend(&triangle.centroid());
+        }
+        let extent = centroid_box.max - centroid_box.min;
+        let axis = if extent.x >= extent.y && extent.x >= extent.z {
+            0
+        } else if extent.y >= extent.z {
+            1
+        } else {
+            2
+        };
+
+        let mid = (start + end) / 2;
+        triangles[start..end].select_nth_unstable_by(mid - start, |a, b| {
+            a.centroid()[axis].total_cmp(&b.centroid()[axis])
+        });
+
+        // Reserve the slot of this node before building the children
+        let index = nodes.len();
+        nodes.push(BvhNode::Leaf { aabb, start, end });
+        let left = Self::build(triangles, start, mid, nodes);
+        let right = Self::build(triangles, mid, end, nodes);
+        nodes[index] = BvhNode::Inner { aabb, left, right };
+        index
+    }
+
+    /// Squared distance from `p` to the nearest triangle
+    fn nearest_distance_squared(&self, p: &Point3<f64>) -> f64 {
+        let mut best = f64::MAX;
+        let mut stack = vec![0];
+
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            if node.aabb().distance_squared(p) >= best {
+                continue;
+            }
+
+            match node {
+                BvhNode::Leaf { start, end, .. } => {
+                    for triangle in &self.triangles[*start..*end] {
+                        best = best.min(triangle.distance_squared(p));
+                    }
+                }
+                BvhNode::Inner { left, right, .. } => {
+                    // Visit the closer child first so the bound tightens quickly
+                    let left_distance = self.nodes[*left].aabb().distance_squared(p);
+                    let right_distance = self.nodes[*right].aabb().distance_squared(p);
+                    if left_distance < right_distance {
+                        stack.push(*right);
+                        stack.push(*left);
+                    } else {
+                        stack.push(*left);
+                        stack.push(*right);
+                    }
+                }
+            }
+        }
+
+        best
+    }
+}
@@ -11,6 +11,42 @@ pub enum Error {
 
     #[error("Rerun error: {0}")]
     Rerun(#[from] rerun::RecordingStreamError),
+
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("Invalid glob pattern: {0}")]
+    Glob(#[from] glob::PatternError),
+
+    #[error("Thread pool error: {0}")]
+    ThreadPool(#[from] rayon::ThreadPoolBuildError),
+
+    #[error("LAS error: {0}")]
+    Las(#[from] las::Error),
+
+    #[error("TIFF error: {0}")]
+    Tiff(#[from] tiff::TiffError),
+}
+
+impl Error {
+    /// Short, stable identifier of the error kind, used in batch summaries
+    pub fn code(&self) -> &'static str {
+        match self {
+            Error::Io(e) => match e.kind() {
+                std::io::ErrorKind::NotFound => "not_found",
+                std::io::ErrorKind::PermissionDenied => "permission_denied",
+                std::io::ErrorKind::InvalidData => "invalid_data",
+                _ => "io",
+            },
+            Error::CommandLine(_) => "command_line",
+            Error::Rerun(_) => "rerun",
+            Error::Json(_) => "json",
+            Error::Glob(_) => "glob",
+            Error::ThreadPool(_) => "thread_pool",
+            Error::Las(_) => "las",
+            Error::Tiff(_) => "tiff",
+        }
+    }
 }
 
 /// Type alias for Result with Error
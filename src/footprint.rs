@@ -0,0 +1,277 @@
+use crate::error::{Error, Result};
+use crate::format::ReadOptions;
+use crate::model::Model;
+use crate::primitives::{Face, SurfaceType, Vertex};
+use crate::simplify::{simplify_footprint, Simplification};
+use crate::triangulate::triangulate_polygon;
+use cgmath::{Point3, Vector3};
+
+/// A footprint polygon: an exterior ring and the rings of its holes.
+///
+/// Rings are open (the first point isn't repeated) and keep the z of the
+/// ground vertices. Exterior rings run counter-clockwise and holes clockwise.
+#[derive(Debug, Clone)]
+pub struct Polygon {
+    pub exterior: Vec<Point3<f64>>,
+    pub holes: Vec<Vec<Point3<f64>>>,
+}
+
+/// The 2D outline of a building's ground surface, one polygon per connected part
+#[derive(Debug, Clone, Default)]
+pub struct Footprint {
+    pub polygons: Vec<Polygon>,
+}
+
+impl Polygon {
+    /// Create a polygon, orienting the exterior counter-clockwise and the holes clockwise
+    pub fn new(mut exterior: Vec<Point3<f64>>, mut holes: Vec<Vec<Point3<f64>>>) -> Self {
+        if signed_area(&exterior) < 0.0 {
+            exterior.reverse();
+        }
+        for hole in &mut holes {
+            if signed_area(hole) > 0.0 {
+                hole.reverse();
+            }
+        }
+        Polygon { exterior, holes }
+    }
+
+    /// Area of the polygon projected on the XY plane, holes excluded
+    pub fn area(&self) -> f64 {
+        signed_area(&self.exterior).abs()
+            - self
+                .holes
+                .iter()
+                .map(|hole| signed_area(hole).abs())
+                .sum::<f64>()
+    }
+
+    /// Whether the point (x, y) lies inside the polygon and outside its holes
+    pub fn contains(&self, x: f64, y: f64) -> bool {
+        point_in_ring(&self.exterior, x, y)
+            && !self.holes.iter().any(|hole| point_in_ring(hole, x, y))
+    }
+}
+
+impl Footprint {
+    /// Footprint of the ground surfaces of a classified model, see `ground_polygons`
+    pub fn from_model(model: &Model) -> Self {
+        let polygons = ground_polygons(model)
+            .into_iter()
+            .map(|rings| {
+                let mut rings = rings
+                    .into_iter()
+                    .map(|ring| ring.iter().map(|&id| model.vertices[id].point).collect());
+                Polygon {
+                    exterior: rings.next().expect("polygon has an exterior ring"),
+                    holes: rings.collect(),
+                }
+            })
+            .collect();
+
+        Footprint { polygons }
+    }
+
+    /// LoD1.2 model of the footprint, extruded from `ground_z` up to `roof_z`.
+    ///
+    /// The footprint becomes the ground surface, which is extruded like the
+    /// ground of a converted LoD2.2 model, so the result is the same solid.
+    pub fn extrude(&self, ground_z: f64, roof_z: f64) -> Model {
+        let mut model = self.to_ground();
+        for vertex in &mut model.vertices {
+            vertex.point.z = ground_z;
+        }
+        model.extrude_to_lod1(roof_z, &[]);
+        model
+    }
+
+    /// Model made of the ground surfaces of the footprint, facing down and
+    /// keeping the z of its points: one face per polygon, or triangles if the
+    /// polygon has holes
+    pub fn to_ground(&self) -> Model {
+        let mut vertices: Vec<Vertex> = Vec::new();
+        let mut faces = Vec::new();
+        for polygon in &self.polygons {
+            let rings: Vec<Vec<usize>> = std::iter::once(&polygon.exterior)
+                .chain(&polygon.holes)
+                .map(|ring| {
+                    ring.iter()
+                        .map(|&point| {
+                            let id = vertices.len();
+                            vertices.push(Vertex { point, id });
+                            id
+                        })
+                        .collect()
+                })
+                .collect();
+
+            // The ground faces down
+            if rings.len() == 1 {
+                faces.push(Face::new(rings[0].iter().rev().copied().collect()));
+            } else {
+                for [a, b, c] in triangulate_polygon(&rings[0], &rings[1..], &vertices) {
+                    faces.push(Face::new(vec![a, c, b]));
+                }
+            }
+        }
+        for face in &mut faces {
+            face.surface_type = SurfaceType::Ground;
+        }
+
+        Model::new(vertices, faces)
+    }
+
+    /// Simplified copy of the footprint, see `simplify_footprint`
+    pub fn simplify(&self, simplification: &Simplification) -> Footprint {
+        simplify_footprint(self, simplification)
+    }
+
+    /// Copy of the footprint moved by `offset`
+    pub fn translated(&self, offset: Vector3<f64>) -> Footprint {
+        let moved = |ring: &Vec<Point3<f64>>| ring.iter().map(|&point| point + offset).collect();
+        Footprint {
+            polygons: self
+                .polygons
+                .iter()
+                .map(|polygon| Polygon {
+                    exterior: moved(&polygon.exterior),
+                    holes: polygon.holes.iter().map(moved).collect(),
+                })
+                .collect(),
+        }
+    }
+
+    /// Whether the footprint has no polygon, e.g. for a model without ground surfaces
+    pub fn is_empty(&self) -> bool {
+        self.polygons.is_empty()
+    }
+
+    /// Total area of the polygons, holes excluded
+    pub fn area(&self) -> f64 {
+        self.polygons.iter().map(Polygon::area).sum()
+    }
+
+    /// Whether the point (x, y) lies inside any of the polygons
+    pub fn contains(&self, x: f64, y: f64) -> bool {
+        self.polygons.iter().any(|polygon| polygon.contains(x, y))
+    }
+}
+
+/// Polygon from rings as read from GeoJSON or WKT: the exterior first, then
+/// the holes, each closed by repeating its first point
+pub(crate) fn polygon_from_rings(rings: Vec<Vec<Point3<f64>>>) -> Result<Polygon> {
+    let mut rings = rings.into_iter().map(|mut ring| {
+        if ring.len() > 1 && ring.first() == ring.last() {
+            ring.pop();
+        }
+        if ring.len() < 3 {
+            return Err(Error::Io(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "Polygon ring must have at least 3 distinct points",
+            )));
+        }
+        Ok(ring)
+    });
+    let exterior = rings.next().unwrap_or_else(|| Ok(Vec::new()))?;
+    Ok(Polygon::new(exterior, rings.collect::<Result<_>>()?))
+}
+
+/// LoD1.2 model of a footprint read with attributes, named `id`.
+///
+/// The height above the ground is the `height_attribute` of `options`, and
+/// the ground z its `ground_attribute`, or else the lowest z of the footprint,
+/// which is 0 for 2D coordinates.
+pub(crate) fn footprint_model(
+    footprint: &Footprint,
+    id: Option<String>,
+    attribute: impl Fn(&str) -> Option<f64>,
+    options: &ReadOptions,
+) -> Result<Model> {
+    let height = attribute(&options.height_attribute).ok_or_else(|| {
+        Error::Io(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!(
+                "Footprint {} has no numeric '{}' attribute",
+                id.as_deref().unwrap_or("<unnamed>"),
+                options.height_attribute
+            ),
+        ))
+    })?;
+    let ground_z = attribute(&options.ground_attribute).unwrap_or_else(|| {
+        footprint
+            .polygons
+            .iter()
+            .flat_map(|polygon| &polygon.exterior)
+            .map(|point| point.z)
+            .reduce(f64::min)
+            .unwrap_or(0.0)
+    });
+
+    let mut model = footprint.extrude(ground_z, ground_z + height);
+    model.id = id;
+    Ok(model)
+}
+
+/// Boundary loops of the ground surfaces of a model, nested into polygons.
+///
+/// Every polygon is a list of rings of vertex IDs: its exterior ring, running
+/// counter-clockwise, followed by its holes, running clockwise. Loops lying
+/// inside another loop become its holes, and islands inside a hole become
+/// polygons of their own.
+pub(crate) fn ground_polygons(model: &Model) -> Vec<Vec<Vec<usize>>> {
+    let points = |ring: &[usize]| -> Vec<Point3<f64>> {
+        ring.iter().map(|&id| model.vertices[id].point).collect()
+    };
+    let mut loops: Vec<(f64, Vec<usize>)> = model
+        .boundary_loops()
+        .into_iter()
+        .map(|ring| (signed_area(&points(&ring)), ring))
+        .collect();
+    // Largest first, so every loop comes after the loops that may contain it
+    loops.sort_by(|a, b| b.0.abs().total_cmp(&a.0.abs()));
+
+    let mut polygons: Vec<Vec<Vec<usize>>> = Vec::new();
+    for (area, mut ring) in loops {
+        let p = model.vertices[ring[0]].point;
+        let container = polygons.iter_mut().find(|rings| {
+            point_in_ring(&points(&rings[0]), p.x, p.y)
+                && !rings[1..]
+                    .iter()
+                    .any(|hole| point_in_ring(&points(hole), p.x, p.y))
+        });
+        if (area > 0.0) == container.is_some() {
+            ring.reverse();
+        }
+        match container {
+            Some(rings) => rings.push(ring),
+            None => polygons.push(vec![ring]),
+        }
+    }
+
+    polygons
+}
+
+/// Signed area of a ring projected on the XY plane, positive if counter-clockwise
+pub fn signed_area(ring: &[Point3<f64>]) -> f64 {
+    let mut area = 0.0;
+    for i in 0..ring.len() {
+        let a = ring[i];
+        let b = ring[(i + 1) % ring.len()];
+        area += a.x * b.y - b.x * a.y;
+    }
+    area / 2.0
+}
+
+/// Whether the point (x, y) lies inside a ring projected on the XY plane, by ray casting
+pub fn point_in_ring(ring: &[Point3<f64>], x: f64, y: f64) -> bool {
+    let mut inside = false;
+    let mut j = ring.len().wrapping_sub(1);
+    for i in 0..ring.len() {
+        let (a, b) = (ring[i], ring[j]);
+        if (a.y > y) != (b.y > y) && x < (b.x - a.x) * (y - a.y) / (b.y - a.y) + a.x {
+            inside = !inside;
+        }
+        j = i;
+    }
+    inside
+}
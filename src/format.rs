@@ -0,0 +1,603 @@
+use crate::cityjson::{CityJsonReader, CityJsonSeqWriter};
+use crate::error::{Error, Result};
+use crate::geojson::{GeoJsonReader, GeoJsonWriter};
+use crate::gltf::GlbWriter;
+use crate::model::Model;
+use crate::obj::{ObjReader, ObjWriter};
+use crate::ply::{PlyReader, PlyWriter};
+use crate::stl::{is_binary_head, StlReader, StlWriter};
+use crate::transform::AffineTransform;
+use crate::wkt::WktReader;
+use crate::{FOOTPRINT_GROUND_ATTRIBUTE, FOOTPRINT_HEIGHT_ATTRIBUTE, WELD_TOLERANCE};
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+
+/// Path standing for stdin or stdout
+pub const STDIO_PATH: &str = "-";
+
+/// Buildings read from an input, in input order
+pub type ModelStream<'a> = Box<dyn Iterator<Item = Result<Model>> + 'a>;
+
+/// Encoding of formats that exist as text and binary
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Encoding {
+    Ascii,
+    #[default]
+    Binary,
+}
+
+/// Options shared by the model writers
+#[derive(Debug, Clone)]
+pub struct WriteOptions {
+    /// Distance under which output vertices are welded together
+    pub weld_tolerance: f64,
+    /// Encoding of formats that exist as text and binary
+    pub encoding: Encoding,
+}
+
+impl Default for WriteOptions {
+    fn default() -> Self {
+        WriteOptions {
+            weld_tolerance: WELD_TOLERANCE,
+            encoding: Encoding::default(),
+        }
+    }
+}
+
+/// Options shared by the model readers
+#[derive(Debug, Clone)]
+pub struct ReadOptions {
+    /// Attribute of footprint inputs holding the ground z
+    pub ground_attribute: String,
+    /// Attribute of footprint inputs holding the building height above the ground
+    pub height_attribute: String,
+    /// EPSG code of the input coordinates, used when the input doesn't give one
+    pub crs: Option<u32>,
+    /// Affine transform applied to the world coordinates of every building
+    pub transform: Option<AffineTransform>,
+}
+
+impl Default for ReadOptions {
+    fn default() -> Self {
+        ReadOptions {
+            ground_attribute: FOOTPRINT_GROUND_ATTRIBUTE.to_string(),
+            height_attribute: FOOTPRINT_HEIGHT_ATTRIBUTE.to_string(),
+            crs: None,
+            transform: None,
+        }
+    }
+}
+
+/// Reads buildings of one format from any buffered reader
+pub trait ModelReader: Send + Sync {
+    /// Start reading buildings from `input`
+    fn read<'a>(
+        &self,
+        input: Box<dyn BufRead + 'a>,
+        options: &ReadOptions,
+    ) -> Result<ModelStream<'a>>;
+}
+
+/// Creates writers of buildings in one format on any writer
+pub trait ModelWriter: Send + Sync {
+    /// Start writing buildings to `output`
+    fn create<'a>(
+        &self,
+        output: Box<dyn Write + 'a>,
+        options: &WriteOptions,
+    ) -> Result<Box<dyn ModelSink + 'a>>;
+}
+
+/// Incremental writer of buildings, as returned by `ModelWriter::create`
+pub trait ModelSink {
+    /// Append a building
+    fn write_model(&mut self, model: &Model) -> Result<()>;
+
+    /// Append a building reprojected to the EPSG code `target`, see `Model::reproject`
+    fn write_reprojected(&mut self, mut model: Model, target: u32) -> Result<()> {
+        model.reproject(target)?;
+        self.write_model(&model)
+    }
+
+    /// Write anything buffered and flush the output
+    fn finish(self: Box<Self>) -> Result<()>;
+}
+
+/// A format known to a `FormatRegistry`
+pub struct RegisteredFormat {
+    pub name: &'static str,
+    pub extensions: &'static [&'static str],
+    pub reader: Option<Box<dyn ModelReader>>,
+    pub writer: Option<Box<dyn ModelWriter>>,
+}
+
+/// Formats available for reading and writing, looked up by name or file extension.
+///
+/// `FormatRegistry::default()` holds the built-in formats: OBJ, STL, PLY,
+/// CityJSONSeq, GeoJSON footprints, CityJSON and CSV with WKT footprints for
+/// reading only, and GLB for writing only. Paths with an unknown extension are
+/// treated as OBJ.
+pub struct FormatRegistry {
+    formats: Vec<RegisteredFormat>,
+    fallback: &'static str,
+}
+
+impl Default for FormatRegistry {
+    fn default() -> Self {
+        let mut registry = FormatRegistry::new("obj");
+        registry.register(RegisteredFormat {
+            name: "obj",
+            extensions: &["obj"],
+            reader: Some(Box::new(ObjFormat)),
+            writer: Some(Box::new(ObjFormat)),
+        });
+        registry.register(RegisteredFormat {
+            name: "stl",
+            extensions: &["stl"],
+            reader: Some(Box::new(StlFormat)),
+            writer: Some(Box::new(StlFormat)),
+        });
+        registry.register(RegisteredFormat {
+            name: "ply",
+            extensions: &["ply"],
+            reader: Some(Box::new(PlyFormat)),
+            writer: Some(Box::new(PlyFormat)),
+        });
+        registry.register(RegisteredFormat {
+            name: "cityjsonseq",
+            extensions: &["jsonl"],
+            reader: Some(Box::new(CityJsonFormat)),
+            writer: Some(Box::new(CityJsonFormat)),
+        });
+        registry.register(RegisteredFormat {
+            name: "cityjson",
+            extensions: &["json"],
+            reader: Some(Box::new(CityJsonFormat)),
+            writer: None,
+        });
+        registry.register(RegisteredFormat {
+            name: "glb",
+            extensions: &["glb"],
+            reader: None,
+            writer: Some(Box::new(GlbFormat)),
+        });
+        registry.register(RegisteredFormat {
+            name: "geojson",
+            extensions: &["geojson"],
+            reader: Some(Box::new(GeoJsonFormat)),
+            writer: Some(Box::new(GeoJsonFormat)),
+        });
+        registry.register(RegisteredFormat {
+            name: "wkt",
+            extensions: &["csv"],
+            reader: Some(Box::new(WktFormat)),
+            writer: None,
+        });
+        registry
+    }
+}
+
+impl FormatRegistry {
+    /// Create an empty registry using `fallback` for paths with an unknown extension
+    pub fn new(fallback: &'static str) -> Self {
+        FormatRegistry {
+            formats: Vec::new(),
+            fallback,
+        }
+    }
+
+    /// Add a format, replacing any format with the same name
+    pub fn register(&mut self, format: RegisteredFormat) {
+        self.formats.retain(|existing| existing.name != format.name);
+        self.formats.push(format);
+    }
+
+    /// Names of the registered formats
+    pub fn names(&self) -> Vec<&'static str> {
+        self.formats.iter().map(|format| format.name).collect()
+    }
+
+    /// Extensions of the formats that can be read
+    pub fn input_extensions(&self) -> Vec<&'static str> {
+        self.formats
+            .iter()
+            .filter(|format| format.reader.is_some())
+            .flat_map(|format| format.extensions.iter().copied())
+            .collect()
+    }
+
+    /// Find a format by its name, case-insensitively
+    pub fn by_name(&self, name: &str) -> Result<&RegisteredFormat> {
+        self.formats
+            .iter()
+            .find(|format| format.name.eq_ignore_ascii_case(name))
+            .ok_or_else(|| {
+                Error::Io(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    format!(
+                        "Unknown format '{}', expected one of: {}",
+                        name,
+                        self.names().join(", ")
+                    ),
+                ))
+            })
+    }
+
+    /// Find a format by the extension of `path`
+    pub fn by_extension(&self, path: &Path) -> Option<&RegisteredFormat> {
+        let extension = path.extension()?.to_string_lossy().to_ascii_lowercase();
+        self.formats
+            .iter()
+            .find(|format| format.extensions.contains(&extension.as_str()))
+    }
+
+    /// Select a format by explicit name if given, or else by the extension of `path`
+    pub fn select(&self, path: &Path, name: Option<&str>) -> Result<&RegisteredFormat> {
+        if let Some(name) = name {
+            return self.by_name(name);
+        }
+        match self.by_extension(path) {
+            Some(format) => Ok(format),
+            None => self.by_name(self.fallback),
+        }
+    }
+
+    /// Reader for `path`, selected as in `select`
+    pub fn reader(&self, path: &Path, name: Option<&str>) -> Result<&dyn ModelReader> {
+        self.select(path, name)?.reader()
+    }
+
+    /// Writer for `path`, selected as in `select`
+    pub fn writer(&self, path: &Path, name: Option<&str>) -> Result<&dyn ModelWriter> {
+        self.select(path, name)?.writer()
+    }
+
+    /// Open an input path, or stdin for `-`, and select its format.
+    ///
+    /// The format is given by `name`, or else by the extension of the path, or
+    /// else guessed from the first bytes of the content, falling back to the
+    /// registry's fallback format.
+    pub fn open_input(
+        &self,
+        path: &Path,
+        name: Option<&str>,
+    ) -> Result<(&RegisteredFormat, Box<dyn BufRead>)> {
+        let mut input = open_input(path)?;
+        let format = match (name, self.by_extension(path)) {
+            (Some(name), _) => self.by_name(name)?,
+            (None, Some(format)) => format,
+            (None, None) => match sniff_format(input.as_mut())? {
+                Some(name) => self.by_name(name)?,
+                None => self.by_name(self.fallback)?,
+            },
+        };
+        Ok((format, input))
+    }
+
+    /// Read the buildings of a file, or of stdin for `-`, selecting the format as in `open_input`
+    pub fn read_file(
+        &self,
+        path: &Path,
+        name: Option<&str>,
+        options: &ReadOptions,
+    ) -> Result<ModelStream<'static>> {
+        let (format, input) = self.open_input(path, name)?;
+        format.reader()?.read(input, options)
+    }
+}
+
+impl RegisteredFormat {
+    /// The reader of the format, or an error if it can't be read
+    pub fn reader(&self) -> Result<&dyn ModelReader> {
+        self.reader.as_deref().ok_or_else(|| {
+            Error::Io(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                format!("Reading {} is not supported", self.name.to_uppercase()),
+            ))
+        })
+    }
+
+    /// The writer of the format, or an error if it can't be written
+    pub fn writer(&self) -> Result<&dyn ModelWriter> {
+        self.writer.as_deref().ok_or_else(|| {
+            Error::Io(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                format!("Writing {} is not supported", self.name.to_uppercase()),
+            ))
+        })
+    }
+}
+
+/// Read the buildings of a file, or of stdin for `-`, with the built-in formats.
+///
+/// The format is selected as in `FormatRegistry::open_input`.
+pub fn read_models(
+    path: &Path,
+    format: Option<&str>,
+    options: &ReadOptions,
+) -> Result<ModelStream<'static>> {
+    FormatRegistry::default().read_file(path, format, options)
+}
+
+/// Tag the buildings read by a reader with the CRS of `options`, transform
+/// them and recentre them on a local origin.
+///
+/// Readers give world coordinates; afterwards every building keeps its own
+/// horizontal offset in `Model::origin`, which writers add back.
+fn localize<'a>(models: ModelStream<'a>, options: &ReadOptions) -> ModelStream<'a> {
+    let (crs, transform) = (options.crs, options.transform);
+    Box::new(models.map(move |model| {
+        let mut model = model?;
+        model.crs = model.crs.or(crs);
+        if let Some(transform) = &transform {
+            model.transform(transform);
+        }
+        model.recentre();
+        Ok(model)
+    }))
+}
+
+/// Open a file for reading, with the path in the error message
+pub(crate) fn open_file(path: &Path) -> Result<File> {
+    File::open(path).map_err(|e| {
+        Error::Io(std::io::Error::new(
+            e.kind(),
+            format!("Failed to open file {}: {}", path.display(), e),
+        ))
+    })
+}
+
+/// Whether a path stands for stdin or stdout
+pub fn is_stdio(path: &Path) -> bool {
+    path.as_os_str() == STDIO_PATH
+}
+
+/// Open a path for buffered reading, or stdin for `-`
+pub fn open_input(path: &Path) -> Result<Box<dyn BufRead>> {
+    if is_stdio(path) {
+        return Ok(Box::new(std::io::stdin().lock()));
+    }
+    Ok(Box::new(BufReader::new(open_file(path)?)))
+}
+
+/// Create a path for buffered writing, or stdout for `-`
+pub fn create_output(path: &Path) -> Result<Box<dyn Write>> {
+    if is_stdio(path) {
+        return Ok(Box::new(BufWriter::new(std::io::stdout().lock())));
+    }
+    Ok(Box::new(BufWriter::new(create_file(path)?)))
+}
+
+/// Guess the format of an input from its first bytes, without consuming them
+fn sniff_format(input: &mut dyn BufRead) -> Result<Option<&'static str>> {
+    let raw = input.fill_buf().map_err(Error::Io)?;
+    let head = raw.trim_ascii_start();
+    let contains = |needle: &[u8]| head.windows(needle.len()).any(|window| window == needle);
+    Ok(
+        if head.starts_with(b"{") && !contains(b"CityJSON") && contains(b"\"Feature") {
+            Some("geojson")
+        } else if head.starts_with(b"{") {
+            Some("cityjsonseq")
+        } else if head.starts_with(b"ply") {
+            Some("ply")
+        } else if head.starts_with(b"solid") || is_binary_head(raw) {
+            Some("stl")
+        } else {
+            None
+        },
+    )
+}
+
+/// Create a file for writing, with the path in the error message
+pub(crate) fn create_file(path: &Path) -> Result<File> {
+    File::create(path).map_err(|e| {
+        Error::Io(std::io::Error::new(
+            e.kind(),
+            format!("Failed to create file {}: {}", path.display(), e),
+        ))
+    })
+}
+
+struct ObjFormat;
+struct StlFormat;
+struct PlyFormat;
+struct GlbFormat;
+struct CityJsonFormat;
+struct GeoJsonFormat;
+struct WktFormat;
+
+impl ModelReader for CityJsonFormat {
+    fn read<'a>(
+        &self,
+        input: Box<dyn BufRead + 'a>,
+        options: &ReadOptions,
+    ) -> Result<ModelStream<'a>> {
+        Ok(localize(Box::new(CityJsonReader::new(input)), options))
+    }
+}
+
+impl ModelWriter for CityJsonFormat {
+    fn create<'a>(
+        &self,
+        output: Box<dyn Write + 'a>,
+        options: &WriteOptions,
+    ) -> Result<Box<dyn ModelSink + 'a>> {
+        Ok(Box::new(CityJsonSeqWriter::new(
+            output,
+            options.weld_tolerance,
+        )))
+    }
+}
+
+impl ModelReader for ObjFormat {
+    fn read<'a>(
+        &self,
+        input: Box<dyn BufRead + 'a>,
+        options: &ReadOptions,
+    ) -> Result<ModelStream<'a>> {
+        Ok(localize(Box::new(ObjReader::new(input)), options))
+    }
+}
+
+impl ModelWriter for ObjFormat {
+    fn create<'a>(
+        &self,
+        output: Box<dyn Write + 'a>,
+        options: &WriteOptions,
+    ) -> Result<Box<dyn ModelSink + 'a>> {
+        Ok(Box::new(ObjWriter::new(output, options.weld_tolerance)?))
+    }
+}
+
+impl ModelReader for StlFormat {
+    fn read<'a>(
+        &self,
+        input: Box<dyn BufRead + 'a>,
+        options: &ReadOptions,
+    ) -> Result<ModelStream<'a>> {
+        let models = StlReader::new(input).read()?;
+        Ok(localize(Box::new(models.into_iter().map(Ok)), options))
+    }
+}
+
+impl ModelWriter for StlFormat {
+    fn create<'a>(
+        &self,
+        output: Box<dyn Write + 'a>,
+        options: &WriteOptions,
+    ) -> Result<Box<dyn ModelSink + 'a>> {
+        Ok(Box::new(StlWriter::new(output, options.encoding)))
+    }
+}
+
+impl ModelReader for PlyFormat {
+    fn read<'a>(
+        &self,
+        input: Box<dyn BufRead + 'a>,
+        options: &ReadOptions,
+    ) -> Result<ModelStream<'a>> {
+        let model = PlyReader::new(input).read();
+        Ok(localize(Box::new(std::iter::once(model)), options))
+    }
+}
+
+impl ModelWriter for PlyFormat {
+    fn create<'a>(
+        &self,
+        output: Box<dyn Write + 'a>,
+        options: &WriteOptions,
+    ) -> Result<Box<dyn ModelSink + 'a>> {
+        Ok(Box::new(PlyWriter::new(
+            output,
+            options.encoding,
+            options.weld_tolerance,
+        )))
+    }
+}
+
+impl ModelWriter for GlbFormat {
+    fn create<'a>(
+        &self,
+        output: Box<dyn Write + 'a>,
+        _options: &WriteOptions,
+    ) -> Result<Box<dyn ModelSink + 'a>> {
+        Ok(Box::new(GlbWriter::new(output)))
+    }
+}
+
+impl ModelReader for GeoJsonFormat {
+    fn read<'a>(
+        &self,
+        input: Box<dyn BufRead + 'a>,
+        options: &ReadOptions,
+    ) -> Result<ModelStream<'a>> {
+        let models = GeoJsonReader::new(input, options).read()?;
+        Ok(localize(Box::new(models.into_iter().map(Ok)), options))
+    }
+}
+
+impl ModelReader for WktFormat {
+    fn read<'a>(
+        &self,
+        input: Box<dyn BufRead + 'a>,
+        options: &ReadOptions,
+    ) -> Result<ModelStream<'a>> {
+        let models = WktReader::new(input, options).read()?;
+        Ok(localize(Box::new(models.into_iter().map(Ok)), options))
+    }
+}
+
+impl ModelWriter for GeoJsonFormat {
+    fn create<'a>(
+        &self,
+        output: Box<dyn Write + 'a>,
+        _options: &WriteOptions,
+    ) -> Result<Box<dyn ModelSink + 'a>> {
+        Ok(Box::new(GeoJsonWriter::new(output)?))
+    }
+}
+
+impl<W: Write> ModelSink for ObjWriter<W> {
+    fn write_model(&mut self, model: &Model) -> Result<()> {
+        ObjWriter::write_model(self, model)
+    }
+
+    fn finish(self: Box<Self>) -> Result<()> {
+        ObjWriter::finish(*self).map(|_| ())
+    }
+}
+
+impl<W: Write> ModelSink for StlWriter<W> {
+    fn write_model(&mut self, model: &Model) -> Result<()> {
+        StlWriter::write_model(self, model)
+    }
+
+    fn finish(self: Box<Self>) -> Result<()> {
+        StlWriter::finish(*self).map(|_| ())
+    }
+}
+
+impl<W: Write> ModelSink for PlyWriter<W> {
+    fn write_model(&mut self, model: &Model) -> Result<()> {
+        PlyWriter::write_model(self, model)
+    }
+
+    fn finish(self: Box<Self>) -> Result<()> {
+        PlyWriter::finish(*self).map(|_| ())
+    }
+}
+
+impl<W: Write> ModelSink for GlbWriter<W> {
+    fn write_model(&mut self, model: &Model) -> Result<()> {
+        GlbWriter::write_model(self, model)
+    }
+
+    fn finish(self: Box<Self>) -> Result<()> {
+        GlbWriter::finish(*self).map(|_| ())
+    }
+}
+
+impl<W: Write> ModelSink for CityJsonSeqWriter<W> {
+    fn write_model(&mut self, model: &Model) -> Result<()> {
+        CityJsonSeqWriter::write_model(self, model)
+    }
+
+    fn finish(self: Box<Self>) -> Result<()> {
+        CityJsonSeqWriter::finish(*self).map(|_| ())
+    }
+}
+
+impl<W: Write> ModelSink for GeoJsonWriter<W> {
+    fn write_model(&mut self, model: &Model) -> Result<()> {
+        GeoJsonWriter::write_model(self, model)
+    }
+
+    fn write_reprojected(&mut self, model: Model, target: u32) -> Result<()> {
+        GeoJsonWriter::write_model_reprojected(self, &model, target)
+    }
+
+    fn finish(self: Box<Self>) -> Result<()> {
+        GeoJsonWriter::finish(*self).map(|_| ())
+    }
+}
@@ -0,0 +1,384 @@
+use cgmath::{EuclideanSpace, Matrix3, Point3, SquareMatrix, Vector3};
+use std::f64::consts::{FRAC_PI_2, FRAC_PI_4};
+
+/// Approximate height of the geoid above the WGS84 ellipsoid in the Netherlands,
+/// used to turn NAP heights into ellipsoidal heights
+pub const NAP_GEOID_HEIGHT: f64 = 43.0;
+
+/// Approximate length of a degree of latitude in metres, used to turn metric
+/// tolerances into degrees
+const METRES_PER_DEGREE: f64 = 111_320.0;
+
+/// Semi-major axis of the WGS84 ellipsoid in metres
+const WGS84_A: f64 = 6_378_137.0;
+/// Flattening of the WGS84 ellipsoid
+const WGS84_F: f64 = 1.0 / 298.257_223_563;
+
+/// Semi-major axis of the Bessel 1841 ellipsoid of the Amersfoort datum in metres
+const BESSEL_A: f64 = 6_377_397.155;
+/// Flattening of the Bessel 1841 ellipsoid
+const BESSEL_F: f64 = 1.0 / 299.152_812_8;
+
+/// Latitude and longitude in degrees of the origin of the RD New stereographic projection, on Bessel
+const RD_PROJECTION_LAT0: f64 = 52.156_160_555_555_55;
+const RD_PROJECTION_LON0: f64 = 5.387_638_888_888_89;
+/// Scale factor of the RD New projection at its origin
+const RD_SCALE: f64 = 0.999_907_9;
+
+/// Helmert transformation from Amersfoort to ETRS89 geocentric coordinates in
+/// the position vector convention: translations in metres, rotations in arc
+/// seconds and scale in parts per million
+const AMERSFOORT_TO_ETRS89: [f64; 7] = [
+    565.2369, 50.0087, 465.658, -0.406857, 0.350733, -1.87035, 4.0812,
+];
+
+/// Scale factor of UTM on the central meridian
+const UTM_SCALE: f64 = 0.9996;
+const UTM_FALSE_EASTING: f64 = 500_000.0;
+/// False northing of UTM zones on the southern hemisphere
+const UTM_FALSE_NORTHING_SOUTH: f64 = 10_000_000.0;
+
+/// EPSG codes of RD New, alone and combined with NAP heights
+pub const RD_CRS: [u32; 2] = [28992, 7415];
+
+/// RD coordinates of the Amersfoort reference point
+const RD_X0: f64 = 155_000.0;
+const RD_Y0: f64 = 463_000.0;
+
+/// East, north and up unit vectors in ECEF at a WGS84 latitude and longitude in degrees
+pub fn enu_axes(lat: f64, lon: f64) -> [Vector3<f64>; 3] {
+    let (lat, lon) = (lat.to_radians(), lon.to_radians());
+    [
+        Vector3::new(-lon.sin(), lon.cos(), 0.0),
+        Vector3::new(-lat.sin() * lon.cos(), -lat.sin() * lon.sin(), lat.cos()),
+        Vector3::new(lat.cos() * lon.cos(), lat.cos() * lon.sin(), lat.sin()),
+    ]
+}
+
+/// A coordinate reference system `reproject` converts between
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Crs {
+    /// RD New (EPSG:28992), with NAP heights (EPSG:7415)
+    Rd,
+    /// Geographic WGS84 or ETRS89 (EPSG:4326, 4979, 4258, 4937): x is the
+    /// longitude and y the latitude in degrees, z the ellipsoidal height
+    Geographic,
+    /// Geocentric WGS84 or ETRS89, i.e. ECEF (EPSG:4978, 4936)
+    Geocentric,
+    /// UTM zone on WGS84 (EPSG:326zz and 327zz) or ETRS89 (EPSG:258zz)
+    Utm { zone: u32, north: bool },
+}
+
+impl Crs {
+    /// The reference system of an EPSG code, if supported
+    pub fn from_epsg(code: u32) -> Option<Crs> {
+        match code {
+            28992 | 7415 => Some(Crs::Rd),
+            4326 | 4979 | 4258 | 4937 => Some(Crs::Geographic),
+            4978 | 4936 => Some(Crs::Geocentric),
+            32601..=32660 => Some(Crs::Utm {
+                zone: code - 32600,
+                north: true,
+            }),
+            32701..=32760 => Some(Crs::Utm {
+                zone: code - 32700,
+                north: false,
+            }),
+            25828..=25838 => Some(Crs::Utm {
+                zone: code - 25800,
+                north: true,
+            }),
+            _ => None,
+        }
+    }
+
+    /// Whether x and y are degrees rather than metres
+    pub fn is_geographic(self) -> bool {
+        self == Crs::Geographic
+    }
+
+    /// WGS84 latitude and longitude in radians and ellipsoidal height of a point
+    fn to_geodetic(self, point: Point3<f64>) -> (f64, f64, f64) {
+        match self {
+            Crs::Rd => {
+                let (lat, lon) = RdProjection::new().rd_to_etrs89(point.x, point.y);
+                (lat, lon, point.z + NAP_GEOID_HEIGHT)
+            }
+            Crs::Geographic => (point.y.to_radians(), point.x.to_radians(), point.z),
+            Crs::Geocentric => geodetic(point.to_vec(), WGS84_A, WGS84_F),
+            Crs::Utm { zone, north } => {
+                let (lat, lon) = utm_to_geodetic(point.x, point.y, zone, north);
+                (lat, lon, point.z)
+            }
+        }
+    }
+
+    /// Point at a WGS84 latitude and longitude in radians and ellipsoidal height
+    fn point_at(self, lat: f64, lon: f64, height: f64) -> Point3<f64> {
+        match self {
+            Crs::Rd => {
+                let (x, y) = RdProjection::new().etrs89_to_rd(lat, lon);
+                Point3::new(x, y, height - NAP_GEOID_HEIGHT)
+            }
+            Crs::Geographic => Point3::new(lon.to_degrees(), lat.to_degrees(), height),
+            Crs::Geocentric => Point3::from_vec(geocentric(lat, lon, height, WGS84_A, WGS84_F)),
+            Crs::Utm { zone, north } => {
+                let (x, y) = geodetic_to_utm(lat, lon, zone, north);
+                Point3::new(x, y, height)
+            }
+        }
+    }
+}
+
+/// Horizontal coordinate units in a metre for the EPSG code `crs`: about
+/// 1 / 111 320 for geographic systems, 1 for the others and unknown ones
+pub fn units_per_metre(crs: Option<u32>) -> f64 {
+    match crs.and_then(Crs::from_epsg) {
+        Some(crs) if crs.is_geographic() => 1.0 / METRES_PER_DEGREE,
+        _ => 1.0,
+    }
+}
+
+/// Convert a point from one reference system to another.
+///
+/// RD New is converted with the exact oblique stereographic projection on the
+/// Bessel ellipsoid and a 7-parameter Helmert transformation to ETRS89,
+/// without the RDNAPTRANS correction grid, so to within a few decimetres. NAP
+/// heights are turned into ellipsoidal heights with `NAP_GEOID_HEIGHT`.
+/// ETRS89 and WGS84 are taken to be the same.
+pub fn reproject(point: Point3<f64>, from: Crs, to: Crs) -> Point3<f64> {
+    if from == to {
+        return point;
+    }
+    let (lat, lon, height) = from.to_geodetic(point);
+    to.point_at(lat, lon, height)
+}
+
+/// Geocentric coordinates of a latitude and longitude in radians and an
+/// ellipsoidal height, on the ellipsoid with semi-major axis `a` and flattening `f`
+fn geocentric(lat: f64, lon: f64, height: f64, a: f64, f: f64) -> Vector3<f64> {
+    let e2 = f * (2.0 - f);
+    // Prime vertical radius of curvature
+    let n = a / (1.0 - e2 * lat.sin().powi(2)).sqrt();
+
+    Vector3::new(
+        (n + height) * lat.cos() * lon.cos(),
+        (n + height) * lat.cos() * lon.sin(),
+        (n * (1.0 - e2) + height) * lat.sin(),
+    )
+}
+
+/// Latitude and longitude in radians and ellipsoidal height of geocentric
+/// coordinates, on the ellipsoid with semi-major axis `a` and flattening `f`
+fn geodetic(p: Vector3<f64>, a: f64, f: f64) -> (f64, f64, f64) {
+    let e2 = f * (2.0 - f);
+    let lon = p.y.atan2(p.x);
+    let r = p.x.hypot(p.y);
+    let mut lat = p.z.atan2(r * (1.0 - e2));
+    let mut height = 0.0;
+    // Converges to below a micrometre in a few iterations away from the poles
+    for _ in 0..10 {
+        let n = a / (1.0 - e2 * lat.sin().powi(2)).sqrt();
+        height = r / lat.cos() - n;
+        let next = p.z.atan2(r * (1.0 - e2 * n / (n + height)));
+        let done = (next - lat).abs() < 1e-14;
+        lat = next;
+        if done {
+            break;
+        }
+    }
+    (lat, lon, height)
+}
+
+/// The oblique stereographic projection of RD New on the Bessel ellipsoid,
+/// after EPSG guidance note 7-2 (method 9809), and the datum shift to ETRS89
+struct RdProjection {
+    e: f64,     // eccentricity of the Bessel ellipsoid
+    n: f64,     // exponent of the conformal sphere
+    c: f64,     // constant of the conformal sphere
+    chi0: f64,  // conformal latitude of the origin
+    scale: f64, // radius of the conformal sphere times the scale factor
+    helmert: (Vector3<f64>, Matrix3<f64>),
+}
+
+impl RdProjection {
+    fn new() -> Self {
+        let e2 = BESSEL_F * (2.0 - BESSEL_F);
+        let e = e2.sqrt();
+        let lat0 = RD_PROJECTION_LAT0.to_radians();
+        let sin0 = lat0.sin();
+        let rho0 = BESSEL_A * (1.0 - e2) / (1.0 - e2 * sin0 * sin0).powf(1.5);
+        let nu0 = BESSEL_A / (1.0 - e2 * sin0 * sin0).sqrt();
+        let n = (1.0 + e2 * lat0.cos().powi(4) / (1.0 - e2)).sqrt();
+        let s1 = (1.0 + sin0) / (1.0 - sin0);
+        let s2 = (1.0 - e * sin0) / (1.0 + e * sin0);
+        let w1 = (s1 * s2.powf(e)).powf(n);
+        let sin_chi = (w1 - 1.0) / (w1 + 1.0);
+        let c = (n + sin0) * (1.0 - sin_chi) / ((n - sin0) * (1.0 + sin_chi));
+        let w2 = c * w1;
+
+        // Position vector rotation with small angles, scaled
+        let [tx, ty, tz, rx, ry, rz, ppm] = AMERSFOORT_TO_ETRS89;
+        let (rx, ry, rz) = (
+            (rx / 3600.0).to_radians(),
+            (ry / 3600.0).to_radians(),
+            (rz / 3600.0).to_radians(),
+        );
+        let rotation = Matrix3::new(1.0, rz, -ry, -rz, 1.0, rx, ry, -rx, 1.0) * (1.0 + ppm * 1e-6);
+
+        RdProjection {
+            e,
+            n,
+            c,
+            chi0: ((w2 - 1.0) / (w2 + 1.0)).asin(),
+            scale: (rho0 * nu0).sqrt() * RD_SCALE,
+            helmert: (Vector3::new(tx, ty, tz), rotation),
+        }
+    }
+
+    /// ETRS89 latitude and longitude in radians of RD New coordinates
+    fn rd_to_etrs89(&self, x: f64, y: f64) -> (f64, f64) {
+        let (lat, lon) = self.inverse(x, y);
+        let (translation, rotation) = self.helmert;
+        let p = translation + rotation * geocentric(lat, lon, 0.0, BESSEL_A, BESSEL_F);
+        let (lat, lon, _) = geodetic(p, WGS84_A, WGS84_F);
+        (lat, lon)
+    }
+
+    /// RD New coordinates of an ETRS89 latitude and longitude in radians
+    fn etrs89_to_rd(&self, lat: f64, lon: f64) -> (f64, f64) {
+        let (translation, rotation) = self.helmert;
+        let inverse = rotation
+            .invert()
+            .expect("the Helmert rotation is invertible");
+        let p = inverse * (geocentric(lat, lon, 0.0, WGS84_A, WGS84_F) - translation);
+        let (lat, lon, _) = geodetic(p, BESSEL_A, BESSEL_F);
+        self.forward(lat, lon)
+    }
+
+    /// Project a Bessel latitude and longitude in radians
+    fn forward(&self, lat: f64, lon: f64) -> (f64, f64) {
+        let d_lambda = self.n * (lon - RD_PROJECTION_LON0.to_radians());
+        let sin = lat.sin();
+        let sa = (1.0 + sin) / (1.0 - sin);
+        let sb = (1.0 - self.e * sin) / (1.0 + self.e * sin);
+        let w = self.c * (sa * sb.powf(self.e)).powf(self.n);
+        let chi = ((w - 1.0) / (w + 1.0)).asin();
+        let b = 1.0 + chi.sin() * self.chi0.sin() + chi.cos() * self.chi0.cos() * d_lambda.cos();
+
+        (
+            RD_X0 + 2.0 * self.scale * chi.cos() * d_lambda.sin() / b,
+            RD_Y0
+                + 2.0
+                    * self.scale
+                    * (chi.sin() * self.chi0.cos() - chi.cos() * self.chi0.sin() * d_lambda.cos())
+                    / b,
+        )
+    }
+
+    /// Bessel latitude and longitude in radians of projected coordinates
+    fn inverse(&self, x: f64, y: f64) -> (f64, f64) {
+        let (de, dn) = (x - RD_X0, y - RD_Y0);
+        let g = 2.0 * self.scale * (FRAC_PI_4 - self.chi0 / 2.0).tan();
+        let h = 4.0 * self.scale * self.chi0.tan() + g;
+        let i = (de / (h + dn)).atan();
+        let j = (de / (g - dn)).atan() - i;
+        let chi = self.chi0 + 2.0 * ((dn - de * (j / 2.0).tan()) / (2.0 * self.scale)).atan();
+        let lon = (j + 2.0 * i) / self.n + RD_PROJECTION_LON0.to_radians();
+
+        // Latitude from the isometric latitude, by iteration
+        let e = self.e;
+        let psi = 0.5 * ((1.0 + chi.sin()) / (self.c * (1.0 - chi.sin()))).ln() / self.n;
+        let mut lat = 2.0 * psi.exp().atan() - FRAC_PI_2;
+        for _ in 0..10 {
+            let sin = lat.sin();
+            let psi_i = ((lat / 2.0 + FRAC_PI_4).tan()
+                * ((1.0 - e * sin) / (1.0 + e * sin)).powf(e / 2.0))
+            .ln();
+            let next = lat - (psi_i - psi) * lat.cos() * (1.0 - e * e * sin * sin) / (1.0 - e * e);
+            let done = (next - lat).abs() < 1e-14;
+            lat = next;
+            if done {
+                break;
+            }
+        }
+        (lat, lon)
+    }
+}
+
+/// Coefficients of the Krüger series of the transverse Mercator projection on
+/// WGS84, to the third order of the third flattening: the rectifying radius,
+/// and the forward (alpha), inverse (beta) and latitude (delta) terms
+fn kruger_series() -> (f64, [f64; 3], [f64; 3], [f64; 3]) {
+    let n = WGS84_F / (2.0 - WGS84_F);
+    let (n2, n3) = (n * n, n * n * n);
+    let radius = WGS84_A / (1.0 + n) * (1.0 + n2 / 4.0 + n2 * n2 / 64.0);
+    (
+        radius,
+        [
+            n / 2.0 - 2.0 * n2 / 3.0 + 5.0 * n3 / 16.0,
+            13.0 * n2 / 48.0 - 3.0 * n3 / 5.0,
+            61.0 * n3 / 240.0,
+        ],
+        [
+            n / 2.0 - 2.0 * n2 / 3.0 + 37.0 * n3 / 96.0,
+            n2 / 48.0 + n3 / 15.0,
+            17.0 * n3 / 480.0,
+        ],
+        [
+            2.0 * n - 2.0 * n2 / 3.0 - 2.0 * n3,
+            7.0 * n2 / 3.0 - 8.0 * n3 / 5.0,
+            56.0 * n3 / 15.0,
+        ],
+    )
+}
+
+/// Central meridian of a UTM zone in radians
+fn utm_central_meridian(zone: u32) -> f64 {
+    (zone as f64 * 6.0 - 183.0).to_radians()
+}
+
+/// UTM easting and northing of a WGS84 latitude and longitude in radians
+fn geodetic_to_utm(lat: f64, lon: f64, zone: u32, north: bool) -> (f64, f64) {
+    let (radius, alpha, _, _) = kruger_series();
+    let n = WGS84_F / (2.0 - WGS84_F);
+    let k = 2.0 * n.sqrt() / (1.0 + n);
+    let d_lon = lon - utm_central_meridian(zone);
+    let t = (lat.sin().atanh() - k * (k * lat.sin()).atanh()).sinh();
+    let xi = t.atan2(d_lon.cos());
+    let eta = (d_lon.sin() / (1.0 + t * t).sqrt()).atanh();
+
+    let (mut easting, mut northing) = (eta, xi);
+    for (j, a) in alpha.iter().enumerate() {
+        let m = 2.0 * (j + 1) as f64;
+        easting += a * (m * xi).cos() * (m * eta).sinh();
+        northing += a * (m * xi).sin() * (m * eta).cosh();
+    }
+    let false_northing = if north { 0.0 } else { UTM_FALSE_NORTHING_SOUTH };
+    (
+        UTM_FALSE_EASTING + UTM_SCALE * radius * easting,
+        false_northing + UTM_SCALE * radius * northing,
+    )
+}
+
+/// WGS84 latitude and longitude in radians of a UTM easting and northing
+fn utm_to_geodetic(easting: f64, northing: f64, zone: u32, north: bool) -> (f64, f64) {
+    let (radius, _, beta, delta) = kruger_series();
+    let false_northing = if north { 0.0 } else { UTM_FALSE_NORTHING_SOUTH };
+    let xi = (northing - false_northing) / (UTM_SCALE * radius);
+    let eta = (easting - UTM_FALSE_EASTING) / (UTM_SCALE * radius);
+
+    let (mut xi_prime, mut eta_prime) = (xi, eta);
+    for (j, b) in beta.iter().enumerate() {
+        let m = 2.0 * (j + 1) as f64;
+        xi_prime -= b * (m * xi).sin() * (m * eta).cosh();
+        eta_prime -= b * (m * xi).cos() * (m * eta).sinh();
+    }
+    let chi = (xi_prime.sin() / eta_prime.cosh()).asin();
+    let mut lat = chi;
+    for (j, d) in delta.iter().enumerate() {
+        lat += d * (2.0 * (j + 1) as f64 * chi).sin();
+    }
+    let lon = utm_central_meridian(zone) + eta_prime.sinh().atan2(xi_prime.cos());
+    (lat, lon)
+}
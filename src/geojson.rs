@@ -0,0 +1,299 @@
+use crate::error::{Error, Result};
+use crate::footprint::{footprint_model, polygon_from_rings, Footprint, Polygon};
+use crate::format::ReadOptions;
+use crate::geodesy::Crs;
+use crate::model::{HeightStrategy, Model};
+use crate::primitives::SurfaceType;
+use crate::transform::{epsg_urn, parse_epsg, supported_crs};
+use cgmath::Point3;
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+use std::io::{Read, Write};
+
+/// Attributes written with every footprint
+#[derive(Debug, Clone, Serialize)]
+pub struct FootprintProperties {
+    pub id: Option<String>,
+    pub ground_z: Option<f64>,   // lowest z of the ground surfaces
+    pub roof_height: f64,        // LoD1.2 roof height above ground_z
+    pub absolute_height: f64,    // z of the LoD1.2 roof
+    pub footprint_area: f64,     // area of the footprint, holes excluded
+    pub height_strategy: String, // how absolute_height was derived from the roof
+    #[serde(flatten)]
+    pub attributes: Map<String, Value>, // other attributes of the model, e.g. its roof type
+}
+
+impl FootprintProperties {
+    /// Properties of a classified model, either LoD2.2 or LoD1.2.
+    ///
+    /// The roof height is the one `to_lod1_2` extrudes to, which is the height
+    /// of the flat roof for a model that is already LoD1.2.
+    pub fn from_model(model: &Model) -> Self {
+        let ground_z = model
+            .faces
+            .iter()
+            .filter(|face| face.surface_type == SurfaceType::Ground)
+            .map(|face| face.z_range(&model.vertices).0)
+            .reduce(f64::min);
+        let absolute_height = model.calculate_lod1_2_height();
+
+        FootprintProperties {
+            id: model.id.clone(),
+            ground_z,
+            roof_height: absolute_height - ground_z.unwrap_or(0.0),
+            absolute_height,
+            footprint_area: model.footprint().area(),
+            height_strategy: model
+                .attributes
+                .get("height_strategy")
+                .and_then(Value::as_str)
+                .map_or_else(|| HeightStrategy::default().name(), str::to_string),
+            attributes: model
+                .attributes
+                .iter()
+                .filter(|(name, _)| name.as_str() != "height_strategy")
+                .map(|(name, value)| (name.clone(), value.clone()))
+                .collect(),
+        }
+    }
+}
+
+/// Streaming writer of building footprints as a GeoJSON FeatureCollection.
+///
+/// Every model becomes a feature with a `Polygon` geometry, or a
+/// `MultiPolygon` if its ground surface has several parts, and
+/// `FootprintProperties` as properties. Coordinates are written in the
+/// coordinate system of the model, without z; one feature is written per line.
+/// The CRS of the first model, if known, is written as a `crs` member.
+pub struct GeoJsonWriter<W: Write> {
+    writer: W,
+    count: usize,
+    crs: Option<u32>,
+}
+
+impl<W: Write> GeoJsonWriter<W> {
+    /// Create a writer and start the FeatureCollection
+    pub fn new(mut writer: W) -> Result<Self> {
+        write!(writer, "{{\"type\":\"FeatureCollection\",\"features\":[").map_err(Error::Io)?;
+        Ok(GeoJsonWriter {
+            writer,
+            count: 0,
+            crs: None,
+        })
+    }
+
+    /// Append the footprint of a classified model as a feature
+    pub fn write_model(&mut self, model: &Model) -> Result<()> {
+        self.write_feature(footprint_feature(model)?, model.crs)
+    }
+
+    /// Append the footprint of a classified model as a feature, reprojected to
+    /// the EPSG code `target`.
+    ///
+    /// Only the geometry is reprojected: the properties, such as the footprint
+    /// area and heights, are those of the model in its own CRS, so they stay in
+    /// metres. Geocentric targets are rejected, as a footprint has no x and y there.
+    pub fn write_model_reprojected(&mut self, model: &Model, target: u32) -> Result<()> {
+        if supported_crs(target)? == Crs::Geocentric {
+            return Err(Error::Io(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!(
+                    "GeoJSON footprints can't be written in the geocentric EPSG:{}",
+                    target
+                ),
+            )));
+        }
+        let mut reprojected = model.clone();
+        reprojected.reproject(target)?;
+        let feature = json!({
+            "type": "Feature",
+            "geometry": footprint_geometry(&reprojected),
+            "properties": serde_json::to_value(FootprintProperties::from_model(model))?,
+        });
+        self.write_feature(feature, reprojected.crs)
+    }
+
+    /// Append a feature, keeping `crs` as the CRS of the collection if it is the first
+    fn write_feature(&mut self, feature: Value, crs: Option<u32>) -> Result<()> {
+        let separator = if self.count == 0 { "\n" } else { ",\n" };
+        write!(self.writer, "{}{}", separator, feature).map_err(Error::Io)?;
+        if self.count == 0 {
+            self.crs = crs;
+        }
+        self.count += 1;
+        Ok(())
+    }
+
+    /// Close the FeatureCollection, flush and return the underlying writer
+    pub fn finish(mut self) -> Result<W> {
+        write!(self.writer, "\n]").map_err(Error::Io)?;
+        if let Some(crs) = self.crs {
+            let crs = json!({ "type": "name", "properties": { "name": epsg_urn(crs) } });
+            write!(self.writer, ",\"crs\":{}", crs).map_err(Error::Io)?;
+        }
+        writeln!(self.writer, "}}").map_err(Error::Io)?;
+        self.writer.flush().map_err(Error::Io)?;
+        Ok(self.writer)
+    }
+}
+
+/// GeoJSON feature with the footprint and `FootprintProperties` of a classified model
+pub fn footprint_feature(model: &Model) -> Result<Value> {
+    Ok(json!({
+        "type": "Feature",
+        "geometry": footprint_geometry(model),
+        "properties": serde_json::to_value(FootprintProperties::from_model(model))?,
+    }))
+}
+
+/// `Polygon` or `MultiPolygon` of the footprint of a classified model, or null without one
+fn footprint_geometry(model: &Model) -> Value {
+    let footprint = model.world_footprint();
+    match footprint.polygons.as_slice() {
+        [] => Value::Null,
+        [polygon] => json!({
+            "type": "Polygon",
+            "coordinates": polygon_coordinates(polygon),
+        }),
+        polygons => json!({
+            "type": "MultiPolygon",
+            "coordinates": polygons.iter().map(polygon_coordinates).collect::<Vec<_>>(),
+        }),
+    }
+}
+
+fn polygon_coordinates(polygon: &Polygon) -> Vec<Vec<[f64; 2]>> {
+    std::iter::once(&polygon.exterior)
+        .chain(&polygon.holes)
+        .map(|ring| ring_coordinates(ring))
+        .collect()
+}
+
+/// Positions of a closed GeoJSON ring, repeating the first point at the end
+fn ring_coordinates(ring: &[Point3<f64>]) -> Vec<[f64; 2]> {
+    ring.iter()
+        .chain(ring.first())
+        .map(|point| [point.x, point.y])
+        .collect()
+}
+
+/// Reader for building footprints in a GeoJSON FeatureCollection or Feature.
+///
+/// Every feature with a `Polygon` or `MultiPolygon` geometry becomes a LoD1.2
+/// model, extruded from its ground z up by its height, both taken from the
+/// feature properties named in `ReadOptions`. Without a ground z property the
+/// lowest z of the coordinates is used, or 0 for 2D coordinates. Features
+/// without geometry are skipped. The EPSG code of a `crs` member, as in
+/// GeoJSON files written before RFC 7946, becomes the CRS of the models.
+pub struct GeoJsonReader<R: Read> {
+    reader: R,
+    options: ReadOptions,
+}
+
+impl<R: Read> GeoJsonReader<R> {
+    /// Create a reader over GeoJSON content
+    pub fn new(reader: R, options: &ReadOptions) -> Self {
+        GeoJsonReader {
+            reader,
+            options: options.clone(),
+        }
+    }
+
+    /// Read every feature as a separate model
+    pub fn read(self) -> Result<Vec<Model>> {
+        let value: Value = serde_json::from_reader(self.reader)?;
+        let features = match value["type"].as_str() {
+            Some("FeatureCollection") => value["features"]
+                .as_array()
+                .ok_or_else(|| invalid_data("FeatureCollection has no features array"))?
+                .iter()
+                .collect(),
+            Some("Feature") => vec![&value],
+            _ => return Err(invalid_data("Not a GeoJSON FeatureCollection or Feature")),
+        };
+
+        let crs = value["crs"]["properties"]["name"]
+            .as_str()
+            .and_then(parse_epsg);
+        features
+            .into_iter()
+            .filter(|feature| !feature["geometry"].is_null())
+            .map(|feature| {
+                let mut model = read_feature(feature, &self.options)?;
+                model.crs = crs;
+                Ok(model)
+            })
+            .collect()
+    }
+}
+
+/// Extrude the footprint of a GeoJSON feature
+fn read_feature(feature: &Value, options: &ReadOptions) -> Result<Model> {
+    let properties = &feature["properties"];
+    // The feature ID, or else an `id` property as written by `GeoJsonWriter`
+    let id = [&feature["id"], &properties["id"]]
+        .into_iter()
+        .find_map(|id| match id {
+            Value::String(id) => Some(id.clone()),
+            Value::Number(id) => Some(id.to_string()),
+            _ => None,
+        });
+
+    let geometry = &feature["geometry"];
+    let coordinates = &geometry["coordinates"];
+    let polygons = match geometry["type"].as_str() {
+        Some("Polygon") => vec![parse_polygon(coordinates)?],
+        Some("MultiPolygon") => coordinates
+            .as_array()
+            .ok_or_else(|| invalid_data("MultiPolygon coordinates must be an array"))?
+            .iter()
+            .map(parse_polygon)
+            .collect::<Result<_>>()?,
+        other => {
+            return Err(invalid_data(&format!(
+                "Unsupported footprint geometry type {}, expected Polygon or MultiPolygon",
+                other.unwrap_or("<none>")
+            )))
+        }
+    };
+
+    let attribute = |name: &str| match &properties[name] {
+        Value::Number(value) => value.as_f64(),
+        Value::String(value) => value.trim().parse().ok(),
+        _ => None,
+    };
+    footprint_model(&Footprint { polygons }, id, attribute, options)
+}
+
+/// Parse the coordinates of a GeoJSON polygon
+fn parse_polygon(coordinates: &Value) -> Result<Polygon> {
+    let rings = coordinates
+        .as_array()
+        .ok_or_else(|| invalid_data("Polygon coordinates must be an array of rings"))?
+        .iter()
+        .map(|ring| {
+            ring.as_array()
+                .ok_or_else(|| invalid_data("Polygon ring must be an array of positions"))?
+                .iter()
+                .map(parse_position)
+                .collect::<Result<Vec<_>>>()
+        })
+        .collect::<Result<Vec<_>>>()?;
+    polygon_from_rings(rings)
+}
+
+/// Parse a GeoJSON position, with z 0 if it is 2D
+fn parse_position(position: &Value) -> Result<Point3<f64>> {
+    let coordinate = |i: usize| position.get(i).and_then(Value::as_f64);
+    match (coordinate(0), coordinate(1)) {
+        (Some(x), Some(y)) => Ok(Point3::new(x, y, coordinate(2).unwrap_or(0.0))),
+        _ => Err(invalid_data(&format!("Invalid position: {}", position))),
+    }
+}
+
+fn invalid_data(message: &str) -> Error {
+    Error::Io(std::io::Error::new(
+        std::io::ErrorKind::InvalidData,
+        message.to_string(),
+    ))
+}
@@ -0,0 +1,392 @@
+use crate::error::{Error, Result};
+use crate::model::Model;
+use crate::primitives::{SurfaceType, Vertex};
+use crate::triangulate::{newell_normal, triangulate_face};
+use cgmath::{EuclideanSpace, InnerSpace, Point3, Vector3};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::io::Write;
+
+/// Surface types in material order; a model gets one primitive per type it contains
+const MATERIAL_SURFACE_TYPES: [SurfaceType; 5] = [
+    SurfaceType::Ground,
+    SurfaceType::Wall,
+    SurfaceType::Roof,
+    SurfaceType::Floor,
+    SurfaceType::Unknown,
+];
+
+const GLB_MAGIC: u32 = 0x4654_6C67; // "glTF"
+const GLB_VERSION: u32 = 2;
+const CHUNK_JSON: u32 = 0x4E4F_534A; // "JSON"
+const CHUNK_BIN: u32 = 0x004E_4942; // "BIN\0"
+
+const COMPONENT_UNSIGNED_INT: u32 = 5125;
+const COMPONENT_FLOAT: u32 = 5126;
+const TARGET_ARRAY_BUFFER: u32 = 34962;
+const TARGET_ELEMENT_ARRAY_BUFFER: u32 = 34963;
+const MODE_TRIANGLES: u32 = 4;
+
+/// Write models as a binary glTF 2.0 (GLB) file.
+///
+/// Every model becomes a node with its own mesh, and faces are triangulated
+/// with flat normals. Each `SurfaceType` gets a material with the colour used
+/// by `Model::visualize`. Coordinates are recentred on the centre of the
+/// bounding box of all models before converting to f32, and the offset is
+/// stored in `scenes[0].extras.offset`. glTF is Y-up, so model coordinates
+/// `(x, y, z)` are written as `(x, z, -y)`; the offset stays in model axes.
+pub fn write_glb<W: Write>(models: &[Model], mut writer: W) -> Result<()> {
+    let offset = bbox_center(models);
+    let mut builder = GltfBuilder::default();
+
+    let mut nodes = Vec::new();
+    for model in models {
+        if let Some(mesh) = builder.add_mesh(model, |point| point - offset, None) {
+            let mut node = json!({ "mesh": mesh });
+            if let Some(id) = &model.id {
+                node["name"] = json!(id);
+            }
+            nodes.push(node);
+        }
+    }
+
+    let node_indices: Vec<usize> = (0..nodes.len()).collect();
+    let document = builder.into_document(
+        nodes,
+        json!({
+            "nodes": node_indices,
+            "extras": { "offset": [offset.x, offset.y, offset.z] },
+        }),
+    );
+
+    writer.write_all(&document.to_glb()?).map_err(Error::Io)?;
+    writer.flush().map_err(Error::Io)?;
+    Ok(())
+}
+
+/// Incremental GLB writer.
+///
+/// The binary buffer of a GLB file is packed at once, so models are buffered
+/// until `finish`, which writes them with `write_glb`.
+pub struct GlbWriter<W: Write> {
+    writer: W,
+    models: Vec<Model>,
+}
+
+impl<W: Write> GlbWriter<W> {
+    /// Create a writer
+    pub fn new(writer: W) -> Self {
+        GlbWriter {
+            writer,
+            models: Vec::new(),
+        }
+    }
+
+    /// Append a model
+    pub fn write_model(&mut self, model: &Model) -> Result<()> {
+        self.models.push(model.clone());
+        Ok(())
+    }
+
+    /// Write the buffered models and return the underlying writer
+    pub fn finish(mut self) -> Result<W> {
+        write_glb(&self.models, &mut self.writer)?;
+        Ok(self.writer)
+    }
+}
+
+/// Centre of the bounding box of all vertices, used as the local origin
+fn bbox_center(models: &[Model]) -> Point3<f64> {
+    let mut min = Point3::new(f64::MAX, f64::MAX, f64::MAX);
+    let mut max = Point3::new(f64::MIN, f64::MIN, f64::MIN);
+    for model in models {
+        for vertex in &model.vertices {
+            let p = model.world_point(vertex.point);
+            min = Point3::new(min.x.min(p.x), min.y.min(p.y), min.z.min(p.z));
+            max = Point3::new(max.x.max(p.x), max.y.max(p.y), max.z.max(p.z));
+        }
+    }
+
+    if min.x > max.x {
+        return Point3::new(0.0, 0.0, 0.0); // no vertices
+    }
+    Point3::new(
+        (min.x + max.x) * 0.5,
+        (min.y + max.y) * 0.5,
+        (min.z + max.z) * 0.5,
+    )
+}
+
+/// Convert a Z-up model vector to glTF's Y-up axes
+fn to_y_up(v: Vector3<f64>) -> [f32; 3] {
+    [v.x as f32, v.z as f32, -v.y as f32]
+}
+
+/// Convert an sRGB colour channel to the linear value glTF expects
+fn srgb_to_linear(channel: u8) -> f64 {
+    let c = channel as f64 / 255.0;
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Accumulates the binary buffer and JSON arrays of a glTF document
+#[derive(Default)]
+pub(crate) struct GltfBuilder {
+    bin: Vec<u8>,
+    buffer_views: Vec<Value>,
+    accessors: Vec<Value>,
+    meshes: Vec<Value>,
+}
+
+/// A finished glTF document ready to be packed as GLB
+pub(crate) struct GltfDocument {
+    json: Value,
+    bin: Vec<u8>,
+}
+
+impl GltfBuilder {
+    /// Add a mesh with one primitive per surface type, returning its index.
+    ///
+    /// `local` maps model points to the Z-up local frame of the mesh. If a batch
+    /// ID is given, every vertex gets it as a `_BATCHID` attribute for 3D Tiles.
+    /// Returns `None` if the model has no faces to render.
+    pub(crate) fn add_mesh(
+        &mut self,
+        model: &Model,
+        local: impl Fn(Point3<f64>) -> Vector3<f64>,
+        batch_id: Option<u32>,
+    ) -> Option<usize> {
+        // Normals are computed in the local frame, which may be rotated from the model's
+        let vertices: Vec<Vertex> = model
+            .vertices
+            .iter()
+            .map(|vertex| Vertex {
+                point: Point3::from_vec(local(model.world_point(vertex.point))),
+                id: vertex.id,
+            })
+            .collect();
+        let mut primitives = Vec::new();
+
+        for (material, surface_type) in MATERIAL_SURFACE_TYPES.iter().enumerate() {
+            let mut positions: Vec<[f32; 3]> = Vec::new();
+            let mut normals: Vec<[f32; 3]> = Vec::new();
+            let mut indices: Vec<u32> = Vec::new();
+
+            for face in model
+                .faces
+                .iter()
+                .filter(|face| face.surface_type == *surface_type)
+            {
+                let triangles = triangulate_face(face, &model.vertices);
+                if triangles.is_empty() {
+                    continue;
+                }
+
+                let normal = newell_normal(&face.vertex_ids, &vertices);
+                let normal = if normal.magnitude() > 0.0 {
+                    normal.normalize()
+                } else {
+                    Vector3::new(0.0, 0.0, 1.0)
+                };
+
+                // Vertices are not shared between faces so every face keeps a flat normal
+                let mut local: HashMap<usize, u32> = HashMap::new();
+                for &vertex_id in &face.vertex_ids {
+                    local.entry(vertex_id).or_insert_with(|| {
+                        positions.push(to_y_up(vertices[vertex_id].point.to_vec()));
+                        normals.push(to_y_up(normal));
+                        (positions.len() - 1) as u32
+                    });
+                }
+                for triangle in triangles {
+                    indices.extend(triangle.iter().map(|id| local[id]));
+                }
+            }
+
+            if indices.is_empty() {
+                continue;
+            }
+
+            let mut primitive = json!({
+                "attributes": {
+                    "POSITION": self.push_vec3(&positions, true),
+                    "NORMAL": self.push_vec3(&normals, false),
+                },
+                "indices": self.push_indices(&indices),
+                "material": material,
+                "mode": MODE_TRIANGLES,
+            });
+            if let Some(batch_id) = batch_id {
+                primitive["attributes"]["_BATCHID"] =
+                    json!(self.push_scalars(&vec![batch_id as f32; positions.len()]));
+            }
+            primitives.push(primitive);
+        }
+
+        if primitives.is_empty() {
+            return None;
+        }
+
+        let mut mesh = json!({ "primitives": primitives });
+        if let Some(id) = &model.id {
+            mesh["name"] = json!(id);
+        }
+        self.meshes.push(mesh);
+        Some(self.meshes.len() - 1)
+    }
+
+    /// Finish the document with the given nodes and scene
+    pub(crate) fn into_document(self, nodes: Vec<Value>, scene: Value) -> GltfDocument {
+        let materials: Vec<Value> = MATERIAL_SURFACE_TYPES
+            .iter()
+            .map(|surface_type| {
+                let [r, g, b, a] = surface_type.color();
+                json!({
+                    "name": format!("{:?}", surface_type),
+                    "pbrMetallicRoughness": {
+                        "baseColorFactor": [
+                            srgb_to_linear(r),
+                            srgb_to_linear(g),
+                            srgb_to_linear(b),
+                            a as f64 / 255.0,
+                        ],
+                        "metallicFactor": 0.0,
+                        "roughnessFactor": 1.0,
+                    },
+                    // Face orientation of the input isn't guaranteed to be consistent
+                    "doubleSided": true,
+                })
+            })
+            .collect();
+
+        let mut json = json!({
+            "asset": { "version": "2.0", "generator": "lodconv" },
+            "scene": 0,
+            "scenes": [scene],
+            "nodes": nodes,
+            "meshes": self.meshes,
+            "materials": materials,
+        });
+        if !self.bin.is_empty() {
+            json["buffers"] = json!([{ "byteLength": self.bin.len() }]);
+            json["bufferViews"] = json!(self.buffer_views);
+            json["accessors"] = json!(self.accessors);
+        }
+
+        GltfDocument {
+            json,
+            bin: self.bin,
+        }
+    }
+
+    /// Append bytes as a new buffer view, aligned to 4 bytes
+    fn push_view(&mut self, bytes: &[u8], target: u32) -> usize {
+        while !self.bin.len().is_multiple_of(4) {
+            self.bin.push(0);
+        }
+        self.buffer_views.push(json!({
+            "buffer": 0,
+            "byteOffset": self.bin.len(),
+            "byteLength": bytes.len(),
+            "target": target,
+        }));
+        self.bin.extend_from_slice(bytes);
+        self.buffer_views.len() - 1
+    }
+
+    fn push_vec3(&mut self, values: &[[f32; 3]], with_bounds: bool) -> usize {
+        let bytes: Vec<u8> = values
+            .iter()
+            .flatten()
+            .flat_map(|v| v.to_le_bytes())
+            .collect();
+        let view = self.push_view(&bytes, TARGET_ARRAY_BUFFER);
+
+        let mut accessor = json!({
+            "bufferView": view,
+            "componentType": COMPONENT_FLOAT,
+            "count": values.len(),
+            "type": "VEC3",
+        });
+        // POSITION accessors must declare their bounds
+        if with_bounds {
+            let mut min = [f32::MAX; 3];
+            let mut max = [f32::MIN; 3];
+            for value in values {
+                for axis in 0..3 {
+                    min[axis] = min[axis].min(value[axis]);
+                    max[axis] = max[axis].max(value[axis]);
+                }
+            }
+            accessor["min"] = json!(min);
+            accessor["max"] = json!(max);
+        }
+
+        self.accessors.push(accessor);
+        self.accessors.len() - 1
+    }
+
+    fn push_scalars(&mut self, values: &[f32]) -> usize {
+        let bytes: Vec<u8> = values.iter().flat_map(|v| v.to_le_bytes()).collect();
+        let view = self.push_view(&bytes, TARGET_ARRAY_BUFFER);
+        self.accessors.push(json!({
+            "bufferView": view,
+            "componentType": COMPONENT_FLOAT,
+            "count": values.len(),
+            "type": "SCALAR",
+        }));
+        self.accessors.len() - 1
+    }
+
+    fn push_indices(&mut self, indices: &[u32]) -> usize {
+        let bytes: Vec<u8> = indices.iter().flat_map(|i| i.to_le_bytes()).collect();
+        let view = self.push_view(&bytes, TARGET_ELEMENT_ARRAY_BUFFER);
+        self.accessors.push(json!({
+            "bufferView": view,
+            "componentType": COMPONENT_UNSIGNED_INT,
+            "count": indices.len(),
+            "type": "SCALAR",
+        }));
+        self.accessors.len() - 1
+    }
+}
+
+impl GltfDocument {
+    /// Pack the document as GLB: a 12-byte header followed by the JSON and binary chunks
+    pub(crate) fn to_glb(&self) -> Result<Vec<u8>> {
+        let mut json = serde_json::to_vec(&self.json)?;
+        // Chunks must be 4-byte aligned; JSON is padded with spaces, binary with zeros
+        while !json.len().is_multiple_of(4) {
+            json.push(b' ');
+        }
+        let mut bin = self.bin.clone();
+        while !bin.len().is_multiple_of(4) {
+            bin.push(0);
+        }
+
+        let mut total = 12 + 8 + json.len();
+        if !bin.is_empty() {
+            total += 8 + bin.len();
+        }
+
+        let mut glb = Vec::with_capacity(total);
+        glb.extend_from_slice(&GLB_MAGIC.to_le_bytes());
+        glb.extend_from_slice(&GLB_VERSION.to_le_bytes());
+        glb.extend_from_slice(&(total as u32).to_le_bytes());
+
+        glb.extend_from_slice(&(json.len() as u32).to_le_bytes());
+        glb.extend_from_slice(&CHUNK_JSON.to_le_bytes());
+        glb.extend_from_slice(&json);
+
+        if !bin.is_empty() {
+            glb.extend_from_slice(&(bin.len() as u32).to_le_bytes());
+            glb.extend_from_slice(&CHUNK_BIN.to_le_bytes());
+            glb.extend_from_slice(&bin);
+        }
+
+        Ok(glb)
+    }
+}
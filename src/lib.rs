@@ -1,47 +1,284 @@
+pub mod batch;
+pub mod cityjson;
+pub mod compare;
+pub mod distance;
 pub mod error;
+pub mod footprint;
+pub mod format;
+pub mod geodesy;
+pub mod geojson;
+pub mod gltf;
+pub mod merge;
+pub mod metrics;
 pub mod model;
+pub mod obj;
+pub mod pipeline;
+pub mod ply;
+pub mod pointcloud;
 pub mod primitives;
+pub mod raster;
+pub mod record;
+pub mod roof;
+pub mod simplify;
+pub mod stats;
+pub mod stl;
+pub mod tiles;
+pub mod transform;
+pub mod triangulate;
+pub mod weld;
+pub mod wkt;
 
+pub use cityjson::{CityJsonReader, CityJsonSeqWriter};
+pub use compare::{compare_buildings, compare_models, ComparisonReport, LodMetrics};
+pub use distance::{one_sided_distance, surface_distance, DistanceStats, SurfaceDistance};
 pub use error::{Error, Result};
-pub use model::Model;
+pub use footprint::{Footprint, Polygon};
+pub use format::{
+    read_models, Encoding, FormatRegistry, ModelReader, ModelSink, ModelStream, ModelWriter,
+    ReadOptions, RegisteredFormat, WriteOptions,
+};
+pub use geodesy::{reproject, Crs};
+pub use geojson::{footprint_feature, FootprintProperties, GeoJsonReader, GeoJsonWriter};
+pub use gltf::{write_glb, GlbWriter};
+pub use merge::merge_coplanar_faces;
+pub use metrics::ShapeMetrics;
+pub use model::{
+    ConversionStats, GroundStrategy, HeightStrategy, Lod1Options, Model, StoreyHeight,
+};
+pub use obj::{ObjReader, ObjWriter};
+pub use pipeline::{convert_models, BuildingReport};
+pub use ply::{PlyReader, PlyWriter};
+pub use pointcloud::PointCloud;
 pub use primitives::{Face, SurfaceType, Vertex};
+pub use raster::Raster;
+pub use record::save_recording;
+pub use roof::{roof_planes, RoofAnalysis, RoofPlane, RoofPlaneFormat, RoofPlaneWriter, RoofType};
+pub use simplify::{simplify_footprint, Simplification};
+pub use stats::ModelStats;
+pub use stl::{StlReader, StlWriter};
+pub use tiles::{write_tileset, TilesetOptions};
+pub use transform::{parse_epsg, supported_crs, AffineTransform};
+pub use weld::VertexWelder;
+pub use wkt::WktReader;
 
-use std::path::Path;
+use format::{create_output, is_stdio};
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
 
 pub const EPSILON: f64 = 1e-6; // epsilon for floating point comparison
 pub const WALL_ANGLE_THRESHOLD: f64 = 0.01; // angle threshold for wall against the up vector
 pub const GROUND_HEIGHT_THRESHOLD: f64 = 1.0; // height threshold for ground. Assuming all ground surfaces vertices are within 1.0 m of min z value
 pub const ROOF_HEIGHT_PERCENTILE: f64 = 0.7; // percentile of roof height to use for LoD1.2 height. Default is 70% which follows 3DBAG decisions
+pub const GROUND_LEVEL_PERCENTILE: f64 = 0.1; // percentile of the ground vertex z used as ground level, ignoring a few low outliers
+pub const SIMPLIFY_TOLERANCE: f64 = 0.1; // distance in metres within which footprint vertices are simplified away
+pub const SQUARING_ANGLE: f64 = 10.0; // deviation in degrees from the main directions under which footprint edges are squared
+pub const STOREY_HEIGHT: f64 = 3.0; // typical storey height in metres, used to estimate the number of storeys
+pub const WELD_TOLERANCE: f64 = EPSILON; // distance under which output vertices are merged into one
+pub const DISTANCE_SAMPLE_SPACING: f64 = 1.0; // spacing in metres of the samples used to measure surface distances
+pub const CONVERT_CHUNK_SIZE: usize = 256; // number of buildings converted in parallel before they are written
+pub const POINT_CLOUD_MARGIN: f64 = 1.0; // margin in metres around the input buildings within which point cloud points are read
+pub const TILE_MAX_FEATURES: usize = 64; // maximum number of buildings in a 3D Tiles tile before it is split
+pub const TILE_MAX_DEPTH: usize = 10; // maximum depth of the 3D Tiles quadtree
+pub const FOOTPRINT_GROUND_ATTRIBUTE: &str = "ground_z"; // footprint attribute holding the ground z
+pub const FOOTPRINT_HEIGHT_ATTRIBUTE: &str = "roof_height"; // footprint attribute holding the building height above the ground
 
-/// Convert a LoD2.2 OBJ file to a LoD1.2 OBJ file
-pub fn convert_lod(input_path: &Path, output_path: &Path) -> Result<()> {
-    // Initialize rerun
-
-    let mut model = Model::read_obj(input_path)?;
-
-    // Debug
-    // =========================
-    // Visualize the model
-    // let mut rec = rerun::RecordingStreamBuilder::new("lodconv.rrd").spawn()?;
-    // println!("Starting visualization viewer...");
-    // if visualize {
-    //     model.visualize(&mut rec, "lod2_2")?;
-    //     // Start the viewer
-    //     println!("Visualization ready. Press Ctrl+C to exit.");
-    // }
-    // =========================
-    // Convert the model from LoD2.2 to LoD1.2
-    model.to_lod1_2()?;
-
-    // Debug
-    // =========================
-    // if visualize {
-    //     model.visualize(&mut rec, "lod1_2")?;
-    // }
-    // =========================
-
-    // Write the output OBJ file
-    model.write_obj(output_path)?;
-
-    Ok(())
+/// Options controlling the LoD2.2 to LoD1.2 conversion
+#[derive(Debug, Clone)]
+pub struct ConvertOptions {
+    /// Distance under which output vertices are welded together
+    pub weld_tolerance: f64,
+    /// Encoding of STL and PLY outputs
+    pub encoding: Encoding,
+    /// Input format name, detected from the input extension if `None`
+    pub input_format: Option<String>,
+    /// Output format name, detected from the output extension if `None`
+    pub output_format: Option<String>,
+    /// Attribute of footprint inputs holding the ground z
+    pub ground_attribute: String,
+    /// Attribute of footprint inputs holding the building height above the ground
+    pub height_attribute: String,
+    /// EPSG code of the input coordinates, used when the input doesn't give one
+    pub crs: Option<u32>,
+    /// Affine transform applied to the world coordinates of every input building
+    pub transform: Option<AffineTransform>,
+    /// EPSG code the converted buildings are reprojected to before they are written
+    pub target_crs: Option<u32>,
+    /// Options of the conversion of every building
+    pub lod1: Lod1Options,
+}
+
+impl ConvertOptions {
+    /// Options passed to the input reader
+    pub fn read_options(&self) -> ReadOptions {
+        ReadOptions {
+            ground_attribute: self.ground_attribute.clone(),
+            height_attribute: self.height_attribute.clone(),
+            crs: self.crs,
+            transform: self.transform,
+        }
+    }
+
+    /// Options passed to the output writer
+    pub fn write_options(&self) -> WriteOptions {
+        WriteOptions {
+            weld_tolerance: self.weld_tolerance,
+            encoding: self.encoding,
+        }
+    }
+}
+
+impl Default for ConvertOptions {
+    fn default() -> Self {
+        ConvertOptions {
+            weld_tolerance: WELD_TOLERANCE,
+            encoding: Encoding::default(),
+            input_format: None,
+            output_format: None,
+            ground_attribute: FOOTPRINT_GROUND_ATTRIBUTE.to_string(),
+            height_attribute: FOOTPRINT_HEIGHT_ATTRIBUTE.to_string(),
+            crs: None,
+            transform: None,
+            target_crs: None,
+            lod1: Lod1Options::default(),
+        }
+    }
+}
+
+/// Convert a LoD2.2 model file to a LoD1.2 model file.
+///
+/// Every building in the input is converted separately, in parallel, and
+/// written in input order. `-` stands for stdin as input and stdout as output.
+/// Formats are taken from `options`, or else detected from the file extensions
+/// with the built-in `FormatRegistry`; stdin is detected from its content, and
+/// stdout gets the input format if it can be written. Returns one report per
+/// building.
+pub fn convert_lod(
+    input_path: &Path,
+    output_path: &Path,
+    options: &ConvertOptions,
+) -> Result<Vec<BuildingReport>> {
+    let registry = FormatRegistry::default();
+    let (input_format, input) = registry.open_input(input_path, options.input_format.as_deref())?;
+
+    let output_format = match &options.output_format {
+        Some(name) => Some(name.as_str()),
+        None if is_stdio(output_path) && input_format.writer.is_some() => Some(input_format.name),
+        None => None,
+    };
+    let writer = registry.writer(output_path, output_format)?;
+
+    let output = create_output(output_path)?;
+    let options = with_recording_source(options, input_path);
+    convert_stream(input, output, input_format.reader()?, writer, &options)
+}
+
+/// `options` recording the buildings under their input path, unless they
+/// already name a source, so buildings of several inputs are kept apart
+fn with_recording_source(options: &ConvertOptions, input_path: &Path) -> ConvertOptions {
+    let mut options = options.clone();
+    if options.lod1.recording.is_some() && options.lod1.recording_source.is_none() {
+        options.lod1.recording_source = Some(if is_stdio(input_path) {
+            "stdin".to_string()
+        } else {
+            input_path.display().to_string()
+        });
+    }
+    options
+}
+
+/// Convert LoD2.2 buildings read from `input` to LoD1.2 buildings written to `output`.
+///
+/// Works on any reader and writer, such as in-memory buffers or stdin and stdout.
+/// Returns one report per building.
+pub fn convert_stream<'a>(
+    input: Box<dyn BufRead + 'a>,
+    output: Box<dyn Write + 'a>,
+    reader: &dyn ModelReader,
+    writer: &dyn ModelWriter,
+    options: &ConvertOptions,
+) -> Result<Vec<BuildingReport>> {
+    let models = reader.read(input, &options.read_options())?;
+    let mut sink = writer.create(output, &options.write_options())?;
+
+    // Convert the buildings from LoD2.2 to LoD1.2 and write them as they are done
+    let reports = convert_models(
+        models,
+        CONVERT_CHUNK_SIZE,
+        &options.lod1,
+        |model| match options.target_crs {
+            Some(target_crs) => sink.write_reprojected(model, target_crs),
+            None => sink.write_model(&model),
+        },
+    )?;
+    sink.finish()?;
+
+    Ok(reports)
+}
+
+/// Horizontal extent (minimum and maximum x and y) of the world coordinates of
+/// the buildings in the input files, read as in `convert_lod`, plus
+/// `POINT_CLOUD_MARGIN`, e.g. to read only the part of a point cloud under them.
+///
+/// `None` if an input is stdin, which can't be read twice, or holds no vertices.
+pub fn buildings_extent(
+    input_paths: &[PathBuf],
+    options: &ConvertOptions,
+) -> Result<Option<([f64; 2], [f64; 2])>> {
+    if input_paths.iter().any(|path| is_stdio(path)) {
+        return Ok(None);
+    }
+    let (mut min, mut max) = ([f64::MAX; 2], [f64::MIN; 2]);
+    for path in input_paths {
+        for model in read_models(
+            path,
+            options.input_format.as_deref(),
+            &options.read_options(),
+        )? {
+            let model = model?;
+            for vertex in &model.vertices {
+                let p = model.world_point(vertex.point);
+                min = [min[0].min(p.x), min[1].min(p.y)];
+                max = [max[0].max(p.x), max[1].max(p.y)];
+            }
+        }
+    }
+    if min[0] > max[0] {
+        return Ok(None);
+    }
+    Ok(Some((
+        [min[0] - POINT_CLOUD_MARGIN, min[1] - POINT_CLOUD_MARGIN],
+        [max[0] + POINT_CLOUD_MARGIN, max[1] + POINT_CLOUD_MARGIN],
+    )))
+}
+
+/// Convert a LoD2.2 model file to a LoD1.2 3D Tiles tileset in `output_dir`.
+///
+/// The input is read and every building converted as in `convert_lod`; the
+/// output options of `options`, such as the weld tolerance and target CRS, don't
+/// apply. See `write_tileset` for the layout of the tileset. Returns one report
+/// per building.
+pub fn convert_to_tileset(
+    input_path: &Path,
+    output_dir: &Path,
+    options: &ConvertOptions,
+    tileset_options: &TilesetOptions,
+) -> Result<Vec<BuildingReport>> {
+    let reader = read_models(
+        input_path,
+        options.input_format.as_deref(),
+        &options.read_options(),
+    )?;
+
+    // The quadtree needs the extent of every building, so collect them first
+    let options = with_recording_source(options, input_path);
+    let mut models = Vec::new();
+    let reports = convert_models(reader, CONVERT_CHUNK_SIZE, &options.lod1, |model| {
+        models.push(model);
+        Ok(())
+    })?;
+    if !models.is_empty() {
+        write_tileset(&models, output_dir, tileset_options)?;
+    }
+
+    Ok(reports)
 }
@@ -1,6 +1,17 @@
-use clap::{Parser, Subcommand};
-use lodconv::{convert_lod, Result};
-use std::path::PathBuf;
+use clap::{Parser, Subcommand, ValueEnum};
+use lodconv::batch::{collect_inputs, convert_batch, write_summary};
+use lodconv::format::{create_output, is_stdio, ReadOptions};
+use lodconv::{
+    buildings_extent, compare_buildings, convert_lod, convert_to_tileset, parse_epsg, read_models,
+    save_recording, supported_crs, AffineTransform, BuildingReport, ConvertOptions, Encoding,
+    Error, GeoJsonWriter, GroundStrategy, HeightStrategy, Lod1Options, Model, ModelStats,
+    PointCloud, Raster, Result, RoofPlaneFormat, RoofPlaneWriter, Simplification, StoreyHeight,
+    TilesetOptions, DISTANCE_SAMPLE_SPACING, FOOTPRINT_GROUND_ATTRIBUTE,
+    FOOTPRINT_HEIGHT_ATTRIBUTE, GROUND_LEVEL_PERCENTILE, ROOF_HEIGHT_PERCENTILE,
+    SIMPLIFY_TOLERANCE, SQUARING_ANGLE, TILE_MAX_DEPTH, TILE_MAX_FEATURES, WELD_TOLERANCE,
+};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
 /// Command line arguments
 #[derive(Parser, Debug)]
@@ -10,22 +21,496 @@ struct Args {
     command: Command,
 }
 
+/// Output format of reports printed to stdout
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum ReportFormat {
+    /// Human-readable table
+    Table,
+    /// JSON
+    Json,
+    /// GeoJSON FeatureCollection of the building footprints (info only)
+    #[value(name = "geojson")]
+    GeoJson,
+}
+
+/// How the ground level of the LoD1.2 base is derived
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum GroundLevel {
+    /// Lowest z of the ground surfaces
+    Lowest,
+    /// Percentile of the z of the ground surface vertices, see --ground-percentile
+    Percentile,
+    /// Median z of the footprint boundary
+    BoundaryMedian,
+    /// Median of the terrain model under the footprint, see --dtm
+    Dtm,
+    /// Ground surfaces following a sloped terrain, each wall rising from its own ground z
+    Sloped,
+}
+
+/// How the footprint is simplified before it is extruded
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum SimplifyMethod {
+    /// Douglas-Peucker within --simplify-tolerance
+    DouglasPeucker,
+    /// Removal of the vertices within --simplify-tolerance of the edge between their neighbours
+    Collinear,
+    /// Collinear removal, then squaring of the edges within --squaring-angle of the main directions
+    Orthogonal,
+}
+
+/// Options of reading the input buildings
+#[derive(clap::Args, Debug)]
+struct ReadArgs {
+    /// Attribute of GeoJSON and WKT footprints holding the ground z
+    #[arg(long, default_value = FOOTPRINT_GROUND_ATTRIBUTE)]
+    ground_attribute: String,
+
+    /// Attribute of GeoJSON and WKT footprints holding the height above the ground
+    #[arg(long, default_value = FOOTPRINT_HEIGHT_ATTRIBUTE)]
+    height_attribute: String,
+
+    /// EPSG code of the input coordinates, e.g. 7415 or EPSG:7415, if the input doesn't give one
+    #[arg(long, value_parser = parse_crs)]
+    crs: Option<u32>,
+
+    /// Affine transform of the input coordinates: a translation "x,y,z", or the 12 values of the rows of a 3×4 matrix
+    #[arg(long, value_delimiter = ',', allow_hyphen_values = true)]
+    transform: Vec<f64>,
+}
+
+/// Options of the conversion of every building
+#[derive(clap::Args, Debug)]
+struct Lod1Args {
+    /// LAS/LAZ point cloud giving the roof height from the points inside each footprint
+    #[arg(long)]
+    point_cloud: Option<PathBuf>,
+
+    /// Percentile (0 to 1) of the heights of the point cloud points used as roof height
+    #[arg(long, default_value_t = ROOF_HEIGHT_PERCENTILE, requires = "point_cloud")]
+    percentile: f64,
+
+    /// Only use point cloud points with these LAS classification codes, e.g. 6 for buildings
+    #[arg(long, value_delimiter = ',', requires = "point_cloud")]
+    classes: Vec<u8>,
+
+    /// How the ground level of the LoD1.2 base is derived
+    #[arg(long, value_enum, default_value_t = GroundLevel::Lowest)]
+    ground: GroundLevel,
+
+    /// Percentile (0 to 1) of the ground surface heights used with --ground percentile
+    #[arg(long, default_value_t = GROUND_LEVEL_PERCENTILE)]
+    ground_percentile: f64,
+
+    /// Terrain model (GeoTIFF, or ESRI ASCII grid) used with --ground dtm
+    #[arg(long, required_if_eq("ground", "dtm"))]
+    dtm: Option<PathBuf>,
+
+    /// Simplify the footprint before it is extruded
+    #[arg(long, value_enum)]
+    simplify: Option<SimplifyMethod>,
+
+    /// Distance in metres within which footprint vertices are simplified away
+    #[arg(long, default_value_t = SIMPLIFY_TOLERANCE, requires = "simplify")]
+    simplify_tolerance: f64,
+
+    /// Largest deviation in degrees from the main directions of an edge squared with --simplify orthogonal
+    #[arg(long, default_value_t = SQUARING_ANGLE, requires = "simplify")]
+    squaring_angle: f64,
+
+    /// Split the LoD1.2 block into storeys with floor surfaces, recording the storeys and gross floor area
+    #[arg(long)]
+    storeys: bool,
+
+    /// Storey height in metres used with --storeys, estimated from the building height if not given
+    #[arg(long, requires = "storeys")]
+    storey_height: Option<f64>,
+
+    /// Merge adjacent coplanar output faces, such as the triangles of the base, into polygons
+    #[arg(long)]
+    merge_coplanar: bool,
+
+    /// Record volume, areas, heights and shape indices of the input and output as attributes
+    #[arg(long)]
+    shape_metrics: bool,
+
+    /// Save a Rerun recording of every stage of the conversion of every building to this .rrd file
+    #[arg(long)]
+    record: Option<PathBuf>,
+}
+
 #[derive(Subcommand, Debug)]
 enum Command {
     /// Convert a LoD2.2 model to LoD1.2
     Convert {
-        /// Input OBJ file path (LoD2.2)
+        /// Input file path (LoD2.2), or - for stdin: OBJ, STL, PLY, CityJSON, CityJSONSeq, or GeoJSON or CSV with WKT footprints
         #[arg(short, long)]
         input: PathBuf,
 
-        /// Output OBJ file path (LoD1.2)
+        /// Output file path (LoD1.2), or - for stdout: OBJ, STL, PLY, CityJSONSeq, GLB or GeoJSON
         #[arg(short, long)]
         output: PathBuf,
 
+        /// Distance under which output vertices are welded together
+        #[arg(long, default_value_t = WELD_TOLERANCE)]
+        weld_tolerance: f64,
+
+        /// Write STL and PLY outputs as ASCII instead of binary
+        #[arg(long)]
+        ascii: bool,
+
+        /// Input format (obj, stl, ply, cityjson, cityjsonseq, geojson, wkt), overriding the file extension
+        #[arg(long)]
+        input_format: Option<String>,
+
+        /// Output format (obj, stl, ply, cityjsonseq, glb, geojson), overriding the file extension
+        #[arg(long)]
+        output_format: Option<String>,
+
+        #[command(flatten)]
+        read: ReadArgs,
+
+        /// EPSG code the output is reprojected to: RD New, WGS84 or ETRS89 (geographic or ECEF) or UTM
+        #[arg(long, value_parser = parse_target_crs)]
+        target_crs: Option<u32>,
+
+        #[command(flatten)]
+        lod1: Lod1Args,
+
         /// Enable verbose logging
         #[arg(short, long)]
         verbose: bool,
     },
+    /// Convert many LoD2.2 models to LoD1.2 in parallel
+    Batch {
+        /// Input directory or glob pattern (e.g. "data/input/*.obj")
+        #[arg(short, long)]
+        input: String,
+
+        /// Output directory for the LoD1.2 models
+        #[arg(short, long)]
+        output: PathBuf,
+
+        /// Summary file, written as JSON for .json and as CSV otherwise
+        #[arg(short, long)]
+        summary: Option<PathBuf>,
+
+        /// Number of worker threads (default: one per core)
+        #[arg(short, long)]
+        jobs: Option<usize>,
+
+        /// Distance under which output vertices are welded together
+        #[arg(long, default_value_t = WELD_TOLERANCE)]
+        weld_tolerance: f64,
+
+        /// Write STL and PLY outputs as ASCII instead of binary
+        #[arg(long)]
+        ascii: bool,
+
+        /// Input format (obj, stl, ply, cityjson, cityjsonseq, geojson, wkt), overriding the file extensions
+        #[arg(long)]
+        input_format: Option<String>,
+
+        /// Output format (obj, stl, ply, cityjsonseq, glb, geojson), also setting the output extensions
+        #[arg(long)]
+        output_format: Option<String>,
+
+        #[command(flatten)]
+        read: ReadArgs,
+
+        /// EPSG code the output is reprojected to: RD New, WGS84 or ETRS89 (geographic or ECEF) or UTM
+        #[arg(long, value_parser = parse_target_crs)]
+        target_crs: Option<u32>,
+
+        #[command(flatten)]
+        lod1: Lod1Args,
+
+        /// Enable verbose logging
+        #[arg(short, long)]
+        verbose: bool,
+    },
+    /// Print geometric and topological statistics of every building in a model
+    #[command(alias = "stats")]
+    Info {
+        /// Input file path, or - for stdin
+        #[arg(short, long)]
+        input: PathBuf,
+
+        /// Output format
+        #[arg(short, long, value_enum, default_value_t = ReportFormat::Table)]
+        format: ReportFormat,
+
+        #[command(flatten)]
+        read: ReadArgs,
+    },
+    /// Export the roof planes of every building, with their area, slope and aspect
+    RoofPlanes {
+        /// Input file path (LoD2.2), or - for stdin
+        #[arg(short, long)]
+        input: PathBuf,
+
+        /// Output file path (CSV, JSON or OBJ), or - for stdout
+        #[arg(short, long)]
+        output: PathBuf,
+
+        /// Output format (csv, json, obj), overriding the output extension
+        #[arg(short, long)]
+        format: Option<String>,
+
+        #[command(flatten)]
+        read: ReadArgs,
+    },
+    /// Compare a LoD2.2 model with its LoD1.2 conversion
+    Compare {
+        /// LoD2.2 file path, or - for stdin
+        #[arg(short, long)]
+        input: PathBuf,
+
+        /// LoD1.2 file path
+        #[arg(short, long)]
+        output: PathBuf,
+
+        /// Spacing in metres of the surface samples used for distances
+        #[arg(long, default_value_t = DISTANCE_SAMPLE_SPACING)]
+        sample_spacing: f64,
+
+        /// Output format
+        #[arg(short, long, value_enum, default_value_t = ReportFormat::Table)]
+        format: ReportFormat,
+
+        /// Options of reading the LoD2.2 input; only its CRS applies to the LoD1.2 file, which is already transformed
+        #[command(flatten)]
+        read: ReadArgs,
+    },
+    /// Convert a LoD2.2 model in RD coordinates to a LoD1.2 3D Tiles tileset
+    Tiles {
+        /// Input file path (LoD2.2, RD New with NAP heights), or - for stdin
+        #[arg(short, long)]
+        input: PathBuf,
+
+        /// Output directory for tileset.json and the tile contents
+        #[arg(short, long)]
+        output: PathBuf,
+
+        /// Maximum number of buildings per tile
+        #[arg(long, default_value_t = TILE_MAX_FEATURES)]
+        max_features: usize,
+
+        /// Maximum depth of the tile quadtree
+        #[arg(long, default_value_t = TILE_MAX_DEPTH)]
+        max_depth: usize,
+
+        /// Input format (obj, stl, ply, cityjson, cityjsonseq, geojson, wkt), overriding the file extension
+        #[arg(long)]
+        input_format: Option<String>,
+
+        #[command(flatten)]
+        read: ReadArgs,
+
+        #[command(flatten)]
+        lod1: Lod1Args,
+
+        /// Enable verbose logging
+        #[arg(short, long)]
+        verbose: bool,
+    },
+}
+
+impl ReadArgs {
+    /// Options passed to the input reader
+    fn options(&self) -> Result<ReadOptions> {
+        Ok(ReadOptions {
+            ground_attribute: self.ground_attribute.clone(),
+            height_attribute: self.height_attribute.clone(),
+            crs: self.crs,
+            transform: affine_transform(&self.transform)?,
+        })
+    }
+}
+
+/// Read every building of a model file and classify its surfaces
+fn read_classified(path: &Path, options: &ReadOptions) -> Result<Vec<Model>> {
+    read_models(path, None, options)?
+        .map(|model| {
+            let mut model = model?;
+            model.classify_surfaces();
+            Ok(model)
+        })
+        .collect()
+}
+
+impl Lod1Args {
+    /// Options of the conversion of every building, reading the point cloud
+    /// under the buildings of `inputs`, read with `convert`, and the terrain
+    /// model if given
+    fn options(
+        &self,
+        verbose: bool,
+        inputs: &[PathBuf],
+        convert: &ConvertOptions,
+    ) -> Result<Lod1Options> {
+        let height = match &self.point_cloud {
+            None => HeightStrategy::RoofPercentile,
+            Some(path) => {
+                check_percentile(self.percentile)?;
+                let classes = if self.classes.is_empty() {
+                    None
+                } else {
+                    Some(self.classes.as_slice())
+                };
+                let extent = buildings_extent(inputs, convert)?;
+                let cloud = PointCloud::read(path, classes, extent)?;
+                if verbose {
+                    eprintln!("Read {} points from {}", cloud.len(), path.display());
+                }
+                HeightStrategy::PointCloud {
+                    cloud: Arc::new(cloud),
+                    percentile: self.percentile,
+                }
+            }
+        };
+
+        let ground = match (self.ground, &self.dtm) {
+            (GroundLevel::Lowest, _) => GroundStrategy::Lowest,
+            (GroundLevel::Percentile, _) => {
+                check_percentile(self.ground_percentile)?;
+                GroundStrategy::Percentile(self.ground_percentile)
+            }
+            (GroundLevel::BoundaryMedian, _) => GroundStrategy::BoundaryMedian,
+            (GroundLevel::Sloped, _) => GroundStrategy::Sloped,
+            (GroundLevel::Dtm, None) => {
+                return Err(Error::Io(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    "--ground dtm requires --dtm",
+                )))
+            }
+            (GroundLevel::Dtm, Some(path)) => {
+                let raster = Raster::read(path)?;
+                if verbose {
+                    eprintln!("Read terrain model {}", path.display());
+                }
+                GroundStrategy::Dtm(Arc::new(raster))
+            }
+        };
+
+        if self.simplify_tolerance < 0.0 || !(0.0..45.0).contains(&self.squaring_angle) {
+            return Err(Error::Io(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "--simplify-tolerance must not be negative and --squaring-angle must be between 0 and 45",
+            )));
+        }
+        let simplify = self.simplify.map(|method| match method {
+            SimplifyMethod::DouglasPeucker => {
+                Simplification::DouglasPeucker(self.simplify_tolerance)
+            }
+            SimplifyMethod::Collinear => Simplification::Collinear(self.simplify_tolerance),
+            SimplifyMethod::Orthogonal => Simplification::Orthogonal {
+                tolerance: self.simplify_tolerance,
+                angle: self.squaring_angle,
+            },
+        });
+
+        if self.storey_height.is_some_and(|height| height <= 0.0) {
+            return Err(Error::Io(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "--storey-height must be positive",
+            )));
+        }
+        let storeys = self.storeys.then_some(match self.storey_height {
+            Some(height) => StoreyHeight::Fixed(height),
+            None => StoreyHeight::Estimated,
+        });
+
+        let recording = match &self.record {
+            Some(path) => {
+                if verbose {
+                    eprintln!("Recording the conversion to {}", path.display());
+                }
+                Some(save_recording(path)?)
+            }
+            None => None,
+        };
+
+        Ok(Lod1Options {
+            height,
+            ground,
+            simplify,
+            storeys,
+            merge_coplanar: self.merge_coplanar,
+            shape_metrics: self.shape_metrics,
+            recording,
+            recording_source: None,
+        })
+    }
+}
+
+/// Parse an EPSG code given as a number, `EPSG:` code, URN or URI
+fn parse_crs(text: &str) -> std::result::Result<u32, String> {
+    parse_epsg(text).ok_or_else(|| format!("'{}' is not an EPSG code", text))
+}
+
+/// Parse an EPSG code that buildings can be reprojected to
+fn parse_target_crs(text: &str) -> std::result::Result<u32, String> {
+    let code = parse_crs(text)?;
+    supported_crs(code).map_err(|e| e.to_string())?;
+    Ok(code)
+}
+
+/// Affine transform from the values of --transform, if any
+fn affine_transform(values: &[f64]) -> Result<Option<AffineTransform>> {
+    if values.is_empty() {
+        return Ok(None);
+    }
+    AffineTransform::from_values(values).map(Some)
+}
+
+/// Check that the weld tolerance is a positive distance
+fn check_weld_tolerance(tolerance: f64) -> Result<()> {
+    if !(tolerance.is_finite() && tolerance > 0.0) {
+        return Err(Error::Io(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("--weld-tolerance must be positive, got {}", tolerance),
+        )));
+    }
+    Ok(())
+}
+
+/// Check that a percentile lies between 0 and 1
+fn check_percentile(percentile: f64) -> Result<()> {
+    if !(0.0..=1.0).contains(&percentile) {
+        return Err(Error::Io(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("Percentile must be between 0 and 1, got {}", percentile),
+        )));
+    }
+    Ok(())
+}
+
+/// Print the buildings that failed to convert, erroring if none succeeded
+fn report_buildings(reports: &[BuildingReport], verbose: bool) -> Result<()> {
+    let failed = reports.iter().filter(|report| !report.success).count();
+    for report in reports.iter().filter(|report| !report.success) {
+        eprintln!(
+            "Failed building {}: [{}] {}",
+            report.id.as_deref().unwrap_or("<unnamed>"),
+            report.error_code.unwrap_or_default(),
+            report.error.as_deref().unwrap_or_default()
+        );
+    }
+    if failed == reports.len() {
+        return Err(Error::Io(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "No building could be converted",
+        )));
+    }
+    if verbose {
+        eprintln!(
+            "Converted {} of {} buildings",
+            reports.len() - failed,
+            reports.len()
+        );
+    }
+    Ok(())
 }
 
 fn main() -> Result<()> {
@@ -35,18 +520,234 @@ fn main() -> Result<()> {
         Command::Convert {
             input,
             output,
+            weld_tolerance,
+            ascii,
+            input_format,
+            output_format,
+            read,
+            target_crs,
+            lod1,
             verbose,
         } => {
+            // Logs go to stderr so that stdout can carry the output model
             if verbose {
-                println!("Converting {} to {}", input.display(), output.display());
+                eprintln!("Converting {} to {}", input.display(), output.display());
             }
 
-            convert_lod(&input, &output)?;
+            check_weld_tolerance(weld_tolerance)?;
+            let mut options = ConvertOptions {
+                weld_tolerance,
+                encoding: if ascii {
+                    Encoding::Ascii
+                } else {
+                    Encoding::Binary
+                },
+                input_format,
+                output_format,
+                ground_attribute: read.ground_attribute,
+                height_attribute: read.height_attribute,
+                crs: read.crs,
+                transform: affine_transform(&read.transform)?,
+                target_crs,
+                lod1: Lod1Options::default(),
+            };
+            options.lod1 = lod1.options(verbose, std::slice::from_ref(&input), &options)?;
+            let reports = convert_lod(&input, &output, &options)?;
+
+            report_buildings(&reports, verbose)?;
 
             if verbose {
-                println!("Conversion completed successfully!");
+                eprintln!("Conversion completed successfully!");
+            }
+        }
+        Command::Batch {
+            input,
+            output,
+            summary,
+            jobs,
+            weld_tolerance,
+            ascii,
+            input_format,
+            output_format,
+            read,
+            target_crs,
+            lod1,
+            verbose,
+        } => {
+            let inputs = collect_inputs(&input)?;
+            if verbose {
+                println!(
+                    "Converting {} files into {}",
+                    inputs.len(),
+                    output.display()
+                );
+            }
+
+            check_weld_tolerance(weld_tolerance)?;
+            let mut options = ConvertOptions {
+                weld_tolerance,
+                encoding: if ascii {
+                    Encoding::Ascii
+                } else {
+                    Encoding::Binary
+                },
+                input_format,
+                output_format,
+                ground_attribute: read.ground_attribute,
+                height_attribute: read.height_attribute,
+                crs: read.crs,
+                transform: affine_transform(&read.transform)?,
+                target_crs,
+                lod1: Lod1Options::default(),
+            };
+            options.lod1 = lod1.options(verbose, &inputs, &options)?;
+            let reports = convert_batch(&inputs, &output, &options, jobs)?;
+
+            let failed = reports.iter().filter(|report| !report.success).count();
+            for report in reports.iter().filter(|report| !report.success) {
+                eprintln!(
+                    "Failed file {}: [{}] {}",
+                    report.input.display(),
+                    report.error_code.unwrap_or_default(),
+                    report.error.as_deref().unwrap_or_default()
+                );
+            }
+            println!(
+                "Converted {} of {} files ({} failed)",
+                reports.len() - failed,
+                reports.len(),
+                failed
+            );
+
+            if let Some(summary) = summary {
+                write_summary(&reports, &summary)?;
+                if verbose {
+                    println!("Summary written to {}", summary.display());
+                }
+            }
+        }
+        Command::Info {
+            input,
+            format,
+            read,
+        } => {
+            let models = read_classified(&input, &read.options()?)?;
+            let stats = || {
+                models
+                    .iter()
+                    .map(ModelStats::from_model)
+                    .collect::<Vec<_>>()
+            };
+
+            match format {
+                ReportFormat::Table => {
+                    for building in stats() {
+                        println!("{}\n", building);
+                    }
+                }
+                ReportFormat::Json => println!("{}", serde_json::to_string_pretty(&stats())?),
+                ReportFormat::GeoJson => {
+                    let mut writer = GeoJsonWriter::new(std::io::stdout())?;
+                    for model in &models {
+                        writer.write_model(model)?;
+                    }
+                    writer.finish()?;
+                }
             }
         }
+        Command::RoofPlanes {
+            input,
+            output,
+            format,
+            read,
+        } => {
+            let format = match &format {
+                Some(name) => RoofPlaneFormat::from_name(name),
+                None if is_stdio(&output) => Some(RoofPlaneFormat::Csv),
+                None => RoofPlaneFormat::from_path(&output),
+            }
+            .ok_or_else(|| {
+                Error::Io(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    "Roof plane output format must be csv, json or obj",
+                ))
+            })?;
+
+            let mut writer = RoofPlaneWriter::new(create_output(&output)?, format)?;
+            for model in read_models(&input, None, &read.options()?)? {
+                let mut model = model?;
+                model.classify_surfaces();
+                writer.write_model(&model)?;
+            }
+            writer.finish()?;
+        }
+        Command::Compare {
+            input,
+            output,
+            sample_spacing,
+            format,
+            read,
+        } => {
+            let input_options = read.options()?;
+            let output_options = ReadOptions {
+                transform: None,
+                ..input_options.clone()
+            };
+            let reports = compare_buildings(
+                &read_classified(&input, &input_options)?,
+                &read_classified(&output, &output_options)?,
+                sample_spacing,
+            );
+
+            match format {
+                ReportFormat::Table => {
+                    for report in &reports {
+                        println!("{}\n", report);
+                    }
+                }
+                ReportFormat::Json => println!("{}", serde_json::to_string_pretty(&reports)?),
+                ReportFormat::GeoJson => {
+                    return Err(Error::Io(std::io::Error::new(
+                        std::io::ErrorKind::InvalidInput,
+                        "GeoJSON output is only available for info",
+                    )))
+                }
+            }
+        }
+        Command::Tiles {
+            input,
+            output,
+            max_features,
+            max_depth,
+            input_format,
+            read,
+            lod1,
+            verbose,
+        } => {
+            if verbose {
+                println!(
+                    "Converting {} to a tileset in {}",
+                    input.display(),
+                    output.display()
+                );
+            }
+
+            let mut options = ConvertOptions {
+                input_format,
+                ground_attribute: read.ground_attribute,
+                height_attribute: read.height_attribute,
+                crs: read.crs,
+                transform: affine_transform(&read.transform)?,
+                ..ConvertOptions::default()
+            };
+            options.lod1 = lod1.options(verbose, std::slice::from_ref(&input), &options)?;
+            let tileset_options = TilesetOptions {
+                max_features_per_tile: max_features,
+                max_depth,
+            };
+            let reports = convert_to_tileset(&input, &output, &options, &tileset_options)?;
+            report_buildings(&reports, verbose)?;
+        }
     }
 
     Ok(())
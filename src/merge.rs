@@ -0,0 +1,214 @@
+use crate::model::Model;
+use crate::primitives::{Face, Vertex};
+use crate::roof::PlaneSum;
+use crate::triangulate::{bridge_holes, newell_normal, projection_axes};
+use crate::EPSILON;
+use cgmath::{EuclideanSpace, InnerSpace, Vector3};
+use std::collections::HashMap;
+
+/// Largest angle in degrees between the normals of faces merged into one polygon
+const MERGE_ANGLE: f64 = 1.0;
+/// Largest distance in metres of a vertex from the plane it is merged into, or
+/// from the edge it is dropped from
+const MERGE_OFFSET: f64 = 0.001;
+
+/// Merge adjacent faces of the same surface type lying in one plane into single polygons.
+///
+/// Every group of coplanar faces becomes one face following the outline of the
+/// group. Faces are only grouped across edges that no third face uses. Holes
+/// are joined to the outline by bridge edges, so a base with a courtyard
+/// becomes a single keyhole polygon, which CityJSON outputs split back into an
+/// exterior and its holes. Groups whose outline is not a polygon, e.g. faces
+/// touching at a corner only, keep their faces.
+///
+/// Vertices lying on a straight edge are then dropped where every face using
+/// them can drop them, so shared edges still match and closed models stay
+/// watertight.
+pub fn merge_coplanar_faces(model: &mut Model) {
+    // Area-weighted normals, in the direction the face is oriented, and centroids
+    let normals: Vec<Vector3<f64>> = model
+        .faces
+        .iter()
+        .map(|face| newell_normal(&face.vertex_ids, &model.vertices) / 2.0)
+        .collect();
+    let centroids: Vec<Vector3<f64>> = model
+        .faces
+        .iter()
+        .map(|face| {
+            face.vertex_ids
+                .iter()
+                .map(|&id| model.vertices[id].point.to_vec())
+                .sum::<Vector3<f64>>()
+                / face.vertex_ids.len().max(1) as f64
+        })
+        .collect();
+
+    // Faces are only merged across edges no other face uses, so that no face
+    // ends up attached to the inside of a merged polygon, e.g. a storey floor
+    let mut edge_faces: HashMap<(usize, usize), usize> = HashMap::new();
+    for face in &model.faces {
+        for (a, b) in edges(&face.vertex_ids) {
+            *edge_faces.entry((a.min(b), a.max(b))).or_default() += 1;
+        }
+    }
+    let manifold_edges = |i: usize, j: usize| {
+        edges(&model.faces[i].vertex_ids)
+            .filter(|&(a, b)| {
+                let ids = &model.faces[j].vertex_ids;
+                ids.contains(&a) && ids.contains(&b)
+            })
+            .all(|(a, b)| edge_faces[&(a.min(b), a.max(b))] == 2)
+    };
+
+    let min_cos = MERGE_ANGLE.to_radians().cos();
+    let mut assigned = vec![false; model.faces.len()];
+    let mut faces = Vec::new();
+
+    for seed in 0..model.faces.len() {
+        if assigned[seed] {
+            continue;
+        }
+        assigned[seed] = true;
+        let surface_type = model.faces[seed].surface_type;
+        if normals[seed].magnitude() <= 0.0 {
+            faces.push(model.faces[seed].clone());
+            continue;
+        }
+
+        let mut region = vec![seed];
+        let mut plane = PlaneSum::new(normals[seed], centroids[seed]);
+        let mut stack = vec![seed];
+        while let Some(i) = stack.pop() {
+            for &j in &model.faces[i].adjacent_faces {
+                if assigned[j]
+                    || model.faces[j].surface_type != surface_type
+                    || !manifold_edges(i, j)
+                {
+                    continue;
+                }
+                let (normal, origin) = plane.plane();
+                let parallel =
+                    normals[j].magnitude() <= 0.0 || normals[j].normalize().dot(normal) >= min_cos;
+                let coplanar = model.faces[j].vertex_ids.iter().all(|&id| {
+                    (model.vertices[id].point.to_vec() - origin)
+                        .dot(normal)
+                        .abs()
+                        <= MERGE_OFFSET
+                });
+                if parallel && coplanar {
+                    assigned[j] = true;
+                    region.push(j);
+                    plane.add(normals[j], centroids[j]);
+                    stack.push(j);
+                }
+            }
+        }
+        region.sort_unstable();
+
+        let merged = match region.len() {
+            1 => None,
+            _ => merged_ring(model, &region, plane.plane().0),
+        };
+        match merged {
+            Some(ring) => faces.push(Face {
+                vertex_ids: ring,
+                surface_type,
+                adjacent_faces: Vec::new(),
+            }),
+            None => faces.extend(region.iter().map(|&i| model.faces[i].clone())),
+        }
+    }
+
+    remove_collinear_vertices(&mut faces, &model.vertices);
+    model.faces = faces;
+    model.remove_unused_vertices();
+    model.build_adjacency();
+}
+
+/// Outline of a group of coplanar faces as a single ring oriented like the
+/// faces, or `None` if the outline is not one polygon with holes
+fn merged_ring(model: &Model, region: &[usize], normal: Vector3<f64>) -> Option<Vec<usize>> {
+    let area = |ring: &[usize]| newell_normal(ring, &model.vertices).dot(normal) / 2.0;
+    let region_area: f64 = region
+        .iter()
+        .map(|&i| area(&model.faces[i].vertex_ids))
+        .sum();
+
+    // The outer ring is the largest; the others must be holes inside it
+    let mut loops: Vec<(f64, Vec<usize>)> = model
+        .boundary_loops_of(region)
+        .into_iter()
+        .map(|ring| (area(&ring).abs(), ring))
+        .collect();
+    loops.sort_by(|a, b| b.0.total_cmp(&a.0));
+    let (exterior_area, exterior) = loops.first()?;
+    let holes_area: f64 = loops[1..].iter().map(|(area, _)| area).sum();
+    if (exterior_area - holes_area - region_area).abs() > EPSILON * region_area.max(1.0) {
+        return None;
+    }
+
+    let holes: Vec<Vec<usize>> = loops[1..].iter().map(|(_, ring)| ring.clone()).collect();
+    let axes = projection_axes(normal);
+    let mut ring = bridge_holes(exterior, &holes, axes, &model.vertices);
+    // The ring runs counter-clockwise seen from the positive side of the dropped axis
+    if normal[3 - axes[0] - axes[1]] < 0.0 {
+        ring.reverse();
+    }
+    Some(ring)
+}
+
+/// Edges of a ring as pairs of consecutive vertex IDs
+fn edges(ring: &[usize]) -> impl Iterator<Item = (usize, usize)> + '_ {
+    (0..ring.len()).map(move |k| (ring[k], ring[(k + 1) % ring.len()]))
+}
+
+/// Drop the vertices lying on the edge between their neighbours in every face using them
+fn remove_collinear_vertices(faces: &mut [Face], vertices: &[Vertex]) {
+    let mut faces_by_vertex: HashMap<usize, Vec<usize>> = HashMap::new();
+    for (i, face) in faces.iter().enumerate() {
+        for &id in &face.vertex_ids {
+            let users = faces_by_vertex.entry(id).or_default();
+            if users.last() != Some(&i) {
+                users.push(i);
+            }
+        }
+    }
+    let mut ids: Vec<usize> = faces_by_vertex.keys().copied().collect();
+    ids.sort_unstable();
+
+    for id in ids {
+        let users = &faces_by_vertex[&id];
+        let droppable = users.iter().all(|&i| {
+            let ring = &faces[i].vertex_ids;
+            // Vertices a keyhole ring passes twice are bridge ends, not edge points
+            if ring.len() <= 3 || ring.iter().filter(|&&v| v == id).count() != 1 {
+                return false;
+            }
+            let k = ring
+                .iter()
+                .position(|&v| v == id)
+                .expect("face uses the vertex");
+            let previous = ring[(k + ring.len() - 1) % ring.len()];
+            let next = ring[(k + 1) % ring.len()];
+            on_segment(vertices, previous, id, next)
+        });
+        if droppable {
+            for &i in users {
+                faces[i].vertex_ids.retain(|&v| v != id);
+            }
+        }
+    }
+}
+
+/// Whether vertex `p` lies between vertices `a` and `b`, within `MERGE_OFFSET` of their edge
+fn on_segment(vertices: &[Vertex], a: usize, p: usize, b: usize) -> bool {
+    let origin = vertices[a].point;
+    let ab = vertices[b].point - origin;
+    let ap = vertices[p].point - origin;
+    let length2 = ab.magnitude2();
+    if length2 <= 0.0 {
+        return false;
+    }
+    let t = ap.dot(ab) / length2;
+    t > 0.0 && t < 1.0 && (ap - ab * t).magnitude() <= MERGE_OFFSET
+}
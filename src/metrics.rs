@@ -0,0 +1,209 @@
+use crate::footprint::{signed_area, Footprint};
+use crate::model::Model;
+use crate::pointcloud::percentile_of;
+use crate::primitives::SurfaceType;
+use crate::stats::volume;
+use cgmath::Point3;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::f64::consts::PI;
+
+/// Size and shape of a building, with the 2D and 3D shape indices of
+/// Labetski et al. (2023), "3D building metrics for urban morphology".
+///
+/// Areas are in square metres, lengths and heights in metres and the volume in
+/// cubic metres. The indices are normalised to 1 for their ideal shape: a
+/// circle for `circularity_2d`, a sphere for `compactness_3d`, a hemisphere
+/// standing on the ground for `hemisphericality_3d` and a cube for
+/// `cubeness_3d`. Indices of a model without a footprint or volume are NaN.
+#[derive(Debug, Clone, Serialize)]
+pub struct ShapeMetrics {
+    pub volume: f64,
+    pub surface_area: f64, // area of the outer shell, storey floors excluded
+    pub ground_area: f64,
+    pub wall_area: f64,
+    pub roof_area: f64,
+    pub floor_area: f64, // area of the storey floors
+    pub footprint_area: f64,
+    pub footprint_perimeter: f64, // length of the footprint rings, holes included
+    pub z_min: f64,
+    pub z_max: f64,
+    pub roof_z_min: Option<f64>, // roof heights from the z of the roof vertices, if any
+    pub roof_z_50p: Option<f64>,
+    pub roof_z_70p: Option<f64>,
+    pub roof_z_max: Option<f64>,
+    pub circularity_2d: f64,      // 4πA / P² of the footprint
+    pub convexity_2d: f64,        // footprint area over the area of its convex hull
+    pub elongation_2d: f64,       // 1 - width / length of the smallest enclosing rectangle
+    pub fractality_2d: f64,       // 1 - ln A / (2 ln P) of the footprint
+    pub compactness_3d: f64,      // (36πV²)^(1/3) / S
+    pub hemisphericality_3d: f64, // 3√(2π) V / S^1.5
+    pub cubeness_3d: f64,         // 6 V^(2/3) / S
+    pub fractality_3d: f64,       // 1 - ln V / (1.5 ln S)
+}
+
+impl ShapeMetrics {
+    /// Measure a model.
+    ///
+    /// The areas per surface type and the footprint use the current surface
+    /// types, so classify the model first.
+    pub fn from_model(model: &Model) -> Self {
+        let mut areas = [0.0; 5];
+        let mut roof_z = Vec::new();
+        for face in &model.faces {
+            let index = match face.surface_type {
+                SurfaceType::Ground => 0,
+                SurfaceType::Wall => 1,
+                SurfaceType::Roof => 2,
+                SurfaceType::Floor => 3,
+                SurfaceType::Unknown => 4,
+            };
+            areas[index] += face.area(&model.vertices);
+            if face.surface_type == SurfaceType::Roof {
+                roof_z.extend(face.vertex_ids.iter().map(|&id| model.vertices[id].point.z));
+            }
+        }
+        let surface_area = areas[0] + areas[1] + areas[2] + areas[4];
+
+        let (z_min, z_max) = model
+            .vertices
+            .iter()
+            .map(|vertex| vertex.point.z)
+            .fold((f64::MAX, f64::MIN), |(low, high), z| {
+                (low.min(z), high.max(z))
+            });
+        let (z_min, z_max) = if model.vertices.is_empty() {
+            (0.0, 0.0)
+        } else {
+            (z_min, z_max)
+        };
+
+        let footprint = model.footprint();
+        let footprint_area = footprint.area();
+        let footprint_perimeter = perimeter(&footprint);
+        let hull = convex_hull(&footprint);
+        let volume = volume(model);
+
+        ShapeMetrics {
+            volume,
+            surface_area,
+            ground_area: areas[0],
+            wall_area: areas[1],
+            roof_area: areas[2],
+            floor_area: areas[3],
+            footprint_area,
+            footprint_perimeter,
+            z_min,
+            z_max,
+            roof_z_min: percentile_of(&mut roof_z, 0.0),
+            roof_z_50p: percentile_of(&mut roof_z, 0.5),
+            roof_z_70p: percentile_of(&mut roof_z, 0.7),
+            roof_z_max: percentile_of(&mut roof_z, 1.0),
+            circularity_2d: 4.0 * PI * footprint_area / footprint_perimeter.powi(2),
+            convexity_2d: footprint_area / signed_area(&hull),
+            elongation_2d: elongation(&hull),
+            fractality_2d: 1.0 - footprint_area.ln() / (2.0 * footprint_perimeter.ln()),
+            compactness_3d: (36.0 * PI * volume.powi(2)).cbrt() / surface_area,
+            hemisphericality_3d: 3.0 * (2.0 * PI).sqrt() * volume / surface_area.powf(1.5),
+            cubeness_3d: 6.0 * volume.powf(2.0 / 3.0) / surface_area,
+            fractality_3d: 1.0 - volume.ln() / (1.5 * surface_area.ln()),
+        }
+    }
+
+    /// The metrics as attributes named `prefix` followed by the field name,
+    /// leaving out the ones that are undefined for this model
+    pub fn attributes(&self, prefix: &str) -> Map<String, Value> {
+        let Ok(Value::Object(fields)) = serde_json::to_value(self) else {
+            return Map::new();
+        };
+        fields
+            .into_iter()
+            .filter(|(_, value)| !value.is_null())
+            .map(|(name, value)| (format!("{}{}", prefix, name), value))
+            .collect()
+    }
+}
+
+/// Length of all rings of a footprint projected on the XY plane
+fn perimeter(footprint: &Footprint) -> f64 {
+    footprint
+        .polygons
+        .iter()
+        .flat_map(|polygon| std::iter::once(&polygon.exterior).chain(&polygon.holes))
+        .map(|ring| {
+            (0..ring.len())
+                .map(|i| {
+                    let (a, b) = (ring[i], ring[(i + 1) % ring.len()]);
+                    (b.x - a.x).hypot(b.y - a.y)
+                })
+                .sum::<f64>()
+        })
+        .sum()
+}
+
+/// Counter-clockwise convex hull of the exterior points of a footprint in the
+/// XY plane, by Andrew's monotone chain
+fn convex_hull(footprint: &Footprint) -> Vec<Point3<f64>> {
+    let mut points: Vec<Point3<f64>> = footprint
+        .polygons
+        .iter()
+        .flat_map(|polygon| polygon.exterior.iter().copied())
+        .collect();
+    points.sort_by(|a, b| a.x.total_cmp(&b.x).then(a.y.total_cmp(&b.y)));
+    points.dedup_by(|a, b| a.x == b.x && a.y == b.y);
+    if points.len() < 3 {
+        return points;
+    }
+
+    let turn = |o: Point3<f64>, a: Point3<f64>, b: Point3<f64>| {
+        (a.x - o.x) * (b.y - o.y) - (a.y - o.y) * (b.x - o.x)
+    };
+    let mut hull: Vec<Point3<f64>> = Vec::with_capacity(2 * points.len());
+    // Lower chain left to right, then upper chain right to left
+    for pass in [points.clone(), points.into_iter().rev().collect()] {
+        let start = hull.len();
+        for p in pass {
+            while hull.len() >= start + 2
+                && turn(hull[hull.len() - 2], hull[hull.len() - 1], p) <= 0.0
+            {
+                hull.pop();
+            }
+            hull.push(p);
+        }
+        // The last point of a chain is the first of the next one
+        hull.pop();
+    }
+    hull
+}
+
+/// 1 - width / length of the smallest-area rectangle around a convex hull,
+/// which has a side along one of the hull edges
+fn elongation(hull: &[Point3<f64>]) -> f64 {
+    let mut best: Option<(f64, f64, f64)> = None;
+    for i in 0..hull.len() {
+        let (a, b) = (hull[i], hull[(i + 1) % hull.len()]);
+        let length = (b.x - a.x).hypot(b.y - a.y);
+        if length <= 0.0 {
+            continue;
+        }
+        let (ux, uy) = ((b.x - a.x) / length, (b.y - a.y) / length);
+        let (mut along_min, mut along_max, mut across_max) = (f64::MAX, f64::MIN, 0.0_f64);
+        for p in hull {
+            let (dx, dy) = (p.x - a.x, p.y - a.y);
+            let along = dx * ux + dy * uy;
+            along_min = along_min.min(along);
+            along_max = along_max.max(along);
+            across_max = across_max.max((dx * uy - dy * ux).abs());
+        }
+        let sides = (along_max - along_min, across_max);
+        let area = sides.0 * sides.1;
+        if best.is_none_or(|(best_area, _, _)| area < best_area) {
+            best = Some((area, sides.0, sides.1));
+        }
+    }
+
+    match best {
+        Some((_, a, b)) if a.max(b) > 0.0 => 1.0 - a.min(b) / a.max(b),
+        _ => f64::NAN,
+    }
+}
@@ -1,269 +1,244 @@
 use crate::error::{Error, Result};
+use crate::footprint::{ground_polygons, Footprint};
+use crate::format::{create_file, open_file, Encoding};
+use crate::merge::merge_coplanar_faces;
+use crate::metrics::ShapeMetrics;
+use crate::obj::{ObjReader, ObjWriter};
+use crate::ply::{PlyReader, PlyWriter};
+use crate::pointcloud::{percentile_of, PointCloud};
 use crate::primitives::{Face, SurfaceType, Vertex};
-use crate::{EPSILON, GROUND_HEIGHT_THRESHOLD, ROOF_HEIGHT_PERCENTILE, WALL_ANGLE_THRESHOLD};
+use crate::raster::Raster;
+use crate::record::{
+    building_path, record_boundary, record_origin, record_plane, record_surfaces, surface_mesh,
+};
+use crate::roof::{roof_planes, RoofAnalysis, RoofPlane};
+use crate::simplify::Simplification;
+use crate::stl::{StlReader, StlWriter};
+use crate::transform::{recentre, reproject_model, set_origin, transform_model, AffineTransform};
+use crate::triangulate::{triangulate_face, triangulate_polygon};
+use crate::{
+    EPSILON, GROUND_HEIGHT_THRESHOLD, ROOF_HEIGHT_PERCENTILE, STOREY_HEIGHT, WALL_ANGLE_THRESHOLD,
+    WELD_TOLERANCE,
+};
 use cgmath::{InnerSpace, Point3, Vector3};
+use serde::Serialize;
+use serde_json::{Map, Value};
 use std::collections::HashSet;
-use std::fs::File;
-use std::io::{BufRead, BufReader, Write};
-use std::str::FromStr;
+use std::io::{BufReader, BufWriter};
+use std::sync::Arc;
 use std::{collections::HashMap, path::Path};
 
 /// A 3D building model
 #[derive(Debug, Clone)]
 pub struct Model {
+    pub id: Option<String>, // building identifier, e.g. the OBJ object name
     pub vertices: Vec<Vertex>,
     pub faces: Vec<Face>,
+    pub attributes: Map<String, Value>, // attributes derived for the building, e.g. its height strategy
+    pub origin: Vector3<f64>, // horizontal offset of the vertices: world coordinates are point + origin
+    pub crs: Option<u32>,     // EPSG code of the world coordinates, if known
 }
 
-impl Model {
-    /// Create a new model with the given vertices and faces
-    pub fn new(vertices: Vec<Vertex>, faces: Vec<Face>) -> Self {
-        let mut model = Model { vertices, faces };
-        model.build_adjacency();
-        model
-    }
-
-    /// Load a model from an OBJ file
-    pub fn read_obj(path: &Path) -> Result<Self> {
-        // Resolve path: if relative, use it relative to current directory
-        let resolved_path = if path.is_absolute() {
-            path.to_path_buf()
-        } else {
-            // Normalize the path to remove any "./" prefixes
-            std::env::current_dir()
-                .map_err(Error::Io)?
-                .join(path)
-                .canonicalize()
-                .map_err(|e| {
-                    Error::Io(std::io::Error::new(
-                        e.kind(),
-                        format!("Failed to resolve path {}: {}", path.display(), e),
-                    ))
-                })?
-        };
-
-        let file = File::open(&resolved_path).map_err(|e| {
-            Error::Io(std::io::Error::new(
-                e.kind(),
-                format!("Failed to open file {}: {}", resolved_path.display(), e),
-            ))
-        })?;
-
-        let reader = BufReader::new(file);
-
-        let mut vertices = Vec::new();
-        let mut faces = Vec::new();
-
-        for (line_number, line_result) in reader.lines().enumerate() {
-            let line = line_result.map_err(Error::Io)?;
-            let line = line.trim();
+/// How the height of the LoD1.2 roof is derived
+#[derive(Debug, Clone, Default)]
+pub enum HeightStrategy {
+    /// Area-weighted average over the roof faces of the `ROOF_HEIGHT_PERCENTILE` of their z range
+    #[default]
+    RoofPercentile,
+    /// Percentile (0 to 1) of the z of the point cloud points inside the footprint
+    PointCloud {
+        cloud: Arc<PointCloud>,
+        percentile: f64,
+    },
+}
 
-            if line.is_empty() || line.starts_with('#') {
-                continue;
+impl HeightStrategy {
+    /// Short name of the strategy, e.g. `roof_percentile_70`
+    pub fn name(&self) -> String {
+        match self {
+            HeightStrategy::RoofPercentile => {
+                format!(
+                    "roof_percentile_{}",
+                    (ROOF_HEIGHT_PERCENTILE * 100.0).round()
+                )
             }
-
-            let parts: Vec<&str> = line.split_whitespace().collect();
-            if parts.is_empty() {
-                continue;
+            HeightStrategy::PointCloud { percentile, .. } => {
+                format!("point_cloud_percentile_{}", (percentile * 100.0).round())
             }
+        }
+    }
+}
 
-            match parts[0] {
-                "v" => {
-                    if parts.len() < 4 {
-                        return Err(Error::Io(std::io::Error::new(
-                            std::io::ErrorKind::InvalidData,
-                            format!(
-                                "Not enough components for vertex at line {}",
-                                line_number + 1
-                            ),
-                        )));
-                    }
-
-                    let x = f64::from_str(parts[1]).map_err(|_| {
-                        Error::Io(std::io::Error::new(
-                            std::io::ErrorKind::InvalidData,
-                            format!(
-                                "Invalid x coordinate: {} at line {}",
-                                parts[1],
-                                line_number + 1
-                            ),
-                        ))
-                    })?;
-
-                    let y = f64::from_str(parts[2]).map_err(|_| {
-                        Error::Io(std::io::Error::new(
-                            std::io::ErrorKind::InvalidData,
-                            format!(
-                                "Invalid y coordinate: {} at line {}",
-                                parts[2],
-                                line_number + 1
-                            ),
-                        ))
-                    })?;
-
-                    let z = f64::from_str(parts[3]).map_err(|_| {
-                        Error::Io(std::io::Error::new(
-                            std::io::ErrorKind::InvalidData,
-                            format!(
-                                "Invalid z coordinate: {} at line {}",
-                                parts[3],
-                                line_number + 1
-                            ),
-                        ))
-                    })?;
-
-                    vertices.push(Vertex {
-                        point: Point3::new(x, y, z),
-                        id: vertices.len(),
-                    });
-                }
-                "f" => {
-                    if parts.len() < 4 {
-                        return Err(Error::Io(std::io::Error::new(
-                            std::io::ErrorKind::InvalidData,
-                            format!(
-                                "Face must have at least 3 vertices at line {}",
-                                line_number + 1
-                            ),
-                        )));
-                    }
-
-                    let mut vertex_ids = Vec::new();
-                    for part in &parts[1..] {
-                        // Extract just the vertex index (ignore texture/normal indices)
-                        let vertex_str = part.split('/').next().unwrap_or("");
-                        let index = usize::from_str(vertex_str).map_err(|_| {
-                            Error::Io(std::io::Error::new(
-                                std::io::ErrorKind::InvalidData,
-                                format!(
-                                    "Invalid vertex index: {} at line {}",
-                                    vertex_str,
-                                    line_number + 1
-                                ),
-                            ))
-                        })?;
-
-                        // OBJ indices are 1-based, convert to 0-based
-                        if index == 0 || index > vertices.len() {
-                            return Err(Error::Io(std::io::Error::new(
-                                std::io::ErrorKind::InvalidData,
-                                format!("Invalid vertex index at line {}", line_number + 1),
-                            )));
-                        }
-
-                        vertex_ids.push(index - 1);
-                    }
+/// How the ground level of the LoD1.2 base is derived.
+///
+/// Ground surfaces are always found near the lowest z of the mesh; the
+/// strategy only decides at which z their footprint is placed.
+#[derive(Debug, Clone, Default)]
+pub enum GroundStrategy {
+    /// The lowest z of the ground surfaces, keeping their own z
+    #[default]
+    Lowest,
+    /// Percentile (0 to 1) of the z of the ground surface vertices
+    Percentile(f64),
+    /// Median z of the vertices of the footprint boundary
+    BoundaryMedian,
+    /// Median of the terrain model cells under the footprint
+    Dtm(Arc<Raster>),
+    /// The ground surfaces as they are, extended along a sloped terrain so
+    /// that the base follows the terrain contact line, see `mark_sloped_ground_faces`
+    Sloped,
+}
 
-                    faces.push(Face::new(vertex_ids));
-                }
-                _ => {
-                    // Ignore other types like normals, textures, etc.
-                    continue;
-                }
+impl GroundStrategy {
+    /// Short name of the strategy, e.g. `ground_percentile_10`
+    pub fn name(&self) -> String {
+        match self {
+            GroundStrategy::Lowest => "lowest".to_string(),
+            GroundStrategy::Percentile(percentile) => {
+                format!("ground_percentile_{}", (percentile * 100.0).round())
             }
+            GroundStrategy::BoundaryMedian => "boundary_median".to_string(),
+            GroundStrategy::Dtm(_) => "dtm".to_string(),
+            GroundStrategy::Sloped => "sloped".to_string(),
         }
-
-        Ok(Self::new(vertices, faces))
     }
+}
 
-    /// Write the model to an OBJ file
-    pub fn write_obj(&self, path: &Path) -> Result<()> {
-        let resolved_path = if path.is_absolute() {
-            path.to_path_buf()
-        } else {
-            // Normalize the path to remove any "./" prefixes
-            std::env::current_dir()
-                .map_err(Error::Io)?
-                .join(path)
-                .canonicalize()
-                .map_err(|e| {
-                    Error::Io(std::io::Error::new(
-                        e.kind(),
-                        format!("Failed to resolve path {}: {}", path.display(), e),
-                    ))
-                })?
-        };
+/// Storey height of a LoD1.2 model split into storeys
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StoreyHeight {
+    /// A fixed storey height in metres; the top storey takes what is left, from half to one and a half storeys
+    Fixed(f64),
+    /// The building height divided into the whole number of storeys closest to `STOREY_HEIGHT`
+    Estimated,
+}
 
-        println!("Writing model to {}", resolved_path.display());
+/// Options of the conversion of a single building from LoD2.2 to LoD1.2
+#[derive(Debug, Clone, Default)]
+pub struct Lod1Options {
+    /// How the height of the LoD1.2 roof is derived
+    pub height: HeightStrategy,
+    /// How the ground level of the LoD1.2 base is derived
+    pub ground: GroundStrategy,
+    /// How the footprint is simplified before it is extruded, if at all
+    pub simplify: Option<Simplification>,
+    /// Storey height of the storeys the LoD1.2 block is split into, if at all
+    pub storeys: Option<StoreyHeight>,
+    /// Whether adjacent coplanar faces of the output are merged into polygons
+    pub merge_coplanar: bool,
+    /// Whether the shape metrics of the input and output are recorded as attributes
+    pub shape_metrics: bool,
+    /// Rerun recording every stage of the conversion is logged to, if any
+    pub recording: Option<rerun::RecordingStream>,
+    /// Name of the input the buildings come from, e.g. its path, under which they are logged to `recording`
+    pub recording_source: Option<String>,
+}
 
-        let mut file = File::create(&resolved_path).map_err(|e| {
-            Error::Io(std::io::Error::new(
-                e.kind(),
-                format!("Failed to create file {}: {}", resolved_path.display(), e),
-            ))
-        })?;
+/// Statistics collected while converting a model from LoD2.2 to LoD1.2
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ConversionStats {
+    pub input_vertices: usize,
+    pub input_faces: usize,
+    pub ground_faces: usize,
+    pub wall_faces: usize,
+    pub roof_faces: usize,
+    pub ground_z: f64, // z of the LoD1.2 base, by default the lowest z of the ground surfaces
+    pub roof_z: f64,   // z of the LoD1.2 roof
+    pub height: f64,   // roof_z - ground_z
+    pub output_vertices: usize,
+    pub output_faces: usize,
+}
 
-        // Write header
-        writeln!(file, "# Converted LoD1.2 model").map_err(Error::Io)?;
+impl Model {
+    /// Create a new model with the given vertices and faces
+    pub fn new(vertices: Vec<Vertex>, faces: Vec<Face>) -> Self {
+        let mut model = Model {
+            id: None,
+            vertices,
+            faces,
+            attributes: Map::new(),
+            origin: Vector3::new(0.0, 0.0, 0.0),
+            crs: None,
+        };
+        model.build_adjacency();
+        model
+    }
 
-        // Create collections for OBJ vertices and faces
-        let mut obj_vertices: Vec<Point3<f64>> = Vec::new();
-        let mut obj_faces: Vec<Vec<usize>> = Vec::new();
+    /// Load a model from an OBJ file, merging all objects into one model
+    pub fn read_obj(path: &Path) -> Result<Self> {
+        ObjReader::new(BufReader::new(open_file(path)?)).read_merged()
+    }
 
-        // Process faces and collect unique vertices
-        for face in &self.faces {
-            let mut face_indices = Vec::new();
+    /// Write the model to an OBJ file, welding vertices closer than `WELD_TOLERANCE`
+    pub fn write_obj(&self, path: &Path) -> Result<()> {
+        self.write_obj_with_tolerance(path, WELD_TOLERANCE)
+    }
 
-            for &vertex_id in &face.vertex_ids {
-                // For each vertex, find if it already exists in our output vertices
-                let vertex = &self.vertices.iter().find(|v| v.id == vertex_id).unwrap();
-                // let vertex = &self.vertices[vertex_id];
-                let point = &vertex.point;
-
-                let mut vertex_index = None;
-
-                for (i, existing_vertex) in obj_vertices.iter().enumerate() {
-                    // Compare using epsilon for floating point precision
-                    if (existing_vertex.x - point.x).abs() < EPSILON
-                        && (existing_vertex.y - point.y).abs() < EPSILON
-                        && (existing_vertex.z - point.z).abs() < EPSILON
-                    {
-                        vertex_index = Some(i);
-                        break;
-                    }
-                }
+    /// Write the model to an OBJ file, welding vertices closer than `tolerance`
+    pub fn write_obj_with_tolerance(&self, path: &Path, tolerance: f64) -> Result<()> {
+        let mut writer = ObjWriter::new(BufWriter::new(create_file(path)?), tolerance)?;
+        writer.write_model(self)?;
+        writer.finish()?;
 
-                let index = match vertex_index {
-                    Some(idx) => idx,
-                    None => {
-                        let idx = obj_vertices.len();
-                        obj_vertices.push(*point);
-                        idx
-                    }
-                };
+        Ok(())
+    }
 
-                face_indices.push(index);
-            }
+    /// Load a model from an ASCII or binary STL file, merging all solids into one model
+    pub fn read_stl(path: &Path) -> Result<Self> {
+        StlReader::new(BufReader::new(open_file(path)?)).read_merged()
+    }
 
-            obj_faces.push(face_indices);
-        }
+    /// Write the triangulated model to an STL file
+    pub fn write_stl(&self, path: &Path, encoding: Encoding) -> Result<()> {
+        let mut writer = StlWriter::new(BufWriter::new(create_file(path)?), encoding);
+        writer.write_model(self)?;
+        writer.finish()?;
 
-        // Write vertices
-        for vertex in &obj_vertices {
-            writeln!(file, "v {} {} {}", vertex.x, vertex.y, vertex.z).map_err(Error::Io)?;
-        }
+        Ok(())
+    }
 
-        // Write faces
-        for face in &obj_faces {
-            write!(file, "f").map_err(Error::Io)?;
-            for &index in face {
-                // OBJ indices are 1-based
-                write!(file, " {}", index + 1).map_err(Error::Io)?;
-            }
-            writeln!(file).map_err(Error::Io)?;
-        }
+    /// Load a model from an ASCII or binary PLY file, with surface types if present
+    pub fn read_ply(path: &Path) -> Result<Self> {
+        PlyReader::new(BufReader::new(open_file(path)?)).read()
+    }
+
+    /// Write the model to a PLY file with a per-face `surface_type` property
+    pub fn write_ply(&self, path: &Path, encoding: Encoding) -> Result<()> {
+        let mut writer =
+            PlyWriter::new(BufWriter::new(create_file(path)?), encoding, WELD_TOLERANCE);
+        writer.write_model(self)?;
+        writer.finish()?;
 
         Ok(())
     }
 
     /// Build the adjacency information for faces
-    fn build_adjacency(&mut self) {
+    pub(crate) fn build_adjacency(&mut self) {
+        // Index faces by vertex so only faces sharing a vertex are compared
+        let mut faces_by_vertex: HashMap<usize, Vec<usize>> = HashMap::new();
+        for (i, face) in self.faces.iter().enumerate() {
+            for &vertex_id in &face.vertex_ids {
+                faces_by_vertex.entry(vertex_id).or_default().push(i);
+            }
+        }
+
         for i in 0..self.faces.len() {
-            for j in 0..self.faces.len() {
-                if i != j && self.faces[i].is_adjacent_to(&self.faces[j]) {
-                    self.faces[i].adjacent_faces.push(j);
+            // Two faces are adjacent if they share at least two vertex IDs
+            let mut shared: HashMap<usize, usize> = HashMap::new();
+            for vertex_id in &self.faces[i].vertex_ids {
+                for &j in &faces_by_vertex[vertex_id] {
+                    if i != j {
+                        *shared.entry(j).or_insert(0) += 1;
+                    }
                 }
             }
+
+            let mut adjacent = shared
+                .into_iter()
+                .filter_map(|(j, count)| if count >= 2 { Some(j) } else { None })
+                .collect::<Vec<_>>();
+            adjacent.sort_unstable();
+            self.faces[i].adjacent_faces = adjacent;
         }
     }
 
@@ -301,7 +276,6 @@ impl Model {
                 ground_faces.insert(i);
             }
         }
-        println!("Minimum z value: {}", min_z.1);
 
         self.faces
             .iter_mut()
@@ -312,21 +286,130 @@ impl Model {
         ground_faces.into_iter().collect()
     }
 
+    /// Extend the ground surfaces along a sloped terrain.
+    ///
+    /// Ground faces must be almost horizontal and near the lowest z, so on a
+    /// slope part of the ground is taken as roof. Starting from the ground
+    /// faces, or else from the lowest face, every adjacent face that isn't a
+    /// wall and has no other face below it joins the ground, however steep or
+    /// high it is.
+    fn mark_sloped_ground_faces(&mut self) {
+        let up = Vector3::new(0.0, 0.0, 1.0);
+        let candidates: Vec<bool> = self
+            .faces
+            .iter()
+            .map(|face| face.normal(&self.vertices).dot(up).abs() >= WALL_ANGLE_THRESHOLD)
+            .collect();
+        let triangles: Vec<Vec<[Point3<f64>; 3]>> = self
+            .faces
+            .iter()
+            .map(|face| {
+                triangulate_face(face, &self.vertices)
+                    .into_iter()
+                    .map(|triangle| triangle.map(|id| self.vertices[id].point))
+                    .collect()
+            })
+            .collect();
+
+        // Grid of the triangles of the candidate faces by their horizontal
+        // bounding box, so only the triangles near a point are searched
+        let (mut min, mut max) = ([f64::MAX; 2], [f64::MIN; 2]);
+        for vertex in &self.vertices {
+            min = [min[0].min(vertex.point.x), min[1].min(vertex.point.y)];
+            max = [max[0].max(vertex.point.x), max[1].max(vertex.point.y)];
+        }
+        let count: usize = (0..self.faces.len())
+            .filter(|&j| candidates[j])
+            .map(|j| triangles[j].len())
+            .sum();
+        let cell =
+            ((max[0] - min[0]).max(max[1] - min[1]) / (count.max(1) as f64).sqrt()).max(EPSILON);
+        let key = |x: f64, y: f64| {
+            (
+                ((x - min[0]) / cell).floor() as i64,
+                ((y - min[1]) / cell).floor() as i64,
+            )
+        };
+        let mut grid: HashMap<(i64, i64), Vec<(usize, usize)>> = HashMap::new();
+        for j in (0..self.faces.len()).filter(|&j| candidates[j]) {
+            for (k, triangle) in triangles[j].iter().enumerate() {
+                let xs = triangle.map(|p| p.x);
+                let ys = triangle.map(|p| p.y);
+                let low = key(
+                    xs.into_iter().fold(f64::MAX, f64::min) - EPSILON,
+                    ys.into_iter().fold(f64::MAX, f64::min) - EPSILON,
+                );
+                let high = key(
+                    xs.into_iter().fold(f64::MIN, f64::max) + EPSILON,
+                    ys.into_iter().fold(f64::MIN, f64::max) + EPSILON,
+                );
+                for cx in low.0..=high.0 {
+                    for cy in low.1..=high.1 {
+                        grid.entry((cx, cy)).or_default().push((j, k));
+                    }
+                }
+            }
+        }
+
+        // Whether a face lies below the middle of the largest triangle of face i
+        let has_face_below: Vec<bool> = (0..self.faces.len())
+            .map(|i| {
+                if !candidates[i] {
+                    return false;
+                }
+                let Some(largest) = triangles[i]
+                    .iter()
+                    .max_by(|a, b| projected_area(a).total_cmp(&projected_area(b)))
+                else {
+                    return false;
+                };
+                let centre = Point3::new(
+                    (largest[0].x + largest[1].x + largest[2].x) / 3.0,
+                    (largest[0].y + largest[1].y + largest[2].y) / 3.0,
+                    (largest[0].z + largest[1].z + largest[2].z) / 3.0,
+                );
+                grid.get(&key(centre.x, centre.y))
+                    .into_iter()
+                    .flatten()
+                    .filter(|&&(j, _)| j != i)
+                    .filter_map(|&(j, k)| z_at(&triangles[j][k], centre.x, centre.y))
+                    .any(|z| z < centre.z - EPSILON)
+            })
+            .collect();
+
+        let mut stack: Vec<usize> = (0..self.faces.len())
+            .filter(|&i| self.faces[i].surface_type == SurfaceType::Ground)
+            .collect();
+        if stack.is_empty() {
+            let lowest = (0..self.faces.len())
+                .filter(|&i| candidates[i] && !has_face_below[i])
+                .min_by(|&a, &b| {
+                    let z = |i: usize| self.faces[i].z_range(&self.vertices).0;
+                    z(a).total_cmp(&z(b))
+                });
+            stack.extend(lowest);
+        }
+
+        let mut ground: HashSet<usize> = stack.iter().copied().collect();
+        while let Some(i) = stack.pop() {
+            for &j in &self.faces[i].adjacent_faces {
+                if candidates[j] && !ground.contains(&j) && !has_face_below[j] {
+                    ground.insert(j);
+                    stack.push(j);
+                }
+            }
+        }
+
+        for i in ground {
+            self.faces[i].surface_type = SurfaceType::Ground;
+        }
+    }
+
     /// Classify all faces as ground, wall, or roof based on orientation
-    fn classify_surfaces(&mut self) {
+    pub fn classify_surfaces(&mut self) {
         // First identify ground faces
         self.mark_ground_faces();
 
-        // Debug: count the number of ground faces
-        // =====================================
-        let ground_face_count = self
-            .faces
-            .iter()
-            .filter(|face| face.surface_type == SurfaceType::Ground)
-            .count();
-        println!("Ground faces: {}", ground_face_count);
-        // =====================================
-
         // Then identify walls (normals approximately horizontal)
         let up = Vector3::new(0.0, 0.0, 1.0);
         for i in 0..self.faces.len() {
@@ -345,36 +428,11 @@ impl Model {
                 self.faces[i].surface_type = SurfaceType::Roof;
             }
         }
-
-        // Debug: print the faces with their surface type
-        // =====================================
-        println!(
-            "Ground faces: {}",
-            self.faces
-                .iter()
-                .filter(|face| face.surface_type == SurfaceType::Ground)
-                .count()
-        );
-        println!(
-            "Wall faces: {}",
-            self.faces
-                .iter()
-                .filter(|face| face.surface_type == SurfaceType::Wall)
-                .count()
-        );
-        println!(
-            "Roof faces: {}",
-            self.faces
-                .iter()
-                .filter(|face| face.surface_type == SurfaceType::Roof)
-                .count()
-        );
-        // =====================================
     }
 
     /// Calculate the appropriate height for the LoD1.2 model based on
     /// the weighted average of roof surfaces
-    fn calculate_lod1_2_height(&self) -> f64 {
+    pub(crate) fn calculate_lod1_2_height(&self) -> f64 {
         let mut total_area = 0.0;
         let mut weighted_height_sum = 0.0;
 
@@ -404,14 +462,84 @@ impl Model {
         }
     }
 
+    /// Storey height and floor levels between `ground_z` and `roof_z` of a
+    /// model reduced to its ground surfaces. Levels below the highest ground
+    /// vertex, e.g. on sloped ground, are left out.
+    fn storey_floor_levels(
+        &self,
+        storeys: &StoreyHeight,
+        ground_z: f64,
+        roof_z: f64,
+    ) -> (f64, Vec<f64>) {
+        let height = roof_z - ground_z;
+        let storey_height = match *storeys {
+            StoreyHeight::Fixed(storey_height) => storey_height,
+            StoreyHeight::Estimated => height / (height / STOREY_HEIGHT).round().max(1.0),
+        };
+        let count = (height / storey_height).round().max(1.0) as usize;
+        let highest_ground = self
+            .vertices
+            .iter()
+            .map(|vertex| vertex.point.z)
+            .fold(ground_z, f64::max);
+        let levels = (1..count)
+            .map(|k| ground_z + k as f64 * storey_height)
+            .filter(|&level| level > highest_ground + EPSILON && level < roof_z - EPSILON)
+            .collect();
+        (storey_height, levels)
+    }
+
+    /// Ground level of a classified model by `strategy`, or `None` to keep the ground surfaces as they are
+    fn ground_level(&self, strategy: &GroundStrategy) -> Result<Option<f64>> {
+        let level = match strategy {
+            GroundStrategy::Lowest | GroundStrategy::Sloped => return Ok(None),
+            GroundStrategy::Percentile(percentile) => {
+                let ids: HashSet<usize> = self
+                    .faces
+                    .iter()
+                    .filter(|face| face.surface_type == SurfaceType::Ground)
+                    .flat_map(|face| face.vertex_ids.iter().copied())
+                    .collect();
+                let mut heights: Vec<f64> =
+                    ids.iter().map(|&id| self.vertices[id].point.z).collect();
+                percentile_of(&mut heights, *percentile)
+            }
+            GroundStrategy::BoundaryMedian => {
+                let mut heights: Vec<f64> = self
+                    .footprint()
+                    .polygons
+                    .iter()
+                    .flat_map(|polygon| std::iter::once(&polygon.exterior).chain(&polygon.holes))
+                    .flatten()
+                    .map(|point| point.z)
+                    .collect();
+                percentile_of(&mut heights, 0.5)
+            }
+            GroundStrategy::Dtm(raster) => {
+                let mut heights = raster.sample_footprint(&self.world_footprint());
+                Some(percentile_of(&mut heights, 0.5).ok_or_else(|| {
+                    Error::Io(std::io::Error::new(
+                        std::io::ErrorKind::InvalidData,
+                        "No terrain model cells under the footprint",
+                    ))
+                })?)
+            }
+        };
+        Ok(level)
+    }
+
     /// Remove all faces labeled as wall or roof, and their unused vertices
     fn remove_non_ground_surfaces(&mut self) {
         // Keep only ground surfaces
         self.faces
             .retain(|face| face.surface_type == SurfaceType::Ground);
+        self.remove_unused_vertices();
+    }
 
-        // Collect the set of vertex IDs that are still in use
-        let mut used_vertices = std::collections::HashSet::new();
+    /// Remove the vertices no face uses, renumbering the others in order
+    pub(crate) fn remove_unused_vertices(&mut self) {
+        // Collect the vertex IDs that are still in use, sorted to keep the output deterministic
+        let mut used_vertices = std::collections::BTreeSet::new();
         for face in &self.faces {
             for &vertex_id in &face.vertex_ids {
                 used_vertices.insert(vertex_id);
@@ -423,7 +551,7 @@ impl Model {
         let mut new_vertices = Vec::new();
 
         for &old_id in used_vertices.iter() {
-            let vertex = &self.vertices.iter().find(|v| v.id == old_id).unwrap();
+            let vertex = &self.vertices[old_id];
             let new_id = new_vertices.len();
 
             // Create a new vertex with updated ID
@@ -450,165 +578,235 @@ impl Model {
     }
 
     /// Identify and return the boundary edges of ground surfaces
-    fn find_boundary_edges(&self) -> Vec<(usize, usize)> {
-        // Create a map to track how many times each edge appears
-        let mut edge_count: HashMap<(usize, usize), usize> = HashMap::new();
+    pub(crate) fn find_boundary_edges(&self) -> Vec<(usize, usize)> {
+        boundary_edges(
+            self.faces
+                .iter()
+                .filter(|face| face.surface_type == SurfaceType::Ground),
+        )
+    }
 
-        // Go through all ground faces and count edge occurrences
-        for face in &self.faces {
-            if face.surface_type != SurfaceType::Ground {
-                continue;
-            }
+    /// Split the boundary edges of the ground surfaces into closed loops of vertex IDs
+    pub(crate) fn boundary_loops(&self) -> Vec<Vec<usize>> {
+        edge_loops(&self.find_boundary_edges())
+    }
 
-            let vertex_count = face.vertex_ids.len();
-            for i in 0..vertex_count {
-                let v1 = face.vertex_ids[i];
-                let v2 = face.vertex_ids[(i + 1) % vertex_count];
+    /// Boundary loops of vertex IDs of a subset of the faces, given by index
+    pub(crate) fn boundary_loops_of(&self, faces: &[usize]) -> Vec<Vec<usize>> {
+        edge_loops(&boundary_edges(faces.iter().map(|&i| &self.faces[i])))
+    }
 
-                // Sort the vertices to ensure the same edge is counted correctly regardless of direction
-                let edge = if v1 < v2 { (v1, v2) } else { (v2, v1) };
+    /// The 2D outline of the ground surfaces, with holes
+    pub fn footprint(&self) -> Footprint {
+        Footprint::from_model(self)
+    }
 
-                *edge_count.entry(edge).or_insert(0) += 1;
-            }
-        }
+    /// The footprint in world coordinates, e.g. to look up terrain and point cloud heights
+    pub fn world_footprint(&self) -> Footprint {
+        self.footprint().translated(self.origin)
+    }
 
-        // if edge count is not 2, it is a boundary edge
-        let boundaries = edge_count
-            .iter()
-            .filter_map(|(&edge, &count)| if count != 2 { Some(edge) } else { None })
-            .collect::<Vec<_>>();
+    /// Roof faces of a classified model merged into planes, see `roof_planes`
+    pub fn roof_planes(&self) -> Vec<RoofPlane> {
+        roof_planes(self)
+    }
 
-        boundaries
+    /// Roof planes and roof type of a classified model, or `None` without roof surfaces
+    pub fn roof_analysis(&self) -> Option<RoofAnalysis> {
+        RoofAnalysis::from_model(self)
     }
 
-    /// Order boundary edges to form a continuous loop
-    fn order_boundary_edges(&self, edges: &[(usize, usize)]) -> Vec<usize> {
-        if edges.is_empty() {
-            return Vec::new();
-        }
+    /// Replace the faces by the ground surfaces of the simplified footprint,
+    /// see `simplify_footprint`
+    pub fn simplify_ground(&mut self, simplification: &Simplification) {
+        let ground = self.footprint().simplify(simplification).to_ground();
+        self.vertices = ground.vertices;
+        self.faces = ground.faces;
+    }
 
-        let mut ordered_vertices = Vec::new();
-        let mut remaining_edges: Vec<(usize, usize)> = edges.to_vec();
-
-        // Start with the first edge
-        let first_edge = remaining_edges.remove(0);
-        ordered_vertices.push(first_edge.0);
-        ordered_vertices.push(first_edge.1);
-
-        // Continue connecting edges until we've used them all
-        while !remaining_edges.is_empty() {
-            let last_vertex = *ordered_vertices.last().unwrap();
-            let mut found = false;
-
-            for i in 0..remaining_edges.len() {
-                let (v1, v2) = remaining_edges[i];
-
-                if v1 == last_vertex {
-                    ordered_vertices.push(v2);
-                    remaining_edges.remove(i);
-                    found = true;
-                    break;
-                } else if v2 == last_vertex {
-                    ordered_vertices.push(v1);
-                    remaining_edges.remove(i);
-                    found = true;
-                    break;
-                }
-            }
+    /// Merge adjacent coplanar faces into polygons, see `merge_coplanar_faces`
+    pub fn merge_coplanar_faces(&mut self) {
+        merge_coplanar_faces(self);
+    }
 
-            if !found {
-                // If we couldn't find a connecting edge, the boundary might be disconnected
-                // Just add the next edge and continue
-                if !remaining_edges.is_empty() {
-                    let edge = remaining_edges.remove(0);
-                    ordered_vertices.push(edge.0);
-                    ordered_vertices.push(edge.1);
-                }
-            }
-        }
+    /// World coordinates of a point in the local coordinates of the vertices
+    pub fn world_point(&self, point: Point3<f64>) -> Point3<f64> {
+        point + self.origin
+    }
 
-        // Remove duplicates while preserving order
-        let mut unique_vertices = Vec::new();
-        for &vertex in &ordered_vertices {
-            if !unique_vertices.contains(&vertex) {
-                unique_vertices.push(vertex);
-            }
-        }
+    /// Move the local origin to the centre of the model, see `recentre`
+    pub fn recentre(&mut self) {
+        recentre(self);
+    }
 
-        unique_vertices
+    /// Move the local origin to `origin`, keeping the world coordinates
+    pub fn set_origin(&mut self, origin: Vector3<f64>) {
+        set_origin(self, origin);
     }
 
-    /// Extrude the ground surface to create the LoD1.2 model
-    fn extrude_to_lod1(&mut self, target_height: f64) {
-        // Find boundary edges of ground surface
-        let boundary_edges = self.find_boundary_edges();
-        let boundary_vertices = self.order_boundary_edges(&boundary_edges);
+    /// Apply an affine transform to the world coordinates, see `transform_model`
+    pub fn transform(&mut self, transform: &AffineTransform) {
+        transform_model(self, transform);
+    }
 
-        if boundary_vertices.is_empty() {
+    /// Reproject the world coordinates to the EPSG code `target`, see `reproject_model`
+    pub fn reproject(&mut self, target: u32) -> Result<()> {
+        reproject_model(self, target)
+    }
+
+    /// Extrude the ground surface to create the LoD1.2 model.
+    ///
+    /// Walls are raised along every boundary loop of the ground, including the
+    /// boundaries of courtyards, and every footprint polygon gets a flat roof:
+    /// a single face, or triangles if the polygon has holes. Every floor level
+    /// between the ground and the roof splits the walls and gets floor
+    /// surfaces like the roof, facing up.
+    pub(crate) fn extrude_to_lod1(&mut self, target_height: f64, floor_levels: &[f64]) {
+        let polygons = ground_polygons(self);
+        if polygons.is_empty() {
             return;
         }
 
-        // Create top vertices at the target height
-        let mut top_vertex_ids = Vec::new();
-        for &index in &boundary_vertices {
-            let original_vertex = &self.vertices[index];
-            let top_point = Point3::new(
-                original_vertex.point.x,
-                original_vertex.point.y,
-                target_height,
-            );
-
-            let new_id = self.vertices.len();
-            self.vertices.push(Vertex {
-                point: top_point,
-                id: new_id,
-            });
+        // Create vertices at every level up to the target height, once per boundary vertex
+        let levels: Vec<f64> = floor_levels
+            .iter()
+            .copied()
+            .chain(std::iter::once(target_height))
+            .collect();
+        let mut level_vertex_ids: HashMap<usize, Vec<usize>> = HashMap::new();
+        for &index in polygons.iter().flatten().flatten() {
+            if level_vertex_ids.contains_key(&index) {
+                continue;
+            }
+            let original_point = self.vertices[index].point;
+            let mut column = vec![index];
+            for &level in &levels {
+                let point = Point3::new(original_point.x, original_point.y, level);
+                let new_id = self.vertices.len();
+                self.vertices.push(Vertex { point, id: new_id });
+                column.push(new_id);
+            }
 
-            top_vertex_ids.push(new_id);
+            level_vertex_ids.insert(index, column);
         }
 
-        // Create wall faces
-        for i in 0..boundary_vertices.len() {
-            let next_i = (i + 1) % boundary_vertices.len();
-
-            let bottom_left = boundary_vertices[i];
-            let bottom_right = boundary_vertices[next_i];
-            let top_left = top_vertex_ids[i];
-            let top_right = top_vertex_ids[next_i];
-
-            // Create a wall face (rectangle) from the two ground vertices and two top vertices
-            let wall_face = Face {
-                vertex_ids: vec![bottom_left, bottom_right, top_right, top_left],
-                surface_type: SurfaceType::Wall,
-                adjacent_faces: Vec::new(),
-            };
+        for rings in &polygons {
+            // Create wall faces, one per edge and storey; exterior rings run
+            // counter-clockwise and holes clockwise, so the walls face outwards
+            for ring in rings {
+                for i in 0..ring.len() {
+                    let left = &level_vertex_ids[&ring[i]];
+                    let right = &level_vertex_ids[&ring[(i + 1) % ring.len()]];
+
+                    // Create a wall face (rectangle) from two vertices of a level and two of the next
+                    for k in 0..levels.len() {
+                        self.faces.push(Face {
+                            vertex_ids: vec![left[k], right[k], right[k + 1], left[k + 1]],
+                            surface_type: SurfaceType::Wall,
+                            adjacent_faces: Vec::new(),
+                        });
+                    }
+                }
+            }
 
-            self.faces.push(wall_face);
+            // Create floor faces at every floor level, and roof faces at the top
+            for (k, &level) in levels.iter().enumerate() {
+                let level_rings: Vec<Vec<usize>> = rings
+                    .iter()
+                    .map(|ring| ring.iter().map(|id| level_vertex_ids[id][k + 1]).collect())
+                    .collect();
+                let surfaces = if level_rings.len() == 1 {
+                    level_rings.into_iter().collect()
+                } else {
+                    triangulate_polygon(&level_rings[0], &level_rings[1..], &self.vertices)
+                        .into_iter()
+                        .map(|triangle| triangle.to_vec())
+                        .collect::<Vec<_>>()
+                };
+                let surface_type = if level < target_height {
+                    SurfaceType::Floor
+                } else {
+                    SurfaceType::Roof
+                };
+                for vertex_ids in surfaces {
+                    self.faces.push(Face {
+                        vertex_ids,
+                        surface_type,
+                        adjacent_faces: Vec::new(),
+                    });
+                }
+            }
         }
 
-        // Create roof face
-        let roof_face = Face {
-            vertex_ids: top_vertex_ids,
-            surface_type: SurfaceType::Roof,
-            adjacent_faces: Vec::new(),
-        };
-
-        self.faces.push(roof_face);
-
         // Update adjacency information
         self.build_adjacency();
     }
 
+    /// Convert the model from LoD2.2 to LoD1.2 with the default options
+    pub fn to_lod1_2(&mut self) -> Result<ConversionStats> {
+        self.to_lod1_2_with(&Lod1Options::default())
+    }
+
     /// Convert the model from LoD2.2 to LoD1.2
-    pub fn to_lod1_2(&mut self) -> Result<()> {
-        // Debug: print the number of faces and vertices
-        // =====================================
-        println!("Number of faces: {}", self.faces.len());
-        println!("Number of vertices: {}", self.vertices.len());
-        // =====================================
+    pub fn to_lod1_2_with(&mut self, options: &Lod1Options) -> Result<ConversionStats> {
+        self.to_lod1_2_indexed(options, 0)
+    }
+
+    /// Convert the model from LoD2.2 to LoD1.2 as the building `index` of its
+    /// input, which sets its entity and time in `options.recording`
+    pub fn to_lod1_2_indexed(
+        &mut self,
+        options: &Lod1Options,
+        index: usize,
+    ) -> Result<ConversionStats> {
+        let mut stats = ConversionStats {
+            input_vertices: self.vertices.len(),
+            input_faces: self.faces.len(),
+            ..Default::default()
+        };
+
+        let recording = options.recording.as_ref().map(|recording| {
+            let building = building_path(self, options.recording_source.as_deref(), index);
+            (recording, building)
+        });
+        if let Some((recording, building)) = &recording {
+            record_origin(recording, building, self, index)?;
+            record_surfaces(recording, building, self, "input", false)?;
+        }
 
         // Step 1: Classify all surfaces
         self.classify_surfaces();
+        // Without an almost horizontal ground face, e.g. on a steep slope, every
+        // strategy takes its ground surfaces from the sloped ground detection
+        let has_ground = self
+            .faces
+            .iter()
+            .any(|face| face.surface_type == SurfaceType::Ground);
+        if matches!(options.ground, GroundStrategy::Sloped) || !has_ground {
+            self.mark_sloped_ground_faces();
+        }
+        if let Some(roof) = self.roof_analysis() {
+            self.attributes
+                .insert("roof_type".to_string(), roof.roof_type.name().into());
+            self.attributes
+                .insert("roof_type_confidence".to_string(), roof.confidence.into());
+        }
+        for face in &self.faces {
+            match face.surface_type {
+                SurfaceType::Ground => stats.ground_faces += 1,
+                SurfaceType::Wall => stats.wall_faces += 1,
+                SurfaceType::Roof => stats.roof_faces += 1,
+                SurfaceType::Floor | SurfaceType::Unknown => {}
+            }
+        }
+
+        if let Some((recording, building)) = &recording {
+            record_surfaces(recording, building, self, "classified", true)?;
+        }
+
+        let input_metrics = options
+            .shape_metrics
+            .then(|| ShapeMetrics::from_model(self));
 
         // Check if we found any ground surfaces
         let ground_faces = self
@@ -622,88 +820,129 @@ impl Model {
                 "No ground surfaces found",
             )));
         }
+        let (lowest_z, highest_z) = ground_faces
+            .iter()
+            .map(|face| face.z_range(&self.vertices))
+            .fold((f64::MAX, f64::MIN), |(low, high), (min, max)| {
+                (low.min(min), high.max(max))
+            });
+        let ground_level = self.ground_level(&options.ground)?;
+        stats.ground_z = ground_level.unwrap_or(lowest_z);
 
         // Step 2: Calculate target height for the LoD1.2 model
-        let target_height = self.calculate_lod1_2_height();
+        let target_height = match &options.height {
+            HeightStrategy::RoofPercentile => self.calculate_lod1_2_height(),
+            HeightStrategy::PointCloud { cloud, percentile } => cloud
+                .percentile_z(&self.world_footprint(), *percentile)
+                .ok_or_else(|| {
+                    Error::Io(std::io::Error::new(
+                        std::io::ErrorKind::InvalidData,
+                        "No point cloud points inside the footprint",
+                    ))
+                })?,
+        };
         if target_height <= 0.0 {
             return Err(Error::Io(std::io::Error::new(
                 std::io::ErrorKind::InvalidData,
                 "Failed to calculate target height",
             )));
         }
+        // The walls rise from every ground vertex, so the roof must be above the highest one
+        let ground_top = ground_level.unwrap_or(highest_z);
+        if target_height <= ground_top {
+            return Err(Error::Io(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!(
+                    "Roof height {} is not above the ground level {}",
+                    target_height, ground_top
+                ),
+            )));
+        }
+        stats.roof_z = target_height;
+        stats.height = target_height - stats.ground_z;
+        if let Some((recording, building)) = &recording {
+            record_plane(recording, building, self, "height_plane", target_height)?;
+        }
+        self.attributes
+            .insert("height_strategy".to_string(), options.height.name().into());
+        self.attributes
+            .insert("ground_strategy".to_string(), options.ground.name().into());
 
-        // Step 3: Remove all non-ground surfaces
+        // Step 3: Remove all non-ground surfaces, move them to the ground level and simplify them
         self.remove_non_ground_surfaces();
+        if let Some(ground_level) = ground_level {
+            for vertex in &mut self.vertices {
+                vertex.point.z = ground_level;
+            }
+        }
 
-        // Debug: visualize the model with only ground surfaces
-        // =====================================
-        // let mut rec = rerun::RecordingStreamBuilder::new("lodconv.rrd").spawn()?;
-        // self.visualize(&mut rec, "only ground")?;
-        // =====================================
-
-        // Create a path under the current working directory
-        let current_dir = std::env::current_dir()?;
-        let output_path = current_dir.join("onlyground_od1_2.obj");
-        self.write_obj(&output_path)?;
-
-        // Step 4: Extrude the ground surface to the target height
-        self.extrude_to_lod1(target_height);
-
-        // Debug: print the number of faces and vertices
-        // =====================================
-        println!("Number of faces: {}", self.faces.len());
-        println!("Number of vertices: {}", self.vertices.len());
-        // =====================================
-        Ok(())
+        if let Some(simplification) = &options.simplify {
+            self.simplify_ground(simplification);
+            self.attributes.insert(
+                "footprint_simplification".to_string(),
+                simplification.name().into(),
+            );
+        }
+
+        if let Some((recording, building)) = &recording {
+            record_surfaces(recording, building, self, "ground", false)?;
+            record_boundary(recording, building, self, "boundary")?;
+        }
+
+        // Step 4: Extrude the ground surface to the target height, split into storeys
+        let floor_levels = match &options.storeys {
+            Some(storeys) => {
+                let (storey_height, levels) =
+                    self.storey_floor_levels(storeys, stats.ground_z, target_height);
+                let storeys = levels.len() + 1;
+                self.attributes
+                    .insert("storeys".to_string(), storeys.into());
+                self.attributes
+                    .insert("storey_height".to_string(), storey_height.into());
+                self.attributes.insert(
+                    "gross_floor_area".to_string(),
+                    (self.footprint().area() * storeys as f64).into(),
+                );
+                levels
+            }
+            None => Vec::new(),
+        };
+        self.extrude_to_lod1(target_height, &floor_levels);
+
+        // Step 5: Merge coplanar faces, such as the triangles of the base
+        if options.merge_coplanar {
+            self.merge_coplanar_faces();
+        }
+
+        // Step 6: Record the size and shape of the LoD2.2 input and the LoD1.2 output
+        if let Some(input_metrics) = input_metrics {
+            self.attributes.extend(input_metrics.attributes("lod22_"));
+            let output_metrics = ShapeMetrics::from_model(self);
+            self.attributes.extend(output_metrics.attributes("lod12_"));
+        }
+
+        if let Some((recording, building)) = &recording {
+            record_surfaces(recording, building, self, "lod12", true)?;
+        }
+
+        stats.output_vertices = self.vertices.len();
+        stats.output_faces = self.faces.len();
+
+        Ok(stats)
     }
 
+    /// Log the model to a Rerun recording as `mesh_<name>`, coloured by
+    /// surface type, and its vertices as `vertices_<name>`
     pub fn visualize(&self, recording: &mut rerun::RecordingStream, name: &str) -> Result<()> {
-        // Convert vertices to rerun format
+        recording.log(format!("mesh_{}", name), &surface_mesh(self))?;
+
+        // Log points separately
         let vertex_positions: Vec<[f32; 3]> = self
             .vertices
             .iter()
             .map(|v| [v.point.x as f32, v.point.y as f32, v.point.z as f32])
             .collect();
-
-        // Process each face into triangles (simple triangulation)
-        let mut triangles = Vec::new();
-        let mut triangle_colors = Vec::new();
-
-        for face in &self.faces {
-            // For faces with more than 3 vertices, triangulate as a fan
-            if face.vertex_ids.len() >= 3 {
-                for i in 1..(face.vertex_ids.len() - 1) {
-                    // Create triangles from the first vertex and subsequent pairs
-                    triangles.push([
-                        face.vertex_ids[0] as u32,
-                        face.vertex_ids[i] as u32,
-                        face.vertex_ids[i + 1] as u32,
-                    ]);
-
-                    // Add color based on surface type
-                    let color = match face.surface_type {
-                        SurfaceType::Ground => [150, 75, 0, 255],     // Brown
-                        SurfaceType::Wall => [200, 200, 200, 255],    // Light gray
-                        SurfaceType::Roof => [220, 20, 20, 255],      // Red
-                        SurfaceType::Unknown => [100, 100, 100, 255], // Dark gray
-                    };
-                    triangle_colors.push(color);
-                }
-            }
-        }
-
-        // Log the mesh with colors
-        recording.log(
-            format!("mesh_{}", name),
-            &rerun::Mesh3D::new(vertex_positions.clone())
-                .with_triangle_indices(triangles)
-                .with_albedo_factor(rerun::Rgba32::from_rgb(128, 128, 128)),
-        )?;
-
-        // Create a vector of radius values for each point
         let point_radii = vec![0.1f32; vertex_positions.len()];
-
-        // Log points separately
         recording.log(
             format!("vertices_{}", name),
             &rerun::Points3D::new(vertex_positions).with_radii(point_radii),
@@ -712,3 +951,132 @@ impl Model {
         Ok(())
     }
 }
+
+/// Edges not shared by exactly two of `faces`, sorted
+fn boundary_edges<'a>(faces: impl Iterator<Item = &'a Face>) -> Vec<(usize, usize)> {
+    // Count how many times each edge appears
+    let mut edge_count: HashMap<(usize, usize), usize> = HashMap::new();
+    for face in faces {
+        let vertex_count = face.vertex_ids.len();
+        for i in 0..vertex_count {
+            let v1 = face.vertex_ids[i];
+            let v2 = face.vertex_ids[(i + 1) % vertex_count];
+
+            // Sort the vertices to ensure the same edge is counted correctly regardless of direction
+            let edge = if v1 < v2 { (v1, v2) } else { (v2, v1) };
+
+            *edge_count.entry(edge).or_insert(0) += 1;
+        }
+    }
+
+    // if edge count is not 2, it is a boundary edge
+    let mut boundaries = edge_count
+        .iter()
+        .filter_map(|(&edge, &count)| if count != 2 { Some(edge) } else { None })
+        .collect::<Vec<_>>();
+    // Sort so the traversal order doesn't depend on hash order
+    boundaries.sort_unstable();
+
+    boundaries
+}
+
+/// Split edges into closed loops of vertex IDs, dropping open chains
+fn edge_loops(edges: &[(usize, usize)]) -> Vec<Vec<usize>> {
+    let mut neighbours: HashMap<usize, Vec<usize>> = HashMap::new();
+    for &(v1, v2) in edges {
+        neighbours.entry(v1).or_default().push(v2);
+        neighbours.entry(v2).or_default().push(v1);
+    }
+
+    let key = |v1: usize, v2: usize| if v1 < v2 { (v1, v2) } else { (v2, v1) };
+    let mut used = HashSet::new();
+    let mut loops = Vec::new();
+
+    for &(start, next) in edges {
+        if !used.insert((start, next)) {
+            continue;
+        }
+
+        // Walk along unused edges until we are back at the start
+        let mut ring = vec![start];
+        let mut current = next;
+        while current != start {
+            ring.push(current);
+            let following = neighbours[&current]
+                .iter()
+                .copied()
+                .find(|&neighbour| !used.contains(&key(current, neighbour)));
+            let Some(following) = following else {
+                break;
+            };
+            used.insert(key(current, following));
+            current = following;
+        }
+
+        // Open chains come from non-manifold surfaces and don't bound anything
+        if current == start && ring.len() >= 3 {
+            loops.push(ring);
+        }
+    }
+
+    loops
+}
+
+/// Area of a triangle projected on the XY plane
+fn projected_area(triangle: &[Point3<f64>; 3]) -> f64 {
+    let [a, b, c] = triangle;
+    ((b.x - a.x) * (c.y - a.y) - (c.x - a.x) * (b.y - a.y)).abs() / 2.0
+}
+
+/// z of a triangle at (x, y), or `None` if (x, y) lies outside its projection on the XY plane
+fn z_at(triangle: &[Point3<f64>; 3], x: f64, y: f64) -> Option<f64> {
+    let [a, b, c] = triangle;
+    let det = (b.y - c.y) * (a.x - c.x) + (c.x - b.x) * (a.y - c.y);
+    if det.abs() < EPSILON {
+        return None;
+    }
+    let u = ((b.y - c.y) * (x - c.x) + (c.x - b.x) * (y - c.y)) / det;
+    let v = ((c.y - a.y) * (x - c.x) + (a.x - c.x) * (y - c.y)) / det;
+    let w = 1.0 - u - v;
+    if u < -EPSILON || v < -EPSILON || w < -EPSILON {
+        return None;
+    }
+    Some(u * a.z + v * b.z + w * c.z)
+}
+
+/// Merge models into one, offsetting vertex IDs. The ID is kept only for a single model.
+pub(crate) fn merge_models(models: Vec<Model>) -> Model {
+    let id = if models.len() == 1 {
+        models[0].id.clone()
+    } else {
+        None
+    };
+
+    // Vertices move to the local origin and CRS of the first model
+    let (origin, crs) = models
+        .first()
+        .map_or((Vector3::new(0.0, 0.0, 0.0), None), |model| {
+            (model.origin, model.crs)
+        });
+    let mut vertices = Vec::new();
+    let mut faces = Vec::new();
+    for model in models {
+        let offset = vertices.len();
+        let shift = model.origin - origin;
+        vertices.extend(model.vertices.into_iter().map(|vertex| Vertex {
+            point: vertex.point + shift,
+            id: vertex.id + offset,
+        }));
+        faces.extend(model.faces.into_iter().map(|face| {
+            let mut merged = Face::new(face.vertex_ids.iter().map(|id| id + offset).collect());
+            merged.surface_type = face.surface_type;
+            merged
+        }));
+    }
+
+    let mut model = Model::new(vertices, faces);
+    model.id = id;
+    model.origin = origin;
+    model.crs = crs;
+    model
+}
@@ -0,0 +1,286 @@
+use crate::error::{Error, Result};
+use crate::model::Model;
+use crate::primitives::{Face, Vertex};
+use crate::weld::weld_model;
+use cgmath::Point3;
+use std::collections::BTreeMap;
+use std::io::{BufRead, Lines, Write};
+use std::str::FromStr;
+
+/// Streaming reader for OBJ files holding one or more objects (`o` statements).
+///
+/// Iterating yields one `Model` per object, in file order, with the object name
+/// as the model ID. OBJ vertex indices are global to the file, so every vertex
+/// position read so far is kept, but faces are only held for the current object.
+pub struct ObjReader<R: BufRead> {
+    lines: Lines<R>,
+    line_number: usize,
+    points: Vec<Point3<f64>>,
+    current_id: Option<String>,
+    current_faces: Vec<Vec<usize>>,
+    finished: bool,
+}
+
+/// A parsed OBJ statement relevant to the geometry
+enum Statement {
+    Vertex(Point3<f64>),
+    Face(Vec<usize>),
+    Object(String),
+}
+
+impl<R: BufRead> ObjReader<R> {
+    /// Create a reader over buffered OBJ content
+    pub fn new(reader: R) -> Self {
+        ObjReader {
+            lines: reader.lines(),
+            line_number: 0,
+            points: Vec::new(),
+            current_id: None,
+            current_faces: Vec::new(),
+            finished: false,
+        }
+    }
+
+    /// Read the whole content as a single model, ignoring object boundaries.
+    ///
+    /// All vertices are kept in file order, including unused ones. The model ID is
+    /// set only if the file names exactly one object.
+    pub fn read_merged(mut self) -> Result<Model> {
+        let mut faces = Vec::new();
+        let mut names = Vec::new();
+
+        while let Some(statement) = self.next_statement()? {
+            match statement {
+                Statement::Vertex(point) => self.points.push(point),
+                Statement::Face(vertex_ids) => faces.push(Face::new(vertex_ids)),
+                Statement::Object(name) => names.push(name),
+            }
+        }
+
+        let vertices = self
+            .points
+            .iter()
+            .enumerate()
+            .map(|(id, &point)| Vertex { point, id })
+            .collect();
+
+        let mut model = Model::new(vertices, faces);
+        if names.len() == 1 {
+            model.id = names.pop();
+        }
+        Ok(model)
+    }
+
+    /// Read the next statement, skipping blank lines, comments and unsupported ones
+    fn next_statement(&mut self) -> Result<Option<Statement>> {
+        for line_result in self.lines.by_ref() {
+            self.line_number += 1;
+            let line = line_result.map_err(Error::Io)?;
+            let parts: Vec<&str> = line.split_whitespace().collect();
+
+            if parts.is_empty() || parts[0].starts_with('#') {
+                continue;
+            }
+
+            match parts[0] {
+                "v" => {
+                    return parse_vertex(&parts, self.line_number)
+                        .map(|p| Some(Statement::Vertex(p)))
+                }
+                "f" => {
+                    return parse_face(&parts, self.line_number, self.points.len())
+                        .map(|ids| Some(Statement::Face(ids)))
+                }
+                "o" => return Ok(Some(Statement::Object(parts[1..].join(" ")))),
+                _ => {
+                    // Ignore other types like normals, textures, etc.
+                    continue;
+                }
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Build a model from the faces of the current object, with compact local vertex IDs
+    fn take_current(&mut self) -> Option<Model> {
+        let faces = std::mem::take(&mut self.current_faces);
+        let id = self.current_id.take();
+        if faces.is_empty() {
+            return None;
+        }
+
+        // Keep the file order of the vertices used by this object
+        let mut local_ids: BTreeMap<usize, usize> = BTreeMap::new();
+        for &global_id in faces.iter().flatten() {
+            local_ids.insert(global_id, 0);
+        }
+        let mut vertices = Vec::with_capacity(local_ids.len());
+        for (&global_id, local_id) in local_ids.iter_mut() {
+            *local_id = vertices.len();
+            vertices.push(Vertex {
+                point: self.points[global_id],
+                id: *local_id,
+            });
+        }
+
+        let faces = faces
+            .into_iter()
+            .map(|ids| Face::new(ids.iter().map(|id| local_ids[id]).collect()))
+            .collect();
+
+        let mut model = Model::new(vertices, faces);
+        model.id = id;
+        Some(model)
+    }
+}
+
+impl<R: BufRead> Iterator for ObjReader<R> {
+    type Item = Result<Model>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.finished {
+            match self.next_statement() {
+                Ok(Some(Statement::Vertex(point))) => self.points.push(point),
+                Ok(Some(Statement::Face(vertex_ids))) => self.current_faces.push(vertex_ids),
+                Ok(Some(Statement::Object(name))) => {
+                    let model = self.take_current();
+                    self.current_id = Some(name);
+                    if model.is_some() {
+                        return model.map(Ok);
+                    }
+                }
+                Ok(None) => {
+                    self.finished = true;
+                    return self.take_current().map(Ok);
+                }
+                Err(e) => {
+                    self.finished = true;
+                    return Some(Err(e));
+                }
+            }
+        }
+
+        None
+    }
+}
+
+/// Incremental OBJ writer that appends models as separate objects.
+///
+/// Vertices are welded per model and indices are offset by the number of
+/// vertices already written, so models can be written one at a time.
+pub struct ObjWriter<W: Write> {
+    writer: W,
+    tolerance: f64,
+    vertex_offset: usize,
+}
+
+impl<W: Write> ObjWriter<W> {
+    /// Create a writer and emit the file header
+    pub fn new(mut writer: W, tolerance: f64) -> Result<Self> {
+        writeln!(writer, "# Converted LoD1.2 model").map_err(Error::Io)?;
+        Ok(ObjWriter {
+            writer,
+            tolerance,
+            vertex_offset: 0,
+        })
+    }
+
+    /// Append a model, as a named object if it has an ID
+    pub fn write_model(&mut self, model: &Model) -> Result<()> {
+        // Weld vertices through a hash grid; output order follows first use by faces
+        let (obj_vertices, obj_faces) = weld_model(model, self.tolerance);
+
+        if let Some(id) = &model.id {
+            writeln!(self.writer, "o {}", id).map_err(Error::Io)?;
+        }
+
+        // Write vertices
+        for vertex in &obj_vertices {
+            writeln!(self.writer, "v {} {} {}", vertex.x, vertex.y, vertex.z).map_err(Error::Io)?;
+        }
+
+        // Write faces
+        for face in &obj_faces {
+            write!(self.writer, "f").map_err(Error::Io)?;
+            for &index in face {
+                // OBJ indices are 1-based
+                write!(self.writer, " {}", self.vertex_offset + index + 1).map_err(Error::Io)?;
+            }
+            writeln!(self.writer).map_err(Error::Io)?;
+        }
+
+        self.vertex_offset += obj_vertices.len();
+        Ok(())
+    }
+
+    /// Flush and return the underlying writer
+    pub fn finish(mut self) -> Result<W> {
+        self.writer.flush().map_err(Error::Io)?;
+        Ok(self.writer)
+    }
+}
+
+/// Parse a `v x y z` statement
+fn parse_vertex(parts: &[&str], line_number: usize) -> Result<Point3<f64>> {
+    if parts.len() < 4 {
+        return Err(Error::Io(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("Not enough components for vertex at line {}", line_number),
+        )));
+    }
+
+    let mut coordinates = [0.0; 3];
+    for (i, axis) in ["x", "y", "z"].iter().enumerate() {
+        coordinates[i] = f64::from_str(parts[i + 1]).map_err(|_| {
+            Error::Io(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!(
+                    "Invalid {} coordinate: {} at line {}",
+                    axis,
+                    parts[i + 1],
+                    line_number
+                ),
+            ))
+        })?;
+    }
+
+    Ok(Point3::new(coordinates[0], coordinates[1], coordinates[2]))
+}
+
+/// Parse an `f v1 v2 v3 ...` statement into 0-based vertex indices
+fn parse_face(parts: &[&str], line_number: usize, vertex_count: usize) -> Result<Vec<usize>> {
+    if parts.len() < 4 {
+        return Err(Error::Io(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("Face must have at least 3 vertices at line {}", line_number),
+        )));
+    }
+
+    let mut vertex_ids = Vec::with_capacity(parts.len() - 1);
+    for part in &parts[1..] {
+        // Extract just the vertex index (ignore texture/normal indices)
+        let vertex_str = part.split('/').next().unwrap_or("");
+        let index = usize::from_str(vertex_str).map_err(|_| {
+            Error::Io(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!(
+                    "Invalid vertex index: {} at line {}",
+                    vertex_str, line_number
+                ),
+            ))
+        })?;
+
+        // OBJ indices are 1-based, convert to 0-based
+        if index == 0 || index > vertex_count {
+            return Err(Error::Io(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("Invalid vertex index at line {}", line_number),
+            )));
+        }
+
+        vertex_ids.push(index - 1);
+    }
+
+    Ok(vertex_ids)
+}
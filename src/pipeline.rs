@@ -0,0 +1,80 @@
+use crate::error::Result;
+use crate::model::{ConversionStats, Lod1Options, Model};
+use rayon::prelude::*;
+use serde::Serialize;
+
+/// Outcome of converting a single building
+#[derive(Debug, Clone, Serialize)]
+pub struct BuildingReport {
+    pub id: Option<String>,
+    pub success: bool,
+    pub error_code: Option<&'static str>,
+    pub error: Option<String>,
+    pub stats: Option<ConversionStats>,
+}
+
+/// Convert buildings to LoD1.2 in parallel and hand them to `sink` in input order.
+///
+/// Buildings are pulled from `models` in chunks of `chunk_size`; each chunk is
+/// converted across cores and written before the next one is read, so memory
+/// stays bounded by the chunk size rather than the size of the input.
+/// Buildings that fail to convert are reported and left out of the output,
+/// while errors from reading the input or from `sink` abort the conversion.
+pub fn convert_models<I, F>(
+    models: I,
+    chunk_size: usize,
+    options: &Lod1Options,
+    mut sink: F,
+) -> Result<Vec<BuildingReport>>
+where
+    I: Iterator<Item = Result<Model>>,
+    F: FnMut(Model) -> Result<()>,
+{
+    let mut models = models.peekable();
+    let mut reports = Vec::new();
+    let mut chunk = Vec::with_capacity(chunk_size.max(1));
+
+    while models.peek().is_some() {
+        chunk.clear();
+        while chunk.len() < chunk_size.max(1) {
+            match models.next() {
+                Some(model) => chunk.push(model?),
+                None => break,
+            }
+        }
+
+        // par_iter_mut keeps the results in the order of the chunk
+        let start = reports.len();
+        let results: Vec<Result<ConversionStats>> = chunk
+            .par_iter_mut()
+            .enumerate()
+            .map(|(i, model)| model.to_lod1_2_indexed(options, start + i))
+            .collect();
+
+        for (model, result) in chunk.drain(..).zip(results) {
+            let report = match result {
+                Ok(stats) => {
+                    let id = model.id.clone();
+                    sink(model)?;
+                    BuildingReport {
+                        id,
+                        success: true,
+                        error_code: None,
+                        error: None,
+                        stats: Some(stats),
+                    }
+                }
+                Err(e) => BuildingReport {
+                    id: model.id.clone(),
+                    success: false,
+                    error_code: Some(e.code()),
+                    error: Some(e.to_string()),
+                    stats: None,
+                },
+            };
+            reports.push(report);
+        }
+    }
+
+    Ok(reports)
+}
@@ -0,0 +1,448 @@
+use crate::error::{Error, Result};
+use crate::format::Encoding;
+use crate::model::Model;
+use crate::primitives::{Face, SurfaceType, Vertex};
+use crate::weld::weld_model;
+use cgmath::Point3;
+use std::io::{BufRead, Write};
+use std::str::FromStr;
+
+/// Surface types by the value of the `surface_type` face property
+const SURFACE_TYPE_CODES: [SurfaceType; 5] = [
+    SurfaceType::Ground,
+    SurfaceType::Wall,
+    SurfaceType::Roof,
+    SurfaceType::Unknown,
+    SurfaceType::Floor,
+];
+
+/// Scalar types of PLY properties
+#[derive(Debug, Clone, Copy)]
+enum ScalarType {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl ScalarType {
+    fn parse(name: &str) -> Option<Self> {
+        Some(match name {
+            "char" | "int8" => ScalarType::I8,
+            "uchar" | "uint8" => ScalarType::U8,
+            "short" | "int16" => ScalarType::I16,
+            "ushort" | "uint16" => ScalarType::U16,
+            "int" | "int32" => ScalarType::I32,
+            "uint" | "uint32" => ScalarType::U32,
+            "float" | "float32" => ScalarType::F32,
+            "double" | "float64" => ScalarType::F64,
+            _ => return None,
+        })
+    }
+
+    fn size(&self) -> usize {
+        match self {
+            ScalarType::I8 | ScalarType::U8 => 1,
+            ScalarType::I16 | ScalarType::U16 => 2,
+            ScalarType::I32 | ScalarType::U32 | ScalarType::F32 => 4,
+            ScalarType::F64 => 8,
+        }
+    }
+}
+
+/// A property of a PLY element
+#[derive(Debug, Clone)]
+enum Property {
+    Scalar(String, ScalarType),
+    List(String, ScalarType, ScalarType), // count type, item type
+}
+
+/// An element declared in the PLY header
+#[derive(Debug, Clone)]
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+/// Format of the PLY body
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BodyFormat {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+/// Reader for ASCII and binary PLY meshes.
+///
+/// The `vertex` element must have `x`, `y` and `z` properties and the `face`
+/// element a `vertex_indices` (or `vertex_index`) list. A `surface_type` face
+/// property is read back into `Face::surface_type`; other properties and
+/// elements are skipped.
+pub struct PlyReader<R: BufRead> {
+    reader: R,
+}
+
+impl<R: BufRead> PlyReader<R> {
+    /// Create a reader over buffered PLY content
+    pub fn new(reader: R) -> Self {
+        PlyReader { reader }
+    }
+
+    /// Read the mesh as a single model
+    pub fn read(mut self) -> Result<Model> {
+        let (format, elements) = self.read_header()?;
+        let mut body = match format {
+            BodyFormat::Ascii => {
+                let mut text = String::new();
+                self.reader.read_to_string(&mut text).map_err(Error::Io)?;
+                Body::Ascii(text.split_whitespace().map(str::to_string).collect(), 0)
+            }
+            _ => {
+                let mut bytes = Vec::new();
+                self.reader.read_to_end(&mut bytes).map_err(Error::Io)?;
+                Body::Binary(bytes, 0, format == BodyFormat::BinaryBigEndian)
+            }
+        };
+
+        let mut points: Vec<Point3<f64>> = Vec::new();
+        let mut faces: Vec<Face> = Vec::new();
+
+        for element in &elements {
+            for _ in 0..element.count {
+                let mut coordinates = [0.0; 3];
+                let mut vertex_ids = Vec::new();
+                let mut surface_type = SurfaceType::Unknown;
+
+                for property in &element.properties {
+                    match property {
+                        Property::Scalar(name, scalar) => {
+                            let value = body.read(*scalar)?;
+                            match (element.name.as_str(), name.as_str()) {
+                                ("vertex", "x") => coordinates[0] = value,
+                                ("vertex", "y") => coordinates[1] = value,
+                                ("vertex", "z") => coordinates[2] = value,
+                                ("face", "surface_type") => {
+                                    surface_type = SURFACE_TYPE_CODES
+                                        .get(value as usize)
+                                        .copied()
+                                        .unwrap_or(SurfaceType::Unknown)
+                                }
+                                _ => {}
+                            }
+                        }
+                        Property::List(name, count_type, item_type) => {
+                            let count = non_negative(body.read(*count_type)?, "list count")?;
+                            let is_indices = element.name == "face"
+                                && (name == "vertex_indices" || name == "vertex_index");
+                            for _ in 0..count {
+                                let value = body.read(*item_type)?;
+                                if is_indices {
+                                    vertex_ids.push(non_negative(value, "vertex index")?);
+                                }
+                            }
+                        }
+                    }
+                }
+
+                match element.name.as_str() {
+                    "vertex" => {
+                        points.push(Point3::new(coordinates[0], coordinates[1], coordinates[2]))
+                    }
+                    "face" => {
+                        if vertex_ids.len() < 3 {
+                            return Err(invalid_data("Face must have at least 3 vertices"));
+                        }
+                        let mut face = Face::new(vertex_ids);
+                        face.surface_type = surface_type;
+                        faces.push(face);
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        if faces
+            .iter()
+            .flat_map(|face| &face.vertex_ids)
+            .any(|&id| id >= points.len())
+        {
+            return Err(invalid_data("Face references a missing vertex"));
+        }
+
+        let vertices = points
+            .into_iter()
+            .enumerate()
+            .map(|(id, point)| Vertex { point, id })
+            .collect();
+        Ok(Model::new(vertices, faces))
+    }
+
+    /// Parse the header up to `end_header`
+    fn read_header(&mut self) -> Result<(BodyFormat, Vec<Element>)> {
+        let mut format = None;
+        let mut elements: Vec<Element> = Vec::new();
+        let mut line_number = 0;
+
+        loop {
+            let mut line = String::new();
+            if self.reader.read_line(&mut line).map_err(Error::Io)? == 0 {
+                return Err(invalid_data("PLY header has no end_header"));
+            }
+            line_number += 1;
+            let parts: Vec<&str> = line.split_whitespace().collect();
+
+            if line_number == 1 {
+                if parts != ["ply"] {
+                    return Err(invalid_data("Not a PLY file: missing 'ply' magic"));
+                }
+                continue;
+            }
+
+            match parts.as_slice() {
+                ["format", name, _version] => {
+                    format = Some(match *name {
+                        "ascii" => BodyFormat::Ascii,
+                        "binary_little_endian" => BodyFormat::BinaryLittleEndian,
+                        "binary_big_endian" => BodyFormat::BinaryBigEndian,
+                        _ => return Err(invalid_data(&format!("Unknown PLY format: {}", name))),
+                    })
+                }
+                ["element", name, count] => elements.push(Element {
+                    name: name.to_string(),
+                    count: usize::from_str(count).map_err(|_| {
+                        invalid_data(&format!("Invalid element count at line {}", line_number))
+                    })?,
+                    properties: Vec::new(),
+                }),
+                ["property", "list", count_type, item_type, name] => {
+                    let property = Property::List(
+                        name.to_string(),
+                        scalar_type(count_type, line_number)?,
+                        scalar_type(item_type, line_number)?,
+                    );
+                    push_property(&mut elements, property, line_number)?;
+                }
+                ["property", scalar, name] => {
+                    let property =
+                        Property::Scalar(name.to_string(), scalar_type(scalar, line_number)?);
+                    push_property(&mut elements, property, line_number)?;
+                }
+                ["end_header"] => break,
+                _ => {
+                    // comment, obj_info and blank lines
+                    continue;
+                }
+            }
+        }
+
+        let format = format.ok_or_else(|| invalid_data("PLY header has no format"))?;
+        Ok((format, elements))
+    }
+}
+
+fn scalar_type(name: &str, line_number: usize) -> Result<ScalarType> {
+    ScalarType::parse(name).ok_or_else(|| {
+        invalid_data(&format!(
+            "Unknown property type {} at line {}",
+            name, line_number
+        ))
+    })
+}
+
+fn push_property(elements: &mut [Element], property: Property, line_number: usize) -> Result<()> {
+    let element = elements.last_mut().ok_or_else(|| {
+        invalid_data(&format!(
+            "Property before any element at line {}",
+            line_number
+        ))
+    })?;
+    element.properties.push(property);
+    Ok(())
+}
+
+/// A count or index read as `value`, or an error if it is negative or not a whole number
+fn non_negative(value: f64, what: &str) -> Result<usize> {
+    if value < 0.0 || value.fract() != 0.0 || !value.is_finite() {
+        return Err(invalid_data(&format!("Invalid PLY {}: {}", what, value)));
+    }
+    Ok(value as usize)
+}
+
+fn invalid_data(message: &str) -> Error {
+    Error::Io(std::io::Error::new(
+        std::io::ErrorKind::InvalidData,
+        message.to_string(),
+    ))
+}
+
+/// PLY body with the position of the next value
+enum Body {
+    Ascii(Vec<String>, usize),
+    Binary(Vec<u8>, usize, bool), // big endian
+}
+
+impl Body {
+    /// Read the next value as f64, which holds every PLY scalar type exactly
+    fn read(&mut self, scalar: ScalarType) -> Result<f64> {
+        match self {
+            Body::Ascii(tokens, position) => {
+                let token = tokens
+                    .get(*position)
+                    .ok_or_else(|| invalid_data("Unexpected end of PLY data"))?;
+                *position += 1;
+                f64::from_str(token)
+                    .map_err(|_| invalid_data(&format!("Invalid PLY value: {}", token)))
+            }
+            Body::Binary(bytes, position, big_endian) => {
+                let size = scalar.size();
+                let slice = bytes
+                    .get(*position..*position + size)
+                    .ok_or_else(|| invalid_data("Unexpected end of PLY data"))?;
+                *position += size;
+
+                let mut buffer = [0u8; 8];
+                buffer[..size].copy_from_slice(slice);
+                if *big_endian {
+                    buffer[..size].reverse();
+                }
+                Ok(match scalar {
+                    ScalarType::I8 => buffer[0] as i8 as f64,
+                    ScalarType::U8 => buffer[0] as f64,
+                    ScalarType::I16 => i16::from_le_bytes([buffer[0], buffer[1]]) as f64,
+                    ScalarType::U16 => u16::from_le_bytes([buffer[0], buffer[1]]) as f64,
+                    ScalarType::I32 => {
+                        i32::from_le_bytes(buffer[..4].try_into().expect("4 bytes")) as f64
+                    }
+                    ScalarType::U32 => {
+                        u32::from_le_bytes(buffer[..4].try_into().expect("4 bytes")) as f64
+                    }
+                    ScalarType::F32 => {
+                        f32::from_le_bytes(buffer[..4].try_into().expect("4 bytes")) as f64
+                    }
+                    ScalarType::F64 => f64::from_le_bytes(buffer),
+                })
+            }
+        }
+    }
+}
+
+/// Writer for ASCII and binary (little endian) PLY meshes.
+///
+/// PLY declares element counts in its header, so models are buffered until
+/// `finish` and written as a single mesh. Vertices are welded per model and
+/// stored as doubles to keep georeferenced coordinates exact. Every face has a
+/// `surface_type` property: 0 ground, 1 wall, 2 roof, 3 unknown, 4 floor.
+pub struct PlyWriter<W: Write> {
+    writer: W,
+    encoding: Encoding,
+    tolerance: f64,
+    points: Vec<Point3<f64>>,
+    faces: Vec<(Vec<usize>, SurfaceType)>,
+}
+
+impl<W: Write> PlyWriter<W> {
+    /// Create a writer welding vertices closer than `tolerance`
+    pub fn new(writer: W, encoding: Encoding, tolerance: f64) -> Self {
+        PlyWriter {
+            writer,
+            encoding,
+            tolerance,
+            points: Vec::new(),
+            faces: Vec::new(),
+        }
+    }
+
+    /// Append a model to the mesh
+    pub fn write_model(&mut self, model: &Model) -> Result<()> {
+        let (points, faces) = weld_model(model, self.tolerance);
+        let offset = self.points.len();
+        self.points.extend(points);
+        for (ids, face) in faces.into_iter().zip(&model.faces) {
+            let ids = ids.into_iter().map(|id| id + offset).collect();
+            self.faces.push((ids, face.surface_type));
+        }
+        Ok(())
+    }
+
+    /// Write the mesh, flush and return the underlying writer
+    pub fn finish(mut self) -> Result<W> {
+        // Most faces fit a one-byte vertex count, which all PLY readers support
+        let max_face_len = self.faces.iter().map(|(ids, _)| ids.len()).max();
+        let count_type = if max_face_len.unwrap_or(0) <= u8::MAX as usize {
+            "uchar"
+        } else {
+            "uint"
+        };
+        let format = match self.encoding {
+            Encoding::Ascii => "ascii",
+            Encoding::Binary => "binary_little_endian",
+        };
+
+        let w = &mut self.writer;
+        writeln!(w, "ply").map_err(Error::Io)?;
+        writeln!(w, "format {} 1.0", format).map_err(Error::Io)?;
+        writeln!(w, "comment Converted LoD1.2 model").map_err(Error::Io)?;
+        writeln!(
+            w,
+            "comment surface_type: 0 ground, 1 wall, 2 roof, 3 unknown, 4 floor"
+        )
+        .map_err(Error::Io)?;
+        writeln!(w, "element vertex {}", self.points.len()).map_err(Error::Io)?;
+        for axis in ["x", "y", "z"] {
+            writeln!(w, "property double {}", axis).map_err(Error::Io)?;
+        }
+        writeln!(w, "element face {}", self.faces.len()).map_err(Error::Io)?;
+        writeln!(w, "property list {} int vertex_indices", count_type).map_err(Error::Io)?;
+        writeln!(w, "property uchar surface_type").map_err(Error::Io)?;
+        writeln!(w, "end_header").map_err(Error::Io)?;
+
+        for point in &self.points {
+            match self.encoding {
+                Encoding::Ascii => {
+                    writeln!(w, "{} {} {}", point.x, point.y, point.z).map_err(Error::Io)?
+                }
+                Encoding::Binary => {
+                    for value in [point.x, point.y, point.z] {
+                        w.write_all(&value.to_le_bytes()).map_err(Error::Io)?;
+                    }
+                }
+            }
+        }
+
+        for (ids, surface_type) in &self.faces {
+            let code = SURFACE_TYPE_CODES
+                .iter()
+                .position(|t| t == surface_type)
+                .expect("every surface type has a code") as u8;
+            match self.encoding {
+                Encoding::Ascii => {
+                    write!(w, "{}", ids.len()).map_err(Error::Io)?;
+                    for id in ids {
+                        write!(w, " {}", id).map_err(Error::Io)?;
+                    }
+                    writeln!(w, " {}", code).map_err(Error::Io)?;
+                }
+                Encoding::Binary => {
+                    if count_type == "uchar" {
+                        w.write_all(&[ids.len() as u8]).map_err(Error::Io)?;
+                    } else {
+                        w.write_all(&(ids.len() as u32).to_le_bytes())
+                            .map_err(Error::Io)?;
+                    }
+                    for &id in ids {
+                        w.write_all(&(id as i32).to_le_bytes()).map_err(Error::Io)?;
+                    }
+                    w.write_all(&[code]).map_err(Error::Io)?;
+                }
+            }
+        }
+
+        self.writer.flush().map_err(Error::Io)?;
+        Ok(self.writer)
+    }
+}
@@ -0,0 +1,157 @@
+use crate::error::Result;
+use crate::footprint::Footprint;
+use cgmath::Point3;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Size in metres of the grid cells indexing the points
+const CELL_SIZE: f64 = 10.0;
+/// Number of points read from a LAS/LAZ file at a time
+const READ_CHUNK_SIZE: u64 = 1 << 20;
+
+/// Points of a LAS/LAZ point cloud, indexed on a 2D grid for footprint queries
+#[derive(Debug, Clone, Default)]
+pub struct PointCloud {
+    cells: HashMap<(i64, i64), Vec<Point3<f64>>>,
+    len: usize,
+}
+
+impl PointCloud {
+    /// Read a LAS or LAZ file, keeping only the points with one of `classes` if
+    /// given, and inside the horizontal `extent` (minimum and maximum x and y)
+    /// if given.
+    ///
+    /// The points are read in chunks straight into the grid, so only the kept
+    /// points are held in memory; pass the extent of the buildings to read a
+    /// large tile. Classification codes are the ASPRS ones, e.g. 2 for ground
+    /// and 6 for buildings.
+    pub fn read(
+        path: &Path,
+        classes: Option<&[u8]>,
+        extent: Option<([f64; 2], [f64; 2])>,
+    ) -> Result<Self> {
+        let mut reader = las::Reader::from_path(path)?;
+        let mut cloud = PointCloud::default();
+
+        // Skip files that don't overlap the extent at all
+        if let Some((min, max)) = extent {
+            let bounds = reader.header().bounds();
+            if bounds.max.x < min[0]
+                || bounds.min.x > max[0]
+                || bounds.max.y < min[1]
+                || bounds.min.y > max[1]
+            {
+                return Ok(cloud);
+            }
+        }
+
+        let mut data = las::PointDataBuilder::new()
+            .for_header(reader.header())
+            .build();
+        while reader.fill_points(READ_CHUNK_SIZE, &mut data)? > 0 {
+            let chunk = data
+                .x()
+                .zip(data.y())
+                .zip(data.z())
+                .zip(data.classification());
+            for (((x, y), z), class) in chunk {
+                let inside = extent.is_none_or(|(min, max)| {
+                    (min[0]..=max[0]).contains(&x) && (min[1]..=max[1]).contains(&y)
+                });
+                if inside && classes.is_none_or(|classes| classes.contains(&class)) {
+                    cloud.insert(Point3::new(x, y, z));
+                }
+            }
+        }
+
+        Ok(cloud)
+    }
+
+    /// Index the given points
+    pub fn from_points(points: Vec<Point3<f64>>) -> Self {
+        let mut cloud = PointCloud::default();
+        for point in points {
+            cloud.insert(point);
+        }
+        cloud
+    }
+
+    /// Add a point to its grid cell
+    fn insert(&mut self, point: Point3<f64>) {
+        self.cells
+            .entry(cell(point.x, point.y))
+            .or_default()
+            .push(point);
+        self.len += 1;
+    }
+
+    /// Number of points
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether the point cloud has no points
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The z of every point lying inside a footprint
+    pub fn z_within(&self, footprint: &Footprint) -> Vec<f64> {
+        if footprint.is_empty() {
+            return Vec::new();
+        }
+        let (mut min, mut max) = ([f64::MAX; 2], [f64::MIN; 2]);
+        for p in footprint
+            .polygons
+            .iter()
+            .flat_map(|polygon| &polygon.exterior)
+        {
+            min = [min[0].min(p.x), min[1].min(p.y)];
+            max = [max[0].max(p.x), max[1].max(p.y)];
+        }
+
+        // Only test the points in the cells overlapping the footprint's bounding box
+        let (min_cell, max_cell) = (cell(min[0], min[1]), cell(max[0], max[1]));
+        let mut heights = Vec::new();
+        for cx in min_cell.0..=max_cell.0 {
+            for cy in min_cell.1..=max_cell.1 {
+                let Some(points) = self.cells.get(&(cx, cy)) else {
+                    continue;
+                };
+                heights.extend(
+                    points
+                        .iter()
+                        .filter(|p| footprint.contains(p.x, p.y))
+                        .map(|p| p.z),
+                );
+            }
+        }
+        heights
+    }
+
+    /// The `percentile` (0 to 1) of the z of the points inside a footprint,
+    /// or `None` if no point lies inside it
+    pub fn percentile_z(&self, footprint: &Footprint, percentile: f64) -> Option<f64> {
+        percentile_of(&mut self.z_within(footprint), percentile)
+    }
+}
+
+/// Grid cell containing (x, y)
+fn cell(x: f64, y: f64) -> (i64, i64) {
+    (
+        (x / CELL_SIZE).floor() as i64,
+        (y / CELL_SIZE).floor() as i64,
+    )
+}
+
+/// The `percentile` (0 to 1) of `values`, interpolating linearly between ranks
+pub fn percentile_of(values: &mut [f64], percentile: f64) -> Option<f64> {
+    if values.is_empty() {
+        return None;
+    }
+    values.sort_by(f64::total_cmp);
+
+    let rank = percentile.clamp(0.0, 1.0) * (values.len() - 1) as f64;
+    let (lower, upper) = (rank.floor() as usize, rank.ceil() as usize);
+    Some(values[lower] + (values[upper] - values[lower]) * (rank - lower as f64))
+}
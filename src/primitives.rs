@@ -1,15 +1,30 @@
 use crate::EPSILON;
 use cgmath::{InnerSpace, Point3, Vector3};
+use serde::Serialize;
 
 /// Surface type classification
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
 pub enum SurfaceType {
     Ground,
     Wall,
     Roof,
+    Floor,   // storey floor or ceiling inside a building
     Unknown, // default value
 }
 
+impl SurfaceType {
+    /// RGBA colour used to display the surface type
+    pub fn color(&self) -> [u8; 4] {
+        match self {
+            SurfaceType::Ground => [150, 75, 0, 255],     // Brown
+            SurfaceType::Wall => [200, 200, 200, 255],    // Light gray
+            SurfaceType::Roof => [220, 20, 20, 255],      // Red
+            SurfaceType::Floor => [230, 190, 60, 255],    // Yellow
+            SurfaceType::Unknown => [100, 100, 100, 255], // Dark gray
+        }
+    }
+}
+
 /// A vertex in the model
 #[derive(Debug, Clone)]
 pub struct Vertex {
@@ -108,23 +123,39 @@ impl Face {
             return v1.cross(v2).magnitude() * 0.5;
         }
 
-        // For polygons with more than 3 vertices, decompose into triangles
-        // using the first vertex as a base
+        // For polygons with more than 3 vertices, use the shoelace formula,
+        // which also works for non-convex polygons such as merged faces
         let p0 = &vertices[self.vertex_ids[0]].point;
-        let mut total_area = 0.0;
+        let mut twice_area = 0.0;
 
         for i in 1..(self.vertex_ids.len() - 1) {
             let p1 = &vertices[self.vertex_ids[i]].point;
             let p2 = &vertices[self.vertex_ids[i + 1]].point;
 
-            // Create vectors in XY plane (z=0) to get projected area
-            let v1 = Vector3::new(p1.x - p0.x, p1.y - p0.y, 0.0);
-            let v2 = Vector3::new(p2.x - p0.x, p2.y - p0.y, 0.0);
+            // Signed areas of the fan triangles cancel out where the polygon is concave
+            twice_area += (p1.x - p0.x) * (p2.y - p0.y) - (p2.x - p0.x) * (p1.y - p0.y);
+        }
+
+        twice_area.abs() * 0.5
+    }
+
+    /// Calculate the true 3D area of the face using Newell's method
+    pub fn area(&self, vertices: &[Vertex]) -> f64 {
+        if self.vertex_ids.len() < 3 {
+            return 0.0;
+        }
 
-            total_area += v1.cross(v2).magnitude() * 0.5;
+        // Newell's method sums the cross products of consecutive vertices,
+        // which also works for non-convex planar polygons
+        let origin = vertices[self.vertex_ids[0]].point;
+        let mut normal = Vector3::new(0.0, 0.0, 0.0);
+        for i in 0..self.vertex_ids.len() {
+            let p1 = vertices[self.vertex_ids[i]].point - origin;
+            let p2 = vertices[self.vertex_ids[(i + 1) % self.vertex_ids.len()]].point - origin;
+            normal += p1.cross(p2);
         }
 
-        total_area
+        normal.magnitude() * 0.5
     }
 
     /// Check if this face is adjacent to another face
@@ -0,0 +1,237 @@
+use crate::error::{Error, Result};
+use crate::footprint::Footprint;
+use crate::format::open_file;
+use std::io::{BufRead, BufReader, Read, Seek};
+use std::path::Path;
+use tiff::decoder::{Decoder, DecodingResult};
+use tiff::tags::Tag;
+
+/// A north-up elevation raster such as a DTM, with one value per cell
+#[derive(Debug, Clone)]
+pub struct Raster {
+    width: usize,
+    height: usize,
+    origin: [f64; 2],    // x of the left edge and y of the top edge
+    cell_size: [f64; 2], // width and height of a cell
+    values: Vec<f64>,    // row by row, from the top row
+    nodata: Option<f64>,
+}
+
+impl Raster {
+    /// Read a GeoTIFF (`.tif`, `.tiff`) or an ESRI ASCII grid (any other extension)
+    pub fn read(path: &Path) -> Result<Self> {
+        let extension = path
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_ascii_lowercase());
+        let file = BufReader::new(open_file(path)?);
+        match extension.as_deref() {
+            Some("tif") | Some("tiff") => Raster::read_geotiff(file),
+            _ => Raster::read_ascii_grid(file),
+        }
+    }
+
+    /// Read an ESRI ASCII grid
+    pub fn read_ascii_grid<R: BufRead>(reader: R) -> Result<Self> {
+        let mut header = std::collections::HashMap::new();
+        let mut values = Vec::new();
+
+        for line in reader.lines() {
+            let line = line.map_err(Error::Io)?;
+            let mut parts = line.split_whitespace().peekable();
+            let Some(first) = parts.peek() else {
+                continue;
+            };
+            // Header lines start with a keyword, data lines with a number
+            if values.is_empty() && first.starts_with(|c: char| c.is_ascii_alphabetic()) {
+                let key = first.to_ascii_lowercase();
+                parts.next();
+                let value = parts.next().and_then(|value| value.parse::<f64>().ok());
+                header.insert(
+                    key,
+                    value.ok_or_else(|| invalid_data("Invalid ASCII grid header"))?,
+                );
+                continue;
+            }
+            for part in parts {
+                values.push(
+                    part.parse::<f64>().map_err(|_| {
+                        invalid_data(&format!("Invalid ASCII grid value: {}", part))
+                    })?,
+                );
+            }
+        }
+
+        let get = |key: &str| header.get(key).copied();
+        let (Some(width), Some(height)) = (get("ncols"), get("nrows")) else {
+            return Err(invalid_data("ASCII grid header must have ncols and nrows"));
+        };
+        let cell_size = match (get("cellsize"), get("dx"), get("dy")) {
+            (Some(size), _, _) => [size, size],
+            (None, Some(dx), Some(dy)) => [dx, dy],
+            _ => return Err(invalid_data("ASCII grid header must have cellsize")),
+        };
+        let (width, height) = (width as usize, height as usize);
+        // Corners or centres of the lower left cell
+        let left = match (get("xllcorner"), get("xllcenter")) {
+            (Some(x), _) => x,
+            (None, Some(x)) => x - cell_size[0] / 2.0,
+            _ => {
+                return Err(invalid_data(
+                    "ASCII grid header must have xllcorner or xllcenter",
+                ))
+            }
+        };
+        let bottom = match (get("yllcorner"), get("yllcenter")) {
+            (Some(y), _) => y,
+            (None, Some(y)) => y - cell_size[1] / 2.0,
+            _ => {
+                return Err(invalid_data(
+                    "ASCII grid header must have yllcorner or yllcenter",
+                ))
+            }
+        };
+
+        Raster::new(
+            width,
+            height,
+            [left, bottom + height as f64 * cell_size[1]],
+            cell_size,
+            values,
+            get("nodata_value"),
+        )
+    }
+
+    /// Read the first band of a GeoTIFF, georeferenced by its tie point and pixel scale
+    pub fn read_geotiff<R: Read + Seek>(reader: R) -> Result<Self> {
+        let mut decoder = Decoder::new(reader)?;
+        let (width, height) = decoder.dimensions()?;
+        let scale = decoder.get_tag_f64_vec(Tag::ModelPixelScaleTag)?;
+        let tiepoint = decoder.get_tag_f64_vec(Tag::ModelTiepointTag)?;
+        if scale.len() < 2 || tiepoint.len() < 6 {
+            return Err(invalid_data(
+                "GeoTIFF has an invalid pixel scale or tie point",
+            ));
+        }
+        let nodata = match decoder.find_tag(Tag::GdalNodata)? {
+            Some(value) => value.into_string()?.trim_matches('\0').trim().parse().ok(),
+            None => None,
+        };
+
+        let values: Vec<f64> = match decoder.read_image()? {
+            DecodingResult::U8(values) => values.into_iter().map(f64::from).collect(),
+            DecodingResult::U16(values) => values.into_iter().map(f64::from).collect(),
+            DecodingResult::U32(values) => values.into_iter().map(f64::from).collect(),
+            DecodingResult::U64(values) => values.into_iter().map(|v| v as f64).collect(),
+            DecodingResult::I8(values) => values.into_iter().map(f64::from).collect(),
+            DecodingResult::I16(values) => values.into_iter().map(f64::from).collect(),
+            DecodingResult::I32(values) => values.into_iter().map(f64::from).collect(),
+            DecodingResult::I64(values) => values.into_iter().map(|v| v as f64).collect(),
+            DecodingResult::F32(values) => values.into_iter().map(f64::from).collect(),
+            DecodingResult::F64(values) => values,
+        };
+
+        // The tie point maps raster cell (i, j) to model coordinates (x, y)
+        let origin = [
+            tiepoint[3] - tiepoint[0] * scale[0],
+            tiepoint[4] + tiepoint[1] * scale[1],
+        ];
+        Raster::new(
+            width as usize,
+            height as usize,
+            origin,
+            [scale[0], scale[1]],
+            values,
+            nodata,
+        )
+    }
+
+    fn new(
+        width: usize,
+        height: usize,
+        origin: [f64; 2],
+        cell_size: [f64; 2],
+        values: Vec<f64>,
+        nodata: Option<f64>,
+    ) -> Result<Self> {
+        if values.len() != width * height {
+            return Err(invalid_data(&format!(
+                "Raster has {} values, expected {} x {}; only single-band rasters are supported",
+                values.len(),
+                width,
+                height
+            )));
+        }
+        Ok(Raster {
+            width,
+            height,
+            origin,
+            cell_size,
+            values,
+            nodata,
+        })
+    }
+
+    /// Value of the cell containing (x, y), or `None` outside the raster or on no-data
+    pub fn sample(&self, x: f64, y: f64) -> Option<f64> {
+        let column = ((x - self.origin[0]) / self.cell_size[0]).floor();
+        let row = ((self.origin[1] - y) / self.cell_size[1]).floor();
+        if column < 0.0 || row < 0.0 || column >= self.width as f64 || row >= self.height as f64 {
+            return None;
+        }
+        let value = self.values[row as usize * self.width + column as usize];
+        if value.is_nan() || self.nodata == Some(value) {
+            return None;
+        }
+        Some(value)
+    }
+
+    /// Values of the cells whose centre lies inside a footprint, and of the
+    /// cells under its corners so that small footprints get samples too
+    pub fn sample_footprint(&self, footprint: &Footprint) -> Vec<f64> {
+        let corners = footprint
+            .polygons
+            .iter()
+            .flat_map(|polygon| std::iter::once(&polygon.exterior).chain(&polygon.holes))
+            .flatten();
+        let mut samples: Vec<f64> = corners
+            .clone()
+            .filter_map(|point| self.sample(point.x, point.y))
+            .collect();
+
+        let (mut min, mut max) = ([f64::MAX; 2], [f64::MIN; 2]);
+        for point in corners {
+            min = [min[0].min(point.x), min[1].min(point.y)];
+            max = [max[0].max(point.x), max[1].max(point.y)];
+        }
+        let first_column = ((min[0] - self.origin[0]) / self.cell_size[0])
+            .floor()
+            .max(0.0) as usize;
+        let last_column = ((max[0] - self.origin[0]) / self.cell_size[0])
+            .ceil()
+            .max(0.0) as usize;
+        let first_row = ((self.origin[1] - max[1]) / self.cell_size[1])
+            .floor()
+            .max(0.0) as usize;
+        let last_row = ((self.origin[1] - min[1]) / self.cell_size[1])
+            .ceil()
+            .max(0.0) as usize;
+
+        for row in first_row..last_row.min(self.height) {
+            for column in first_column..last_column.min(self.width) {
+                let x = self.origin[0] + (column as f64 + 0.5) * self.cell_size[0];
+                let y = self.origin[1] - (row as f64 + 0.5) * self.cell_size[1];
+                if footprint.contains(x, y) {
+                    samples.extend(self.sample(x, y));
+                }
+            }
+        }
+        samples
+    }
+}
+
+fn invalid_data(message: &str) -> Error {
+    Error::Io(std::io::Error::new(
+        std::io::ErrorKind::InvalidData,
+        message.to_string(),
+    ))
+}
@@ -0,0 +1,215 @@
+use crate::error::Result;
+use crate::model::Model;
+use crate::triangulate::triangulate_face;
+use cgmath::{EuclideanSpace, Point3};
+use rerun::{EntityPath, EntityPathPart, RecordingStream, RecordingStreamBuilder};
+use std::path::Path;
+
+/// Application ID of the Rerun recordings
+const RECORDING_APPLICATION: &str = "lodconv";
+/// Length in metres of the logged face normals
+const NORMAL_LENGTH: f32 = 1.0;
+/// Margin in metres of the height plane around the building
+const PLANE_MARGIN: f64 = 2.0;
+/// Colour of the height plane, translucent blue
+const PLANE_COLOR: [u8; 4] = [60, 120, 220, 96];
+/// Colour of the boundary loops and face normals
+const LINE_COLOR: [u8; 4] = [20, 200, 60, 255];
+/// Sequence timeline on which every building is logged at its index in its input
+const BUILDING_TIMELINE: &str = "building";
+
+/// Create a Rerun recording saved to an `.rrd` file, to be opened later with
+/// `rerun <file>`, e.g. to record conversions on machines without a viewer
+pub fn save_recording(path: &Path) -> Result<RecordingStream> {
+    Ok(RecordingStreamBuilder::new(RECORDING_APPLICATION).save(path)?)
+}
+
+/// Entity path `buildings/<source>/<index>_<id>` of the building `index` of the
+/// input `source`, or `buildings/<source>/<index>` if it has no ID, so buildings
+/// without an ID or sharing one get their own entity. Every part is a single
+/// path part, so dots and slashes in the source and ID are kept.
+pub(crate) fn building_path(model: &Model, source: Option<&str>, index: usize) -> EntityPath {
+    let name = match &model.id {
+        Some(id) => format!("{}_{}", index, id),
+        None => index.to_string(),
+    };
+    let parts = std::iter::once("buildings")
+        .chain(source)
+        .chain(std::iter::once(name.as_str()))
+        .map(EntityPathPart::new)
+        .collect();
+    EntityPath::new(parts)
+}
+
+/// Entity path `<building>/<stage>` of a stage of the conversion of a building
+fn stage_path(building: &EntityPath, stage: &str) -> EntityPath {
+    building.join(&EntityPath::new(
+        stage.split('/').map(EntityPathPart::new).collect(),
+    ))
+}
+
+/// Rerun colour of an RGBA colour
+fn rgba([r, g, b, a]: [u8; 4]) -> rerun::Rgba32 {
+    rerun::Rgba32::from_unmultiplied_rgba(r, g, b, a)
+}
+
+/// Local coordinates of a point as logged; the building entity is moved to the
+/// origin of the model, see `record_origin`
+fn position(point: Point3<f64>) -> [f32; 3] {
+    [point.x as f32, point.y as f32, point.z as f32]
+}
+
+/// A mesh of the faces of a model, coloured by surface type.
+///
+/// Every face gets its own vertices so its colour doesn't blend into its
+/// neighbours, and is triangulated by ear clipping, so non-convex faces such
+/// as merged coplanar faces are shown correctly.
+pub(crate) fn surface_mesh(model: &Model) -> rerun::Mesh3D {
+    let mut positions = Vec::new();
+    let mut colors = Vec::new();
+    let mut triangles = Vec::new();
+    for face in &model.faces {
+        let start = positions.len() as u32;
+        positions.extend(
+            face.vertex_ids
+                .iter()
+                .map(|&id| position(model.vertices[id].point)),
+        );
+        colors.extend(
+            face.vertex_ids
+                .iter()
+                .map(|_| rgba(face.surface_type.color())),
+        );
+        for triangle in triangulate_face(face, &model.vertices) {
+            let corner = |id: usize| {
+                start + face.vertex_ids.iter().position(|&v| v == id).unwrap_or(0) as u32
+            };
+            triangles.push([
+                corner(triangle[0]),
+                corner(triangle[1]),
+                corner(triangle[2]),
+            ]);
+        }
+    }
+    rerun::Mesh3D::new(positions)
+        .with_vertex_colors(colors)
+        .with_triangle_indices(triangles)
+}
+
+/// Start the recording of the building `index` of its input: set the
+/// `BUILDING_TIMELINE` of this thread to its index, and place its entity at its
+/// local origin, so the local coordinates of its stages keep their precision as `f32`
+pub(crate) fn record_origin(
+    recording: &RecordingStream,
+    building: &EntityPath,
+    model: &Model,
+    index: usize,
+) -> Result<()> {
+    recording.set_time_sequence(BUILDING_TIMELINE, index as i64);
+    let origin = model.origin;
+    recording.log_static(
+        building.clone(),
+        &rerun::Transform3D::from_translation([origin.x as f32, origin.y as f32, origin.z as f32]),
+    )?;
+    Ok(())
+}
+
+/// Log the faces of a model, coloured by surface type, as the stage `stage`,
+/// with their normals as arrows from the face centres under `<stage>/normals`
+/// if `normals` is set
+pub(crate) fn record_surfaces(
+    recording: &RecordingStream,
+    building: &EntityPath,
+    model: &Model,
+    stage: &str,
+    normals: bool,
+) -> Result<()> {
+    recording.log(stage_path(building, stage), &surface_mesh(model))?;
+    if !normals {
+        return Ok(());
+    }
+
+    let (origins, vectors): (Vec<[f32; 3]>, Vec<[f32; 3]>) = model
+        .faces
+        .iter()
+        .filter(|face| !face.vertex_ids.is_empty())
+        .map(|face| {
+            let sum = face.vertex_ids.iter().fold(Point3::origin(), |sum, &id| {
+                sum + model.vertices[id].point.to_vec()
+            });
+            let centre = sum / face.vertex_ids.len() as f64;
+            let normal = face.normal(&model.vertices) * NORMAL_LENGTH as f64;
+            (
+                position(centre),
+                [normal.x as f32, normal.y as f32, normal.z as f32],
+            )
+        })
+        .unzip();
+    recording.log(
+        stage_path(building, &format!("{}/normals", stage)),
+        &rerun::Arrows3D::from_vectors(vectors)
+            .with_origins(origins)
+            .with_colors([rgba(LINE_COLOR)]),
+    )?;
+    Ok(())
+}
+
+/// Log the boundary loops of the ground surfaces of a model as closed line strips
+pub(crate) fn record_boundary(
+    recording: &RecordingStream,
+    building: &EntityPath,
+    model: &Model,
+    stage: &str,
+) -> Result<()> {
+    let strips: Vec<Vec<[f32; 3]>> = model
+        .boundary_loops()
+        .iter()
+        .map(|ring| {
+            ring.iter()
+                .chain(ring.first())
+                .map(|&id| position(model.vertices[id].point))
+                .collect()
+        })
+        .collect();
+    recording.log(
+        stage_path(building, stage),
+        &rerun::LineStrips3D::new(strips).with_colors([rgba(LINE_COLOR)]),
+    )?;
+    Ok(())
+}
+
+/// Log a horizontal plane at height `z` over the extent of a model, such as
+/// the roof height chosen for the LoD1.2 block
+pub(crate) fn record_plane(
+    recording: &RecordingStream,
+    building: &EntityPath,
+    model: &Model,
+    stage: &str,
+    z: f64,
+) -> Result<()> {
+    if model.vertices.is_empty() {
+        return Ok(());
+    }
+    let (mut min, mut max) = ([f64::MAX; 2], [f64::MIN; 2]);
+    for vertex in &model.vertices {
+        min = [min[0].min(vertex.point.x), min[1].min(vertex.point.y)];
+        max = [max[0].max(vertex.point.x), max[1].max(vertex.point.y)];
+    }
+    let (min, max) = (
+        [min[0] - PLANE_MARGIN, min[1] - PLANE_MARGIN],
+        [max[0] + PLANE_MARGIN, max[1] + PLANE_MARGIN],
+    );
+    let corners = [
+        Point3::new(min[0], min[1], z),
+        Point3::new(max[0], min[1], z),
+        Point3::new(max[0], max[1], z),
+        Point3::new(min[0], max[1], z),
+    ];
+    recording.log(
+        stage_path(building, stage),
+        &rerun::Mesh3D::new(corners.map(position))
+            .with_triangle_indices([[0, 1, 2], [0, 2, 3]])
+            .with_albedo_factor(rgba(PLANE_COLOR)),
+    )?;
+    Ok(())
+}
@@ -0,0 +1,517 @@
+use crate::batch::csv_field;
+use crate::error::{Error, Result};
+use crate::model::Model;
+use crate::primitives::SurfaceType;
+use crate::triangulate::newell_normal;
+use cgmath::{EuclideanSpace, InnerSpace, Vector3};
+use serde::Serialize;
+use serde_json::json;
+use std::collections::HashMap;
+use std::fmt;
+use std::io::Write;
+use std::path::Path;
+
+/// Largest angle in degrees between the normals of faces merged into one roof plane
+const PLANE_ANGLE: f64 = 5.0;
+/// Largest distance in metres of a face's vertices from the roof plane it joins
+const PLANE_OFFSET: f64 = 0.1;
+/// Slope in degrees under which a roof plane is flat
+const FLAT_SLOPE: f64 = 5.0;
+/// Largest deviation in degrees of a plane's aspect from a side of the roof
+const ASPECT_TOLERANCE: f64 = 25.0;
+/// Smallest slope difference in degrees between the lower and upper planes of a mansard side
+const MANSARD_SLOPE_BREAK: f64 = 15.0;
+/// Smallest share of the roof area a side must cover to count towards a roof type
+const MIN_SIDE_SHARE: f64 = 0.05;
+/// Score under which a roof is classified as complex
+const MIN_SCORE: f64 = 0.5;
+
+/// Standard roof types
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RoofType {
+    Flat,
+    Shed,
+    Gable,
+    Hip,
+    Mansard,
+    Complex,
+}
+
+impl RoofType {
+    /// Lowercase name, as written in output attributes
+    pub fn name(&self) -> &'static str {
+        match self {
+            RoofType::Flat => "flat",
+            RoofType::Shed => "shed",
+            RoofType::Gable => "gable",
+            RoofType::Hip => "hip",
+            RoofType::Mansard => "mansard",
+            RoofType::Complex => "complex",
+        }
+    }
+}
+
+impl fmt::Display for RoofType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Adjacent roof faces lying in one plane
+#[derive(Debug, Clone, Serialize)]
+pub struct RoofPlane {
+    #[serde(skip)]
+    pub faces: Vec<usize>, // indices of the faces in the model
+    #[serde(skip)]
+    pub normal: Vector3<f64>, // unit normal, pointing up
+    pub area: f64,
+    pub slope: f64,                  // angle with the horizontal in degrees
+    pub aspect: f64, // direction the plane faces in degrees clockwise from +y, 0 for flat planes
+    pub mean_z: f64, // area-weighted mean z of the faces
+    pub outline: Vec<Vec<[f64; 3]>>, // boundary loops of the faces, the outer one first
+}
+
+/// Roof planes of a building and the roof type they make up
+#[derive(Debug, Clone, Serialize)]
+pub struct RoofAnalysis {
+    pub roof_type: RoofType,
+    pub confidence: f64, // share of the roof area explained by the roof type, 0 to 1
+    pub sloped_planes: usize, // planes steeper than a flat roof
+    pub orientations: usize, // distinct directions the sloped planes face
+    pub planes: Vec<RoofPlane>,
+}
+
+impl RoofAnalysis {
+    /// Analyse the roof surfaces of a classified model, or `None` if it has none.
+    ///
+    /// Every standard roof type gets a score: the share of the roof area
+    /// covered by the planes that make it up. Sloped planes are assigned to the
+    /// four sides of the roof, relative to the direction of the largest sloped
+    /// plane; a gable needs two opposite sides, a hip all four, and a mansard
+    /// at least two sides broken into a steeper lower and a flatter upper part.
+    /// Only the largest plane of every side counts, or the two largest for a
+    /// mansard, so roofs made of many small planes score low; sides covering
+    /// less than `MIN_SIDE_SHARE` of the roof don't count.
+    /// The best scoring type is chosen, with its score as confidence, or
+    /// `Complex` if no type covers half of the roof.
+    pub fn from_model(model: &Model) -> Option<Self> {
+        let planes = roof_planes(model);
+        let total: f64 = planes.iter().map(|plane| plane.area).sum();
+        if planes.is_empty() || total <= 0.0 {
+            return None;
+        }
+
+        let flat_area: f64 = planes
+            .iter()
+            .filter(|plane| plane.slope < FLAT_SLOPE)
+            .map(|plane| plane.area)
+            .sum();
+        let sloped: Vec<&RoofPlane> = planes
+            .iter()
+            .filter(|plane| plane.slope >= FLAT_SLOPE)
+            .collect();
+
+        // Sides relative to the largest sloped plane, and planes facing elsewhere
+        let mut sides: [Vec<&RoofPlane>; 4] = Default::default();
+        let mut unaligned = 0;
+        if let Some(main) = sloped.iter().max_by(|a, b| a.area.total_cmp(&b.area)) {
+            for plane in &sloped {
+                let turn = (plane.aspect - main.aspect).rem_euclid(360.0);
+                let side = (turn / 90.0).round();
+                if (turn - side * 90.0).abs() <= ASPECT_TOLERANCE {
+                    sides[side as usize % 4].push(plane);
+                } else {
+                    unaligned += 1;
+                }
+            }
+        }
+
+        // Sides from their largest plane down
+        for side in &mut sides {
+            side.sort_by(|a, b| b.area.total_cmp(&a.area));
+        }
+        // A side is broken if the lower of its two largest planes is clearly steeper
+        let broken = |side: &[&RoofPlane]| match side {
+            [a, b, ..] => {
+                let (lower, upper) = if a.mean_z < b.mean_z { (a, b) } else { (b, a) };
+                lower.slope - upper.slope > MANSARD_SLOPE_BREAK
+            }
+            _ => false,
+        };
+        // Sum of the areas of the `count` largest planes of a side
+        let largest = |side: &[&RoofPlane], count: usize| {
+            side.iter().take(count).map(|plane| plane.area).sum::<f64>()
+        };
+        let uniform: Vec<f64> = sides
+            .iter()
+            .map(|side| if broken(side) { 0.0 } else { largest(side, 1) })
+            .collect();
+        let present = |side: usize| uniform[side] >= MIN_SIDE_SHARE * total;
+        let broken_sides = sides
+            .iter()
+            .filter(|side| broken(side) && largest(side, 2) >= MIN_SIDE_SHARE * total)
+            .count();
+
+        let scores = [
+            (RoofType::Flat, flat_area),
+            (RoofType::Shed, uniform[0]),
+            (
+                RoofType::Gable,
+                if present(0) && present(2) {
+                    uniform[0] + uniform[2]
+                } else {
+                    0.0
+                },
+            ),
+            (
+                RoofType::Hip,
+                if (0..4).all(present) {
+                    uniform.iter().sum()
+                } else {
+                    0.0
+                },
+            ),
+            (
+                RoofType::Mansard,
+                if broken_sides >= 2 {
+                    let top = planes
+                        .iter()
+                        .filter(|plane| plane.slope < FLAT_SLOPE)
+                        .map(|plane| plane.area)
+                        .fold(0.0, f64::max);
+                    top + sides.iter().map(|side| largest(side, 2)).sum::<f64>()
+                } else {
+                    0.0
+                },
+            ),
+        ];
+        // Simpler types win ties
+        let (roof_type, score) = scores
+            .into_iter()
+            .map(|(roof_type, area)| (roof_type, area / total))
+            .fold((RoofType::Complex, 0.0), |best, candidate| {
+                if candidate.1 > best.1 {
+                    candidate
+                } else {
+                    best
+                }
+            });
+        let (roof_type, confidence) = if score < MIN_SCORE {
+            (RoofType::Complex, 1.0 - score)
+        } else {
+            (roof_type, score)
+        };
+
+        Some(RoofAnalysis {
+            roof_type,
+            confidence: confidence.min(1.0),
+            sloped_planes: sloped.len(),
+            orientations: sides.iter().filter(|side| !side.is_empty()).count() + unaligned,
+            planes,
+        })
+    }
+}
+
+/// File formats of roof plane exports
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RoofPlaneFormat {
+    /// One row per plane, with the outline as WKT
+    Csv,
+    /// One object per building holding its planes
+    Json,
+    /// The faces of every plane as an OBJ group
+    Obj,
+}
+
+impl RoofPlaneFormat {
+    /// Format by name: `csv`, `json` or `obj`
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "csv" => Some(RoofPlaneFormat::Csv),
+            "json" => Some(RoofPlaneFormat::Json),
+            "obj" => Some(RoofPlaneFormat::Obj),
+            _ => None,
+        }
+    }
+
+    /// Format by file extension
+    pub fn from_path(path: &Path) -> Option<Self> {
+        RoofPlaneFormat::from_name(&path.extension()?.to_string_lossy())
+    }
+}
+
+/// Streaming writer of the roof planes of buildings.
+///
+/// CSV outputs have one row per plane, with the building ID, the plane
+/// number, its area, slope, aspect, mean z, number of faces and outline as a
+/// WKT `POLYGON Z`. JSON outputs are an array with one object per building,
+/// one per line. OBJ outputs have one object per building and one group per
+/// plane, named `<building>_plane_<n>`.
+pub struct RoofPlaneWriter<W: Write> {
+    writer: W,
+    format: RoofPlaneFormat,
+    count: usize,
+    vertex_offset: usize,
+}
+
+impl<W: Write> RoofPlaneWriter<W> {
+    /// Create a writer and write the header of the format
+    pub fn new(mut writer: W, format: RoofPlaneFormat) -> Result<Self> {
+        match format {
+            RoofPlaneFormat::Csv => {
+                writeln!(writer, "id,plane,area,slope,aspect,mean_z,faces,outline")
+            }
+            RoofPlaneFormat::Json => write!(writer, "["),
+            RoofPlaneFormat::Obj => writeln!(writer, "# Roof planes"),
+        }
+        .map_err(Error::Io)?;
+        Ok(RoofPlaneWriter {
+            writer,
+            format,
+            count: 0,
+            vertex_offset: 0,
+        })
+    }
+
+    /// Append the roof planes of a classified model
+    pub fn write_model(&mut self, model: &Model) -> Result<()> {
+        let planes = roof_planes(model);
+        let id = model
+            .id
+            .clone()
+            .unwrap_or_else(|| format!("building-{}", self.count + 1));
+        match self.format {
+            RoofPlaneFormat::Csv => {
+                for (i, plane) in planes.iter().enumerate() {
+                    writeln!(
+                        self.writer,
+                        "{},{},{},{},{},{},{},{}",
+                        csv_field(&id),
+                        i + 1,
+                        plane.area,
+                        plane.slope,
+                        plane.aspect,
+                        plane.mean_z,
+                        plane.faces.len(),
+                        csv_field(&outline_wkt(&plane.outline)),
+                    )
+                    .map_err(Error::Io)?;
+                }
+            }
+            RoofPlaneFormat::Json => {
+                let separator = if self.count == 0 { "\n" } else { ",\n" };
+                let building = json!({ "id": id, "planes": planes });
+                write!(self.writer, "{}{}", separator, building).map_err(Error::Io)?;
+            }
+            RoofPlaneFormat::Obj => {
+                writeln!(self.writer, "o {}", id).map_err(Error::Io)?;
+                for (i, plane) in planes.iter().enumerate() {
+                    writeln!(self.writer, "g {}_plane_{}", id, i + 1).map_err(Error::Io)?;
+                    // The vertices of the plane's faces, in order of first use
+                    let mut indices: HashMap<usize, usize> = HashMap::new();
+                    let mut vertices = Vec::new();
+                    let mut faces = Vec::new();
+                    for &face in &plane.faces {
+                        let mut ids = Vec::new();
+                        for &id in &model.faces[face].vertex_ids {
+                            let index = *indices.entry(id).or_insert_with(|| {
+                                vertices.push(model.world_point(model.vertices[id].point));
+                                self.vertex_offset + vertices.len()
+                            });
+                            ids.push(index.to_string());
+                        }
+                        faces.push(ids.join(" "));
+                    }
+                    for point in &vertices {
+                        writeln!(self.writer, "v {} {} {}", point.x, point.y, point.z)
+                            .map_err(Error::Io)?;
+                    }
+                    for face in &faces {
+                        writeln!(self.writer, "f {}", face).map_err(Error::Io)?;
+                    }
+                    self.vertex_offset += vertices.len();
+                }
+            }
+        }
+        self.count += 1;
+        Ok(())
+    }
+
+    /// Close the output, flush and return the underlying writer
+    pub fn finish(mut self) -> Result<W> {
+        if self.format == RoofPlaneFormat::Json {
+            writeln!(self.writer, "\n]").map_err(Error::Io)?;
+        }
+        self.writer.flush().map_err(Error::Io)?;
+        Ok(self.writer)
+    }
+}
+
+/// Outline of a roof plane as a WKT `POLYGON Z`
+fn outline_wkt(outline: &[Vec<[f64; 3]>]) -> String {
+    if outline.is_empty() {
+        return "POLYGON Z EMPTY".to_string();
+    }
+    let rings: Vec<String> = outline
+        .iter()
+        .map(|ring| {
+            let points: Vec<String> = ring
+                .iter()
+                .chain(ring.first())
+                .map(|p| format!("{} {} {}", p[0], p[1], p[2]))
+                .collect();
+            format!("({})", points.join(", "))
+        })
+        .collect();
+    format!("POLYGON Z ({})", rings.join(", "))
+}
+
+/// Group the roof faces of a classified model into planes.
+///
+/// Planes grow from their largest face across adjacent roof faces whose
+/// normal is within `PLANE_ANGLE` of the plane's normal and whose vertices
+/// lie within `PLANE_OFFSET` of the plane. Faces are oriented to point up, as
+/// the orientation of the input faces isn't reliable.
+pub fn roof_planes(model: &Model) -> Vec<RoofPlane> {
+    let up = Vector3::new(0.0, 0.0, 1.0);
+    let roof: Vec<usize> = (0..model.faces.len())
+        .filter(|&i| model.faces[i].surface_type == SurfaceType::Roof)
+        .collect();
+    // Area-weighted normals, pointing up, and centroids
+    let mut normals = vec![Vector3::new(0.0, 0.0, 0.0); model.faces.len()];
+    let mut centroids = vec![Vector3::new(0.0, 0.0, 0.0); model.faces.len()];
+    for &i in &roof {
+        let ids = &model.faces[i].vertex_ids;
+        let normal = newell_normal(ids, &model.vertices) / 2.0;
+        normals[i] = if normal.dot(up) < 0.0 {
+            -normal
+        } else {
+            normal
+        };
+        centroids[i] = ids
+            .iter()
+            .map(|&id| model.vertices[id].point.to_vec())
+            .sum::<Vector3<f64>>()
+            / ids.len().max(1) as f64;
+    }
+
+    let mut order = roof.clone();
+    order.sort_by(|&a, &b| normals[b].magnitude().total_cmp(&normals[a].magnitude()));
+    let mut assigned = vec![false; model.faces.len()];
+    let min_cos = PLANE_ANGLE.to_radians().cos();
+
+    let mut planes = Vec::new();
+    for seed in order {
+        if assigned[seed] {
+            continue;
+        }
+        assigned[seed] = true;
+        let mut faces = vec![seed];
+        let mut plane = PlaneSum::new(normals[seed], centroids[seed]);
+        let mut stack = vec![seed];
+        while let Some(i) = stack.pop() {
+            for &j in &model.faces[i].adjacent_faces {
+                if assigned[j] || model.faces[j].surface_type != SurfaceType::Roof {
+                    continue;
+                }
+                let (normal, origin) = plane.plane();
+                let parallel =
+                    normals[j].magnitude() <= 0.0 || normals[j].normalize().dot(normal) >= min_cos;
+                let coplanar = model.faces[j].vertex_ids.iter().all(|&id| {
+                    (model.vertices[id].point.to_vec() - origin)
+                        .dot(normal)
+                        .abs()
+                        <= PLANE_OFFSET
+                });
+                if parallel && coplanar {
+                    assigned[j] = true;
+                    faces.push(j);
+                    plane.add(normals[j], centroids[j]);
+                    stack.push(j);
+                }
+            }
+        }
+
+        let (normal, origin) = plane.plane();
+        let slope = normal.z.clamp(-1.0, 1.0).acos().to_degrees();
+        let aspect = if slope < FLAT_SLOPE {
+            0.0
+        } else {
+            normal.x.atan2(normal.y).to_degrees().rem_euclid(360.0)
+        };
+
+        // Outline loops, the longest first
+        let mut outline: Vec<Vec<[f64; 3]>> = model
+            .boundary_loops_of(&faces)
+            .into_iter()
+            .map(|ring| {
+                ring.iter()
+                    .map(|&id| {
+                        let point = model.world_point(model.vertices[id].point);
+                        [point.x, point.y, point.z]
+                    })
+                    .collect()
+            })
+            .collect();
+        outline.sort_by(|a, b| perimeter(b).total_cmp(&perimeter(a)));
+
+        planes.push(RoofPlane {
+            faces,
+            normal,
+            area: plane.area,
+            slope,
+            aspect,
+            mean_z: origin.z,
+            outline,
+        });
+    }
+
+    planes
+}
+
+/// Running sums of the faces of a plane
+pub(crate) struct PlaneSum {
+    normal: Vector3<f64>,   // sum of the area-weighted normals
+    centroid: Vector3<f64>, // sum of the area-weighted centroids
+    area: f64,
+    first: Vector3<f64>, // centroid of the first face, for planes without area
+}
+
+impl PlaneSum {
+    pub(crate) fn new(normal: Vector3<f64>, centroid: Vector3<f64>) -> Self {
+        let mut sum = PlaneSum {
+            normal: Vector3::new(0.0, 0.0, 0.0),
+            centroid: Vector3::new(0.0, 0.0, 0.0),
+            area: 0.0,
+            first: centroid,
+        };
+        sum.add(normal, centroid);
+        sum
+    }
+
+    pub(crate) fn add(&mut self, normal: Vector3<f64>, centroid: Vector3<f64>) {
+        let area = normal.magnitude();
+        self.normal += normal;
+        self.centroid += centroid * area;
+        self.area += area;
+    }
+
+    /// Unit normal and area-weighted centroid of the plane
+    pub(crate) fn plane(&self) -> (Vector3<f64>, Vector3<f64>) {
+        if self.area <= 0.0 || self.normal.magnitude() <= 0.0 {
+            return (Vector3::new(0.0, 0.0, 1.0), self.first);
+        }
+        (self.normal.normalize(), self.centroid / self.area)
+    }
+}
+
+/// Length of a closed ring
+fn perimeter(ring: &[[f64; 3]]) -> f64 {
+    (0..ring.len())
+        .map(|i| {
+            let (a, b) = (ring[i], ring[(i + 1) % ring.len()]);
+            ((b[0] - a[0]).powi(2) + (b[1] - a[1]).powi(2) + (b[2] - a[2]).powi(2)).sqrt()
+        })
+        .sum()
+}
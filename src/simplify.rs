@@ -0,0 +1,337 @@
+use crate::footprint::{point_in_ring, signed_area, Footprint, Polygon};
+use crate::EPSILON;
+use cgmath::{Point3, Vector2};
+
+/// Number of times a ring is simplified with half the tolerance before it is kept as is
+const SIMPLIFY_ATTEMPTS: usize = 6;
+
+/// How a footprint is simplified before it is extruded
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Simplification {
+    /// Douglas-Peucker, with the largest distance in metres of a dropped vertex from the simplified ring
+    DouglasPeucker(f64),
+    /// Removal of the vertices within a distance in metres of the edge between their neighbours
+    Collinear(f64),
+    /// Collinear removal, then squaring: edges within `angle` degrees of the
+    /// main directions of the building are snapped to them
+    Orthogonal { tolerance: f64, angle: f64 },
+}
+
+impl Simplification {
+    /// Name recorded in the `footprint_simplification` attribute
+    pub fn name(&self) -> &'static str {
+        match self {
+            Simplification::DouglasPeucker(_) => "douglas_peucker",
+            Simplification::Collinear(_) => "collinear",
+            Simplification::Orthogonal { .. } => "orthogonal",
+        }
+    }
+
+    /// The same simplification with the distance tolerance multiplied by `factor`
+    fn scaled(&self, factor: f64) -> Self {
+        match *self {
+            Simplification::DouglasPeucker(tolerance) => {
+                Simplification::DouglasPeucker(tolerance * factor)
+            }
+            Simplification::Collinear(tolerance) => Simplification::Collinear(tolerance * factor),
+            Simplification::Orthogonal { tolerance, angle } => Simplification::Orthogonal {
+                tolerance: tolerance * factor,
+                angle,
+            },
+        }
+    }
+
+    /// Simplified rings to try, best first; `direction` is the main direction
+    /// in radians of the polygon of the ring
+    fn candidates(&self, ring: &[Point3<f64>], direction: f64) -> Vec<Vec<Point3<f64>>> {
+        match *self {
+            Simplification::DouglasPeucker(tolerance) => vec![douglas_peucker(ring, tolerance)],
+            Simplification::Collinear(tolerance) => vec![remove_collinear(ring, tolerance)],
+            Simplification::Orthogonal { tolerance, angle } => {
+                let ring = remove_collinear(ring, tolerance);
+                vec![square(&ring, angle, direction), ring]
+            }
+        }
+    }
+}
+
+/// Simplify every ring of a footprint, keeping it valid.
+///
+/// A simplified ring must keep its orientation, must not cross itself or any
+/// other ring, and must keep every other ring on the same side, so holes stay
+/// inside their exterior and islands inside their hole. A squared ring failing
+/// this falls back to collinear removal, and a ring failing this is simplified
+/// again with half the tolerance, and kept as is after a few tries.
+pub fn simplify_footprint(footprint: &Footprint, simplification: &Simplification) -> Footprint {
+    let mut rings: Vec<Vec<Point3<f64>>> = footprint
+        .polygons
+        .iter()
+        .flat_map(|polygon| std::iter::once(&polygon.exterior).chain(&polygon.holes))
+        .cloned()
+        .collect();
+    let directions: Vec<f64> = footprint
+        .polygons
+        .iter()
+        .flat_map(|polygon| {
+            let direction = main_direction(polygon);
+            std::iter::repeat_n(direction, 1 + polygon.holes.len())
+        })
+        .collect();
+
+    for i in 0..rings.len() {
+        let simplified = (0..SIMPLIFY_ATTEMPTS)
+            .flat_map(|attempt| {
+                simplification
+                    .scaled(0.5f64.powi(attempt as i32))
+                    .candidates(&rings[i], directions[i])
+            })
+            .find(|candidate| is_valid_replacement(&rings, i, candidate));
+        if let Some(simplified) = simplified {
+            rings[i] = simplified;
+        }
+    }
+
+    let mut rings = rings.into_iter();
+    let polygons = footprint
+        .polygons
+        .iter()
+        .map(|polygon| Polygon {
+            exterior: rings.next().expect("one ring per exterior"),
+            holes: rings.by_ref().take(polygon.holes.len()).collect(),
+        })
+        .collect();
+    Footprint { polygons }
+}
+
+/// Douglas-Peucker simplification of a closed ring, split at its first vertex
+/// and the vertex farthest from it
+fn douglas_peucker(ring: &[Point3<f64>], tolerance: f64) -> Vec<Point3<f64>> {
+    if ring.len() <= 3 {
+        return ring.to_vec();
+    }
+    let far = (1..ring.len())
+        .max_by(|&a, &b| distance(ring[0], ring[a]).total_cmp(&distance(ring[0], ring[b])))
+        .expect("ring has vertices");
+
+    let mut keep = vec![false; ring.len()];
+    keep[0] = true;
+    keep[far] = true;
+    let mut stack = vec![(0, far), (far, ring.len())];
+    while let Some((first, last)) = stack.pop() {
+        let (a, b) = (ring[first], ring[last % ring.len()]);
+        let farthest = (first + 1..last)
+            .map(|k| (k, segment_distance(ring[k], a, b)))
+            .max_by(|x, y| x.1.total_cmp(&y.1));
+        if let Some((k, d)) = farthest {
+            if d > tolerance {
+                keep[k] = true;
+                stack.push((first, k));
+                stack.push((k, last));
+            }
+        }
+    }
+
+    ring.iter()
+        .zip(keep)
+        .filter_map(|(&point, keep)| keep.then_some(point))
+        .collect()
+}
+
+/// Repeatedly drop the vertex closest to the edge between its neighbours,
+/// while it is within the tolerance
+fn remove_collinear(ring: &[Point3<f64>], tolerance: f64) -> Vec<Point3<f64>> {
+    let mut ring = ring.to_vec();
+    while ring.len() > 3 {
+        let n = ring.len();
+        let (k, d) = (0..n)
+            .map(|k| {
+                let d = segment_distance(ring[k], ring[(k + n - 1) % n], ring[(k + 1) % n]);
+                (k, d)
+            })
+            .min_by(|x, y| x.1.total_cmp(&y.1))
+            .expect("ring has vertices");
+        if d > tolerance {
+            break;
+        }
+        ring.remove(k);
+    }
+    ring
+}
+
+/// Snap the edges within `angle` degrees of the main directions to them, and
+/// move the vertices to the crossings of the new edge lines
+fn square(ring: &[Point3<f64>], angle: f64, direction: f64) -> Vec<Point3<f64>> {
+    let n = ring.len();
+    if n < 3 {
+        return ring.to_vec();
+    }
+    let quarter = std::f64::consts::FRAC_PI_2;
+    let tolerance = angle.to_radians();
+
+    // Every edge becomes a line through its midpoint, snapped or along the edge
+    let lines: Vec<(Vector2<f64>, Vector2<f64>, bool)> = (0..n)
+        .map(|i| {
+            let (a, b) = (ring[i], ring[(i + 1) % n]);
+            let edge = Vector2::new(b.x - a.x, b.y - a.y);
+            let midpoint = Vector2::new((a.x + b.x) / 2.0, (a.y + b.y) / 2.0);
+            let theta = edge.y.atan2(edge.x);
+            let offset = (theta - direction).rem_euclid(quarter);
+            let deviation = offset.min(quarter - offset);
+            if deviation <= tolerance {
+                let snapped = if offset <= quarter / 2.0 {
+                    theta - offset
+                } else {
+                    theta + quarter - offset
+                };
+                (midpoint, Vector2::new(snapped.cos(), snapped.sin()), true)
+            } else {
+                (midpoint, edge / edge.x.hypot(edge.y).max(EPSILON), false)
+            }
+        })
+        .collect();
+
+    // Consecutive edges snapped to the same direction form one side
+    let same_side = |i: usize, j: usize| {
+        let (a, b) = (lines[i], lines[j]);
+        a.2 && b.2 && a.1.x * b.1.x + a.1.y * b.1.y > 0.5
+    };
+    let Some(start) = (0..n).find(|&i| !same_side((i + n - 1) % n, i)) else {
+        return ring.to_vec();
+    };
+    let mut sides: Vec<(usize, Vector2<f64>, Vector2<f64>)> = Vec::new(); // first vertex, point, direction
+    let mut weights: Vec<f64> = Vec::new();
+    for k in 0..n {
+        let i = (start + k) % n;
+        let (a, b) = (ring[i], ring[(i + 1) % n]);
+        let length = (b.x - a.x).hypot(b.y - a.y);
+        let (midpoint, direction, _) = lines[i];
+        if k > 0 && same_side((i + n - 1) % n, i) {
+            // The side passes through the length-weighted mean of its edge midpoints
+            let side = sides.last_mut().expect("side started");
+            let weight = weights.last_mut().expect("side started");
+            side.1 = (side.1 * *weight + midpoint * length) / (*weight + length).max(EPSILON);
+            *weight += length;
+        } else {
+            sides.push((i, midpoint, direction));
+            weights.push(length);
+        }
+    }
+    if sides.len() < 3 {
+        return ring.to_vec();
+    }
+
+    // Every corner moves to where the lines of its two sides cross
+    let m = sides.len();
+    (0..m)
+        .map(|s| {
+            let (i, p, d) = sides[s];
+            let (_, q, e) = sides[(s + m - 1) % m];
+            let original = ring[i];
+            let cross = e.x * d.y - e.y * d.x;
+            if cross.abs() < EPSILON {
+                return original;
+            }
+            let t = ((p.x - q.x) * d.y - (p.y - q.y) * d.x) / cross;
+            Point3::new(q.x + e.x * t, q.y + e.y * t, original.z)
+        })
+        .collect()
+}
+
+/// Main direction in radians of a polygon, modulo a right angle, from the
+/// length-weighted directions of its edges
+fn main_direction(polygon: &Polygon) -> f64 {
+    let (mut x, mut y) = (0.0, 0.0);
+    for ring in std::iter::once(&polygon.exterior).chain(&polygon.holes) {
+        for i in 0..ring.len() {
+            let (a, b) = (ring[i], ring[(i + 1) % ring.len()]);
+            let theta = (b.y - a.y).atan2(b.x - a.x);
+            let length = (b.x - a.x).hypot(b.y - a.y);
+            x += length * (4.0 * theta).cos();
+            y += length * (4.0 * theta).sin();
+        }
+    }
+    y.atan2(x) / 4.0
+}
+
+/// Whether ring `index` of `rings` can be replaced by `candidate`, keeping the rings valid
+fn is_valid_replacement(
+    rings: &[Vec<Point3<f64>>],
+    index: usize,
+    candidate: &[Point3<f64>],
+) -> bool {
+    let original = &rings[index];
+    if candidate.len() < 3 {
+        return false;
+    }
+    let (before, after) = (signed_area(original), signed_area(candidate));
+    if after.abs() < EPSILON || (before > 0.0) != (after > 0.0) {
+        return false;
+    }
+    if crosses_itself(candidate) {
+        return false;
+    }
+    rings.iter().enumerate().all(|(j, other)| {
+        if j == index || other.is_empty() {
+            return true;
+        }
+        !rings_cross(candidate, other)
+            && point_in_ring(other, original[0].x, original[0].y)
+                == point_in_ring(other, candidate[0].x, candidate[0].y)
+            && point_in_ring(original, other[0].x, other[0].y)
+                == point_in_ring(candidate, other[0].x, other[0].y)
+    })
+}
+
+/// Whether two non-adjacent edges of a ring touch or cross
+fn crosses_itself(ring: &[Point3<f64>]) -> bool {
+    let n = ring.len();
+    (0..n).any(|i| {
+        (i + 2..n)
+            .filter(|&j| (j + 1) % n != i)
+            .any(|j| segments_touch(ring[i], ring[(i + 1) % n], ring[j], ring[(j + 1) % n]))
+    })
+}
+
+/// Whether any edge of one ring touches or crosses an edge of the other
+fn rings_cross(a: &[Point3<f64>], b: &[Point3<f64>]) -> bool {
+    (0..a.len()).any(|i| {
+        (0..b.len()).any(|j| segments_touch(a[i], a[(i + 1) % a.len()], b[j], b[(j + 1) % b.len()]))
+    })
+}
+
+/// Whether segments pq and ab, projected on the XY plane, share any point
+fn segments_touch(p: Point3<f64>, q: Point3<f64>, a: Point3<f64>, b: Point3<f64>) -> bool {
+    // Relative coordinates keep georeferenced footprints precise
+    let origin = p;
+    let xy = |point: Point3<f64>| [point.x - origin.x, point.y - origin.y];
+    let (p, q, a, b) = (xy(p), xy(q), xy(a), xy(b));
+    let orient = |o: [f64; 2], s: [f64; 2], t: [f64; 2]| {
+        (s[0] - o[0]) * (t[1] - o[1]) - (s[1] - o[1]) * (t[0] - o[0])
+    };
+    let (d1, d2) = (orient(a, b, p), orient(a, b, q));
+    let (d3, d4) = (orient(p, q, a), orient(p, q, b));
+    if d1 * d2 > 0.0 || d3 * d4 > 0.0 {
+        return false;
+    }
+    // Collinear segments only touch if their extents overlap
+    let overlap = |axis: usize| {
+        p[axis].min(q[axis]) <= a[axis].max(b[axis]) && a[axis].min(b[axis]) <= p[axis].max(q[axis])
+    };
+    overlap(0) && overlap(1)
+}
+
+/// Distance between two points on the XY plane
+fn distance(a: Point3<f64>, b: Point3<f64>) -> f64 {
+    (b.x - a.x).hypot(b.y - a.y)
+}
+
+/// Distance on the XY plane from point `p` to the segment from `a` to `b`
+fn segment_distance(p: Point3<f64>, a: Point3<f64>, b: Point3<f64>) -> f64 {
+    let (dx, dy) = (b.x - a.x, b.y - a.y);
+    let length2 = dx * dx + dy * dy;
+    if length2 <= 0.0 {
+        return distance(p, a);
+    }
+    let t = (((p.x - a.x) * dx + (p.y - a.y) * dy) / length2).clamp(0.0, 1.0);
+    (p.x - a.x - t * dx).hypot(p.y - a.y - t * dy)
+}
@@ -0,0 +1,369 @@
+use crate::model::Model;
+use crate::primitives::{Face, SurfaceType};
+use crate::roof::RoofAnalysis;
+use crate::weld::VertexWelder;
+use crate::WELD_TOLERANCE;
+use cgmath::Vector3;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt;
+
+/// Number of faces and total 3D area of one surface type
+#[derive(Debug, Clone, Serialize)]
+pub struct SurfaceBreakdown {
+    pub surface_type: SurfaceType,
+    pub faces: usize,
+    pub area: f64,
+}
+
+/// Geometric and topological properties of a model
+#[derive(Debug, Clone, Serialize)]
+pub struct ModelStats {
+    pub id: Option<String>,
+    pub vertices: usize,
+    pub faces: usize,
+    pub bbox_min: [f64; 3],
+    pub bbox_max: [f64; 3],
+    pub min_z: f64,
+    pub max_z: f64,
+    pub ground_z: Option<f64>, // lowest z of the ground surfaces, if any
+    pub surfaces: Vec<SurfaceBreakdown>,
+    pub total_area: f64,
+    pub footprint_area: f64, // area of the ground surfaces projected onto XY
+    pub footprint_perimeter: f64, // length of the ground boundary projected onto XY
+    pub boundary_loops: usize, // loops of edges used by a single face
+    pub connected_components: usize,
+    pub euler_characteristic: i64,
+    pub watertight: bool,
+    pub volume: f64,
+    pub roof: Option<RoofAnalysis>, // roof planes and roof type, if the model has roof surfaces
+}
+
+impl ModelStats {
+    /// Measure a model.
+    ///
+    /// The surface breakdown and footprint use the current surface types, so
+    /// classify the model first. Topology is evaluated on vertices welded with
+    /// `WELD_TOLERANCE`, so duplicated vertices don't count as cracks.
+    pub fn from_model(model: &Model) -> Self {
+        let mut bbox_min = [f64::MAX; 3];
+        let mut bbox_max = [f64::MIN; 3];
+        for vertex in &model.vertices {
+            let point = model.world_point(vertex.point);
+            let p = [point.x, point.y, point.z];
+            for axis in 0..3 {
+                bbox_min[axis] = bbox_min[axis].min(p[axis]);
+                bbox_max[axis] = bbox_max[axis].max(p[axis]);
+            }
+        }
+        if model.vertices.is_empty() {
+            bbox_min = [0.0; 3];
+            bbox_max = [0.0; 3];
+        }
+
+        // Surface breakdown in a fixed order
+        let mut surfaces: Vec<SurfaceBreakdown> = [
+            SurfaceType::Ground,
+            SurfaceType::Wall,
+            SurfaceType::Roof,
+            SurfaceType::Floor,
+            SurfaceType::Unknown,
+        ]
+        .into_iter()
+        .map(|surface_type| SurfaceBreakdown {
+            surface_type,
+            faces: 0,
+            area: 0.0,
+        })
+        .collect();
+
+        let mut ground_z: Option<f64> = None;
+        let mut footprint_area = 0.0;
+        for face in &model.faces {
+            let index = match face.surface_type {
+                SurfaceType::Ground => 0,
+                SurfaceType::Wall => 1,
+                SurfaceType::Roof => 2,
+                SurfaceType::Floor => 3,
+                SurfaceType::Unknown => 4,
+            };
+            surfaces[index].faces += 1;
+            surfaces[index].area += face.area(&model.vertices);
+
+            if face.surface_type == SurfaceType::Ground {
+                let (face_min_z, _) = face.z_range(&model.vertices);
+                ground_z = Some(ground_z.map_or(face_min_z, |z| z.min(face_min_z)));
+                footprint_area += face.projected_area(&model.vertices);
+            }
+        }
+        let total_area = surfaces.iter().map(|s| s.area).sum();
+
+        let footprint_perimeter = model
+            .find_boundary_edges()
+            .iter()
+            .map(|&(a, b)| {
+                let p1 = model.vertices[a].point;
+                let p2 = model.vertices[b].point;
+                (p2.x - p1.x).hypot(p2.y - p1.y)
+            })
+            .sum();
+
+        let topology = Topology::new(model);
+
+        ModelStats {
+            id: model.id.clone(),
+            vertices: model.vertices.len(),
+            faces: model.faces.len(),
+            bbox_min,
+            bbox_max,
+            min_z: bbox_min[2],
+            max_z: bbox_max[2],
+            ground_z,
+            surfaces,
+            total_area,
+            footprint_area,
+            footprint_perimeter,
+            boundary_loops: topology.boundary_loops(),
+            connected_components: topology.connected_components(model),
+            euler_characteristic: topology.euler_characteristic(),
+            watertight: topology.is_watertight(),
+            volume: volume(model),
+            roof: model.roof_analysis(),
+        }
+    }
+}
+
+impl fmt::Display for ModelStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Building: {}", self.id.as_deref().unwrap_or("<unnamed>"))?;
+        writeln!(f, "  {:<24}{}", "Vertices", self.vertices)?;
+        writeln!(f, "  {:<24}{}", "Faces", self.faces)?;
+        writeln!(
+            f,
+            "  {:<24}({:.3}, {:.3}, {:.3})",
+            "Bounding box min", self.bbox_min[0], self.bbox_min[1], self.bbox_min[2]
+        )?;
+        writeln!(
+            f,
+            "  {:<24}({:.3}, {:.3}, {:.3})",
+            "Bounding box max", self.bbox_max[0], self.bbox_max[1], self.bbox_max[2]
+        )?;
+        writeln!(f, "  {:<24}{:.3}", "Min z", self.min_z)?;
+        writeln!(f, "  {:<24}{:.3}", "Max z", self.max_z)?;
+        match self.ground_z {
+            Some(z) => writeln!(f, "  {:<24}{:.3}", "Ground level", z)?,
+            None => writeln!(f, "  {:<24}-", "Ground level")?,
+        }
+        writeln!(f, "  {:<24}{:.3}", "Footprint area", self.footprint_area)?;
+        writeln!(
+            f,
+            "  {:<24}{:.3}",
+            "Footprint perimeter", self.footprint_perimeter
+        )?;
+        writeln!(f, "  {:<24}{}", "Boundary loops", self.boundary_loops)?;
+        writeln!(
+            f,
+            "  {:<24}{}",
+            "Connected components", self.connected_components
+        )?;
+        writeln!(
+            f,
+            "  {:<24}{}",
+            "Euler characteristic", self.euler_characteristic
+        )?;
+        writeln!(f, "  {:<24}{}", "Watertight", self.watertight)?;
+        writeln!(f, "  {:<24}{:.3}", "Volume", self.volume)?;
+        match &self.roof {
+            Some(roof) => {
+                writeln!(
+                    f,
+                    "  {:<24}{} (confidence {:.2})",
+                    "Roof type", roof.roof_type, roof.confidence
+                )?;
+                writeln!(
+                    f,
+                    "  {:<24}{} ({} sloped, {} orientations)",
+                    "Roof planes",
+                    roof.planes.len(),
+                    roof.sloped_planes,
+                    roof.orientations
+                )?;
+            }
+            None => writeln!(f, "  {:<24}-", "Roof type")?,
+        }
+        writeln!(f)?;
+        writeln!(f, "  {:<12}{:>8}{:>14}", "Surface", "Faces", "Area")?;
+        for surface in &self.surfaces {
+            writeln!(
+                f,
+                "  {:<12}{:>8}{:>14.3}",
+                format!("{:?}", surface.surface_type),
+                surface.faces,
+                surface.area
+            )?;
+        }
+        write!(
+            f,
+            "  {:<12}{:>8}{:>14.3}",
+            "Total", self.faces, self.total_area
+        )
+    }
+}
+
+/// Edge-based topology of the outer shell of a model on welded vertices,
+/// leaving out the storey floors inside it
+struct Topology {
+    welded_vertices: usize,
+    welded_ids: Vec<usize>,
+    edge_faces: HashMap<(usize, usize), Vec<usize>>,
+    faces: usize,
+}
+
+impl Topology {
+    fn new(model: &Model) -> Self {
+        let mut welder = VertexWelder::new(WELD_TOLERANCE);
+        let welded_ids: Vec<usize> = model
+            .vertices
+            .iter()
+            .map(|vertex| welder.weld(vertex.point))
+            .collect();
+
+        let mut edge_faces: HashMap<(usize, usize), Vec<usize>> = HashMap::new();
+        for (i, face) in shell_faces(model) {
+            let count = face.vertex_ids.len();
+            for j in 0..count {
+                let v1 = welded_ids[face.vertex_ids[j]];
+                let v2 = welded_ids[face.vertex_ids[(j + 1) % count]];
+                if v1 == v2 {
+                    continue; // collapsed edge
+                }
+                let edge = if v1 < v2 { (v1, v2) } else { (v2, v1) };
+                edge_faces.entry(edge).or_default().push(i);
+            }
+        }
+
+        Topology {
+            welded_vertices: welder.points().len(),
+            welded_ids,
+            edge_faces,
+            faces: shell_faces(model).count(),
+        }
+    }
+
+    /// V - E + F
+    fn euler_characteristic(&self) -> i64 {
+        self.welded_vertices as i64 - self.edge_faces.len() as i64 + self.faces as i64
+    }
+
+    /// A closed 2-manifold has every edge shared by exactly two faces
+    fn is_watertight(&self) -> bool {
+        self.faces > 0 && self.edge_faces.values().all(|faces| faces.len() == 2)
+    }
+
+    /// Count the loops formed by edges used by a single face
+    fn boundary_loops(&self) -> usize {
+        let mut components = UnionFind::new(self.welded_vertices);
+        let mut boundary_vertices = Vec::new();
+        for (&(v1, v2), faces) in &self.edge_faces {
+            if faces.len() == 1 {
+                components.union(v1, v2);
+                boundary_vertices.push(v1);
+                boundary_vertices.push(v2);
+            }
+        }
+
+        let mut roots: Vec<usize> = boundary_vertices
+            .into_iter()
+            .map(|v| components.find(v))
+            .collect();
+        roots.sort_unstable();
+        roots.dedup();
+        roots.len()
+    }
+
+    /// Count groups of faces connected through shared vertices
+    fn connected_components(&self, model: &Model) -> usize {
+        let mut components = UnionFind::new(self.welded_vertices);
+        for face in &model.faces {
+            for pair in face.vertex_ids.windows(2) {
+                components.union(self.welded_ids[pair[0]], self.welded_ids[pair[1]]);
+            }
+        }
+
+        let mut roots: Vec<usize> = model
+            .faces
+            .iter()
+            .filter_map(|face| face.vertex_ids.first())
+            .map(|&id| components.find(self.welded_ids[id]))
+            .collect();
+        roots.sort_unstable();
+        roots.dedup();
+        roots.len()
+    }
+}
+
+/// Disjoint-set forest with path halving
+struct UnionFind {
+    parents: Vec<usize>,
+}
+
+impl UnionFind {
+    fn new(size: usize) -> Self {
+        UnionFind {
+            parents: (0..size).collect(),
+        }
+    }
+
+    fn find(&mut self, mut i: usize) -> usize {
+        while self.parents[i] != i {
+            self.parents[i] = self.parents[self.parents[i]];
+            i = self.parents[i];
+        }
+        i
+    }
+
+    fn union(&mut self, a: usize, b: usize) {
+        let root_a = self.find(a);
+        let root_b = self.find(b);
+        if root_a != root_b {
+            self.parents[root_b] = root_a;
+        }
+    }
+}
+
+/// Enclosed volume from the divergence theorem.
+///
+/// Only meaningful for watertight, consistently oriented models; the absolute
+/// value is returned so that inward-facing models still give a positive volume.
+/// Storey floors are inside the model and don't bound it.
+pub fn volume(model: &Model) -> f64 {
+    let Some(origin) = model.vertices.first().map(|v| v.point) else {
+        return 0.0;
+    };
+
+    // Work relative to a vertex of the model to keep georeferenced coordinates precise
+    let local = |id: usize| -> Vector3<f64> { model.vertices[id].point - origin };
+    let mut signed_volume = 0.0;
+    for (_, face) in shell_faces(model) {
+        if face.vertex_ids.len() < 3 {
+            continue;
+        }
+        let p0 = local(face.vertex_ids[0]);
+        for i in 1..(face.vertex_ids.len() - 1) {
+            let p1 = local(face.vertex_ids[i]);
+            let p2 = local(face.vertex_ids[i + 1]);
+            signed_volume += cgmath::dot(p0, p1.cross(p2)) / 6.0;
+        }
+    }
+
+    signed_volume.abs()
+}
+
+/// Faces of the outer shell of a model, with their index: all but storey floors
+fn shell_faces(model: &Model) -> impl Iterator<Item = (usize, &Face)> {
+    model
+        .faces
+        .iter()
+        .enumerate()
+        .filter(|(_, face)| face.surface_type != SurfaceType::Floor)
+}
@@ -0,0 +1,356 @@
+use crate::error::{Error, Result};
+use crate::format::Encoding;
+use crate::model::{merge_models, Model};
+use crate::primitives::{Face, Vertex};
+use crate::triangulate::{newell_normal, triangulate_face};
+use crate::weld::VertexWelder;
+use crate::WELD_TOLERANCE;
+use cgmath::{InnerSpace, Point3, Vector3};
+use std::io::{Read, Write};
+use std::str::FromStr;
+
+const STL_HEADER_LENGTH: usize = 80;
+const STL_TRIANGLE_LENGTH: usize = 50;
+/// Title of the header of binary STL files
+const STL_TITLE: &str = "Converted LoD1.2 model";
+/// Keyword in the header of binary STL files before the offset of the coordinates
+const STL_OFFSET_KEYWORD: &str = "offset";
+
+/// Reader for ASCII and binary STL files.
+///
+/// The encoding is detected from the content. STL stores every triangle with
+/// its own corner coordinates, so corners closer than `WELD_TOLERANCE` are
+/// welded back into shared vertices. Every `solid` of an ASCII file becomes a
+/// model named after the solid; a binary file holds a single unnamed model,
+/// whose coordinates are moved by the `offset x y z` in its header, if any.
+pub struct StlReader<R: Read> {
+    reader: R,
+}
+
+impl<R: Read> StlReader<R> {
+    /// Create a reader over STL content
+    pub fn new(reader: R) -> Self {
+        StlReader { reader }
+    }
+
+    /// Read every solid as a separate model
+    pub fn read(mut self) -> Result<Vec<Model>> {
+        let mut bytes = Vec::new();
+        self.reader.read_to_end(&mut bytes).map_err(Error::Io)?;
+
+        // Binary files may also start with "solid", so trust the size first
+        if bytes.len() >= STL_HEADER_LENGTH + 4 {
+            let count = u32::from_le_bytes(
+                bytes[STL_HEADER_LENGTH..STL_HEADER_LENGTH + 4]
+                    .try_into()
+                    .expect("slice of 4 bytes"),
+            ) as usize;
+            if bytes.len() == STL_HEADER_LENGTH + 4 + count * STL_TRIANGLE_LENGTH {
+                let offset = header_offset(&bytes[..STL_HEADER_LENGTH]);
+                return Ok(vec![read_binary(
+                    &bytes[STL_HEADER_LENGTH + 4..],
+                    count,
+                    offset,
+                )]);
+            }
+        }
+
+        if bytes.trim_ascii_start().starts_with(b"solid") {
+            let text = String::from_utf8(bytes).map_err(|_| {
+                Error::Io(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    "ASCII STL is not valid UTF-8",
+                ))
+            })?;
+            return read_ascii(&text);
+        }
+
+        Err(Error::Io(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "Not an STL file: size doesn't match a binary STL and it doesn't start with 'solid'",
+        )))
+    }
+
+    /// Read the whole content as a single model, ignoring solid boundaries
+    pub fn read_merged(self) -> Result<Model> {
+        Ok(merge_models(self.read()?))
+    }
+}
+
+/// Accumulates welded triangles into a model
+struct MeshBuilder {
+    welder: VertexWelder,
+    faces: Vec<Face>,
+}
+
+impl MeshBuilder {
+    fn new() -> Self {
+        MeshBuilder {
+            welder: VertexWelder::new(WELD_TOLERANCE),
+            faces: Vec::new(),
+        }
+    }
+
+    fn add_triangle(&mut self, corners: [Point3<f64>; 3]) {
+        let ids: Vec<usize> = corners.iter().map(|&p| self.welder.weld(p)).collect();
+        // Triangles collapsed by welding have no area
+        if ids[0] != ids[1] && ids[1] != ids[2] && ids[2] != ids[0] {
+            self.faces.push(Face::new(ids));
+        }
+    }
+
+    fn build(self, id: Option<String>) -> Model {
+        let vertices = self
+            .welder
+            .into_points()
+            .into_iter()
+            .enumerate()
+            .map(|(id, point)| Vertex { point, id })
+            .collect();
+        let mut model = Model::new(vertices, self.faces);
+        model.id = id;
+        model
+    }
+}
+
+/// Whether the first bytes of an input, e.g. stdin, look like a binary STL:
+/// a header, a triangle count, and then either exactly that many triangles, or
+/// more bytes than `head` holds with a NUL byte among them, which text never has
+pub(crate) fn is_binary_head(head: &[u8]) -> bool {
+    let Some(count) = head.get(STL_HEADER_LENGTH..STL_HEADER_LENGTH + 4) else {
+        return false;
+    };
+    let count = u32::from_le_bytes(count.try_into().expect("4 bytes")) as usize;
+    let length = STL_HEADER_LENGTH + 4 + count * STL_TRIANGLE_LENGTH;
+    count > 0
+        && (head.len() == length
+            || (head.len() < length && head[STL_HEADER_LENGTH + 4..].contains(&0)))
+}
+
+/// Offset of the coordinates of a binary STL, written in its header by `StlWriter`
+fn header_offset(header: &[u8]) -> Vector3<f64> {
+    let text = String::from_utf8_lossy(header);
+    let mut words = text.split_whitespace();
+    if words.any(|word| word == STL_OFFSET_KEYWORD) {
+        let values: Vec<f64> = words.take(3).map_while(|word| word.parse().ok()).collect();
+        if let [x, y, z] = values[..] {
+            return Vector3::new(x, y, z);
+        }
+    }
+    Vector3::new(0.0, 0.0, 0.0)
+}
+
+fn read_binary(data: &[u8], count: usize, offset: Vector3<f64>) -> Model {
+    let mut builder = MeshBuilder::new();
+    for triangle in data.chunks_exact(STL_TRIANGLE_LENGTH).take(count) {
+        // Skip the 12-byte normal; it is recomputed from the corners when needed
+        let float = |i: usize| {
+            f32::from_le_bytes(triangle[i * 4..i * 4 + 4].try_into().expect("4 bytes")) as f64
+        };
+        let corner =
+            |c: usize| Point3::new(float(3 + c * 3), float(4 + c * 3), float(5 + c * 3)) + offset;
+        builder.add_triangle([corner(0), corner(1), corner(2)]);
+    }
+    builder.build(None)
+}
+
+fn read_ascii(text: &str) -> Result<Vec<Model>> {
+    let mut models = Vec::new();
+    let mut solid: Option<(Option<String>, MeshBuilder)> = None;
+    let mut corners: Vec<Point3<f64>> = Vec::new();
+
+    for (index, line) in text.lines().enumerate() {
+        let line_number = index + 1;
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        let Some(&keyword) = parts.first() else {
+            continue;
+        };
+
+        match keyword {
+            "solid" => {
+                let name = parts[1..].join(" ");
+                let name = if name.is_empty() { None } else { Some(name) };
+                solid = Some((name, MeshBuilder::new()));
+            }
+            "outer" => corners.clear(),
+            "vertex" => corners.push(parse_vertex(&parts, line_number)?),
+            "endloop" => {
+                let Some((_, builder)) = solid.as_mut() else {
+                    return Err(invalid_data("Facet outside of a solid", line_number));
+                };
+                if corners.len() != 3 {
+                    return Err(invalid_data("Facet must have 3 vertices", line_number));
+                }
+                builder.add_triangle([corners[0], corners[1], corners[2]]);
+            }
+            "endsolid" => {
+                if let Some((name, builder)) = solid.take() {
+                    models.push(builder.build(name));
+                }
+            }
+            _ => {
+                // facet normal, endfacet: nothing to keep
+                continue;
+            }
+        }
+    }
+
+    // Tolerate a missing endsolid at the end of the file
+    if let Some((name, builder)) = solid.take() {
+        models.push(builder.build(name));
+    }
+    Ok(models)
+}
+
+/// Parse a `vertex x y z` line
+fn parse_vertex(parts: &[&str], line_number: usize) -> Result<Point3<f64>> {
+    if parts.len() < 4 {
+        return Err(invalid_data(
+            "Not enough components for vertex",
+            line_number,
+        ));
+    }
+
+    let mut coordinates = [0.0; 3];
+    for (i, axis) in ["x", "y", "z"].iter().enumerate() {
+        coordinates[i] = f64::from_str(parts[i + 1]).map_err(|_| {
+            invalid_data(
+                &format!("Invalid {} coordinate: {}", axis, parts[i + 1]),
+                line_number,
+            )
+        })?;
+    }
+
+    Ok(Point3::new(coordinates[0], coordinates[1], coordinates[2]))
+}
+
+fn invalid_data(message: &str, line_number: usize) -> Error {
+    Error::Io(std::io::Error::new(
+        std::io::ErrorKind::InvalidData,
+        format!("{} at line {}", message, line_number),
+    ))
+}
+
+/// Writer for ASCII and binary STL files.
+///
+/// Faces are triangulated and written with their Newell normal. ASCII models
+/// are written as they come, one `solid` per model; binary STL stores the
+/// triangle count in its header, so triangles are buffered until `finish`.
+/// Binary STL only has 32-bit floats, so its coordinates are written relative
+/// to the origin of the first model, which the header records as
+/// `offset x y z`, to keep millimetres on georeferenced coordinates.
+pub struct StlWriter<W: Write> {
+    writer: W,
+    encoding: Encoding,
+    triangles: Vec<[[f32; 3]; 4]>, // normal and corners, binary only
+    offset: Option<Vector3<f64>>,  // offset of the binary coordinates
+}
+
+impl<W: Write> StlWriter<W> {
+    /// Create a writer with the given encoding
+    pub fn new(writer: W, encoding: Encoding) -> Self {
+        StlWriter {
+            writer,
+            encoding,
+            triangles: Vec::new(),
+            offset: None,
+        }
+    }
+
+    /// Append the triangles of a model
+    pub fn write_model(&mut self, model: &Model) -> Result<()> {
+        let name = model.id.as_deref().unwrap_or("building");
+        if self.encoding == Encoding::Ascii {
+            writeln!(self.writer, "solid {}", name).map_err(Error::Io)?;
+        }
+
+        let offset = *self.offset.get_or_insert(model.origin);
+        for face in &model.faces {
+            let normal = newell_normal(&face.vertex_ids, &model.vertices);
+            let normal = if normal.magnitude() > 0.0 {
+                normal.normalize()
+            } else {
+                Vector3::new(0.0, 0.0, 0.0)
+            };
+
+            for triangle in triangulate_face(face, &model.vertices) {
+                let corners = triangle.map(|id| model.world_point(model.vertices[id].point));
+                match self.encoding {
+                    Encoding::Ascii => self.write_ascii_facet(normal, corners)?,
+                    Encoding::Binary => {
+                        let local = corners.map(|corner| (corner - offset).map(|c| c as f32));
+                        self.triangles.push([
+                            [normal.x as f32, normal.y as f32, normal.z as f32],
+                            local[0].into(),
+                            local[1].into(),
+                            local[2].into(),
+                        ])
+                    }
+                }
+            }
+        }
+
+        if self.encoding == Encoding::Ascii {
+            writeln!(self.writer, "endsolid {}", name).map_err(Error::Io)?;
+        }
+        Ok(())
+    }
+
+    fn write_ascii_facet(&mut self, normal: Vector3<f64>, corners: [Point3<f64>; 3]) -> Result<()> {
+        writeln!(
+            self.writer,
+            "  facet normal {} {} {}",
+            normal.x, normal.y, normal.z
+        )
+        .map_err(Error::Io)?;
+        writeln!(self.writer, "    outer loop").map_err(Error::Io)?;
+        for corner in corners {
+            writeln!(
+                self.writer,
+                "      vertex {} {} {}",
+                corner.x, corner.y, corner.z
+            )
+            .map_err(Error::Io)?;
+        }
+        writeln!(self.writer, "    endloop").map_err(Error::Io)?;
+        writeln!(self.writer, "  endfacet").map_err(Error::Io)?;
+        Ok(())
+    }
+
+    /// Write the buffered binary triangles, flush and return the underlying writer
+    pub fn finish(mut self) -> Result<W> {
+        if self.encoding == Encoding::Binary {
+            let mut header = [b' '; STL_HEADER_LENGTH];
+            let offset = self.offset.unwrap_or(Vector3::new(0.0, 0.0, 0.0));
+            let title = format!(
+                "{} {} {} {} {}",
+                STL_TITLE, STL_OFFSET_KEYWORD, offset.x, offset.y, offset.z
+            );
+            if title.len() > STL_HEADER_LENGTH {
+                return Err(Error::Io(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!("STL offset {:?} doesn't fit in the header", offset),
+                )));
+            }
+            header[..title.len()].copy_from_slice(title.as_bytes());
+            self.writer.write_all(&header).map_err(Error::Io)?;
+            self.writer
+                .write_all(&(self.triangles.len() as u32).to_le_bytes())
+                .map_err(Error::Io)?;
+
+            for triangle in &self.triangles {
+                for value in triangle.iter().flatten() {
+                    self.writer
+                        .write_all(&value.to_le_bytes())
+                        .map_err(Error::Io)?;
+                }
+                self.writer
+                    .write_all(&0u16.to_le_bytes()) // attribute byte count
+                    .map_err(Error::Io)?;
+            }
+        }
+
+        self.writer.flush().map_err(Error::Io)?;
+        Ok(self.writer)
+    }
+}
@@ -0,0 +1,368 @@
+use crate::error::{Error, Result};
+use crate::geodesy::{enu_axes, reproject, Crs, RD_CRS};
+use crate::gltf::GltfBuilder;
+use crate::model::Model;
+use crate::{TILE_MAX_DEPTH, TILE_MAX_FEATURES};
+use cgmath::{InnerSpace, Point3, Vector3};
+use serde_json::{json, Value};
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+const B3DM_MAGIC: &[u8; 4] = b"b3dm";
+const B3DM_VERSION: u32 = 1;
+const B3DM_HEADER_LENGTH: usize = 28;
+
+/// Options controlling the tile hierarchy of a 3D Tiles tileset
+#[derive(Debug, Clone)]
+pub struct TilesetOptions {
+    /// Maximum number of buildings in a tile before it is split into quadrants
+    pub max_features_per_tile: usize,
+    /// Maximum depth of the quadtree
+    pub max_depth: usize,
+}
+
+impl Default for TilesetOptions {
+    fn default() -> Self {
+        TilesetOptions {
+            max_features_per_tile: TILE_MAX_FEATURES,
+            max_depth: TILE_MAX_DEPTH,
+        }
+    }
+}
+
+/// Footprint bounding box and height range of a building, in RD coordinates
+#[derive(Debug, Clone, Copy)]
+struct Bounds {
+    min: Point3<f64>,
+    max: Point3<f64>,
+}
+
+impl Bounds {
+    fn of_model(model: &Model) -> Option<Self> {
+        let mut points = model
+            .vertices
+            .iter()
+            .map(|vertex| model.world_point(vertex.point));
+        let first = points.next()?;
+        Some(points.fold(
+            Bounds {
+                min: first,
+                max: first,
+            },
+            |bounds, p| bounds.extend(&Bounds { min: p, max: p }),
+        ))
+    }
+
+    fn extend(&self, other: &Bounds) -> Bounds {
+        Bounds {
+            min: Point3::new(
+                self.min.x.min(other.min.x),
+                self.min.y.min(other.min.y),
+                self.min.z.min(other.min.z),
+            ),
+            max: Point3::new(
+                self.max.x.max(other.max.x),
+                self.max.y.max(other.max.y),
+                self.max.z.max(other.max.z),
+            ),
+        }
+    }
+
+    fn center_xy(&self) -> (f64, f64) {
+        (
+            (self.min.x + self.max.x) * 0.5,
+            (self.min.y + self.max.y) * 0.5,
+        )
+    }
+
+    /// Length of the footprint diagonal in metres
+    fn diagonal(&self) -> f64 {
+        (self.max.x - self.min.x).hypot(self.max.y - self.min.y)
+    }
+
+    /// 3D Tiles region: west, south, east, north in radians, then ellipsoidal heights
+    fn region(&self) -> [f64; 6] {
+        // RD heights don't move the horizontal position, so the lowest and
+        // highest corners also give the ellipsoidal heights
+        let corners = [
+            Point3::new(self.min.x, self.min.y, self.min.z),
+            Point3::new(self.max.x, self.min.y, self.min.z),
+            Point3::new(self.min.x, self.max.y, self.max.z),
+            Point3::new(self.max.x, self.max.y, self.max.z),
+        ]
+        .map(|corner| reproject(corner, Crs::Rd, Crs::Geographic));
+        let (mut west, mut south) = (corners[0].x, corners[0].y);
+        let (mut east, mut north) = (corners[0].x, corners[0].y);
+        for corner in &corners {
+            west = west.min(corner.x);
+            south = south.min(corner.y);
+            east = east.max(corner.x);
+            north = north.max(corner.y);
+        }
+        [
+            west.to_radians(),
+            south.to_radians(),
+            east.to_radians(),
+            north.to_radians(),
+            corners[0].z,
+            corners[3].z,
+        ]
+    }
+}
+
+/// A node of the quadtree; only leaves hold buildings
+struct TileNode {
+    name: String,
+    bounds: Bounds,          // tight bounds of the buildings below the node
+    buildings: Vec<usize>,   // indices into the model list, leaves only
+    children: Vec<TileNode>, // non-empty quadrants
+}
+
+/// Write converted buildings as an OGC 3D Tiles 1.0 tileset in `output_dir`.
+///
+/// The buildings are split into a quadtree by the centre of their footprint
+/// bounding box, until a tile holds at most `max_features_per_tile` buildings.
+/// Each leaf tile is written as a Batched 3D Model (`tiles/<name>.b3dm`) whose
+/// batch table holds the ID, ground height, roof height and height of every
+/// building. Models are expected in RD New (EPSG:28992) with NAP heights, and
+/// models tagged with another CRS are rejected; they are converted to ECEF,
+/// and every tile is stored in a local east-north-up frame placed by its
+/// `transform`.
+pub fn write_tileset(models: &[Model], output_dir: &Path, options: &TilesetOptions) -> Result<()> {
+    if let Some(crs) = models
+        .iter()
+        .filter_map(|model| model.crs)
+        .find(|crs| !RD_CRS.contains(crs))
+    {
+        return Err(Error::Io(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!(
+                "3D Tiles output needs RD New coordinates (EPSG:28992 or EPSG:7415), got EPSG:{}",
+                crs
+            ),
+        )));
+    }
+
+    let tiles_dir = output_dir.join("tiles");
+    fs::create_dir_all(&tiles_dir).map_err(|e| {
+        Error::Io(std::io::Error::new(
+            e.kind(),
+            format!("Failed to create directory {}: {}", tiles_dir.display(), e),
+        ))
+    })?;
+
+    let bounds: Vec<(usize, Bounds)> = models
+        .iter()
+        .enumerate()
+        .filter_map(|(index, model)| Some((index, Bounds::of_model(model)?)))
+        .collect();
+    if bounds.is_empty() {
+        return Err(Error::Io(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "No building to write to the tileset",
+        )));
+    }
+
+    let root = build_quadtree("root".to_string(), &bounds, options, 0);
+    let root_json = write_tile(&root, models, &tiles_dir)?;
+    let tileset = json!({
+        "asset": { "version": "1.0", "generator": "lodconv", "gltfUpAxis": "Y" },
+        "geometricError": root.bounds.diagonal(),
+        "root": root_json,
+    });
+
+    let path = output_dir.join("tileset.json");
+    let file = File::create(&path).map_err(|e| {
+        Error::Io(std::io::Error::new(
+            e.kind(),
+            format!("Failed to create file {}: {}", path.display(), e),
+        ))
+    })?;
+    let mut writer = BufWriter::new(file);
+    serde_json::to_writer_pretty(&mut writer, &tileset)?;
+    writer.flush().map_err(Error::Io)?;
+    Ok(())
+}
+
+/// Split buildings into quadrants of their bounding box until tiles are small enough
+fn build_quadtree(
+    name: String,
+    buildings: &[(usize, Bounds)],
+    options: &TilesetOptions,
+    depth: usize,
+) -> TileNode {
+    let bounds = buildings
+        .iter()
+        .skip(1)
+        .fold(buildings[0].1, |bounds, (_, other)| bounds.extend(other));
+
+    if buildings.len() <= options.max_features_per_tile.max(1) || depth >= options.max_depth {
+        return TileNode {
+            name,
+            bounds,
+            buildings: buildings.iter().map(|(index, _)| *index).collect(),
+            children: Vec::new(),
+        };
+    }
+
+    let (mid_x, mid_y) = bounds.center_xy();
+    let mut quadrants: [Vec<(usize, Bounds)>; 4] = Default::default();
+    for building in buildings {
+        let (x, y) = building.1.center_xy();
+        let quadrant = usize::from(x >= mid_x) + 2 * usize::from(y >= mid_y);
+        quadrants[quadrant].push(*building);
+    }
+
+    let children = quadrants
+        .iter()
+        .enumerate()
+        .filter(|(_, quadrant)| !quadrant.is_empty())
+        .map(|(i, quadrant)| {
+            build_quadtree(format!("{}_{}", name, i), quadrant, options, depth + 1)
+        })
+        .collect();
+
+    TileNode {
+        name,
+        bounds,
+        buildings: Vec::new(),
+        children,
+    }
+}
+
+/// Write the content of a tile and its children, returning the tile JSON
+fn write_tile(node: &TileNode, models: &[Model], tiles_dir: &Path) -> Result<Value> {
+    let mut tile = json!({
+        "boundingVolume": { "region": node.bounds.region() },
+        "refine": "ADD",
+    });
+
+    if node.children.is_empty() {
+        let (center_x, center_y) = node.bounds.center_xy();
+        let origin = Point3::new(center_x, center_y, node.bounds.min.z);
+        let path = tiles_dir.join(format!("{}.b3dm", node.name));
+        let file = File::create(&path).map_err(|e| {
+            Error::Io(std::io::Error::new(
+                e.kind(),
+                format!("Failed to create file {}: {}", path.display(), e),
+            ))
+        })?;
+        let mut writer = BufWriter::new(file);
+        let buildings: Vec<&Model> = node.buildings.iter().map(|&i| &models[i]).collect();
+        writer
+            .write_all(&b3dm(&buildings, origin)?)
+            .map_err(Error::Io)?;
+        writer.flush().map_err(Error::Io)?;
+
+        tile["geometricError"] = json!(0.0);
+        tile["transform"] = json!(enu_to_ecef_matrix(origin));
+        tile["content"] = json!({ "uri": format!("tiles/{}.b3dm", node.name) });
+    } else {
+        let children = node
+            .children
+            .iter()
+            .map(|child| write_tile(child, models, tiles_dir))
+            .collect::<Result<Vec<_>>>()?;
+        tile["geometricError"] = json!(node.bounds.diagonal());
+        tile["children"] = json!(children);
+    }
+
+    Ok(tile)
+}
+
+/// Column-major 4x4 matrix from the east-north-up frame at an RD point to ECEF
+fn enu_to_ecef_matrix(origin: Point3<f64>) -> [f64; 16] {
+    let geographic = reproject(origin, Crs::Rd, Crs::Geographic);
+    let [east, north, up] = enu_axes(geographic.y, geographic.x);
+    let o = reproject(origin, Crs::Rd, Crs::Geocentric);
+    [
+        east.x, east.y, east.z, 0.0, //
+        north.x, north.y, north.z, 0.0, //
+        up.x, up.y, up.z, 0.0, //
+        o.x, o.y, o.z, 1.0,
+    ]
+}
+
+/// Pack buildings as a Batched 3D Model in the east-north-up frame at `origin`
+fn b3dm(buildings: &[&Model], origin: Point3<f64>) -> Result<Vec<u8>> {
+    let geographic = reproject(origin, Crs::Rd, Crs::Geographic);
+    let [east, north, up] = enu_axes(geographic.y, geographic.x);
+    let origin_ecef = reproject(origin, Crs::Rd, Crs::Geocentric);
+    let to_enu = |point: Point3<f64>| -> Vector3<f64> {
+        let d = reproject(point, Crs::Rd, Crs::Geocentric) - origin_ecef;
+        Vector3::new(d.dot(east), d.dot(north), d.dot(up))
+    };
+
+    let mut builder = GltfBuilder::default();
+    let mut nodes = Vec::new();
+    let mut ids = Vec::new();
+    let mut ground_z = Vec::new();
+    let mut roof_z = Vec::new();
+    let mut heights = Vec::new();
+
+    for model in buildings {
+        let batch_id = ids.len() as u32;
+        let Some(mesh) = builder.add_mesh(model, to_enu, Some(batch_id)) else {
+            continue;
+        };
+        let mut node = json!({ "mesh": mesh });
+        if let Some(id) = &model.id {
+            node["name"] = json!(id);
+        }
+        nodes.push(node);
+
+        // LoD1.2 blocks span from the ground to the roof height
+        let bounds = Bounds::of_model(model).expect("a model with a mesh has vertices");
+        ids.push(json!(model.id));
+        ground_z.push(bounds.min.z);
+        roof_z.push(bounds.max.z);
+        heights.push(bounds.max.z - bounds.min.z);
+    }
+
+    let node_indices: Vec<usize> = (0..nodes.len()).collect();
+    let glb = builder
+        .into_document(nodes, json!({ "nodes": node_indices }))
+        .to_glb()?;
+
+    let feature_table = json!({ "BATCH_LENGTH": ids.len() });
+    let batch_table = json!({
+        "id": ids,
+        "ground_z": ground_z,
+        "roof_z": roof_z,
+        "height": heights,
+    });
+
+    // Both tables end on 8-byte boundaries so that the GLB is aligned
+    let mut feature_table = serde_json::to_vec(&feature_table)?;
+    pad_to_8(&mut feature_table, B3DM_HEADER_LENGTH, b' ');
+    let mut batch_table = serde_json::to_vec(&batch_table)?;
+    pad_to_8(
+        &mut batch_table,
+        B3DM_HEADER_LENGTH + feature_table.len(),
+        b' ',
+    );
+    let mut glb = glb;
+    pad_to_8(&mut glb, 0, 0);
+
+    let total = B3DM_HEADER_LENGTH + feature_table.len() + batch_table.len() + glb.len();
+    let mut bytes = Vec::with_capacity(total);
+    bytes.extend_from_slice(B3DM_MAGIC);
+    bytes.extend_from_slice(&B3DM_VERSION.to_le_bytes());
+    bytes.extend_from_slice(&(total as u32).to_le_bytes());
+    bytes.extend_from_slice(&(feature_table.len() as u32).to_le_bytes());
+    bytes.extend_from_slice(&0u32.to_le_bytes()); // feature table binary
+    bytes.extend_from_slice(&(batch_table.len() as u32).to_le_bytes());
+    bytes.extend_from_slice(&0u32.to_le_bytes()); // batch table binary
+    bytes.extend_from_slice(&feature_table);
+    bytes.extend_from_slice(&batch_table);
+    bytes.extend_from_slice(&glb);
+    Ok(bytes)
+}
+
+/// Pad `bytes` so that it ends on an 8-byte boundary when placed at `offset`
+fn pad_to_8(bytes: &mut Vec<u8>, offset: usize, padding: u8) {
+    while !(offset + bytes.len()).is_multiple_of(8) {
+        bytes.push(padding);
+    }
+}
@@ -0,0 +1,169 @@
+use crate::error::{Error, Result};
+use crate::geodesy::{reproject, Crs};
+use crate::model::Model;
+use cgmath::{EuclideanSpace, Matrix, Matrix3, Point3, SquareMatrix, Vector3};
+
+/// Prefix of the EPSG coordinate reference system URIs written to CityJSON
+const EPSG_URI: &str = "https://www.opengis.net/def/crs/EPSG/0/";
+/// Prefix of the EPSG coordinate reference system URNs written to GeoJSON
+const EPSG_URN: &str = "urn:ogc:def:crs:EPSG::";
+
+/// An affine transform of world coordinates: `matrix * point + translation`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AffineTransform {
+    pub matrix: Matrix3<f64>,
+    pub translation: Vector3<f64>,
+}
+
+impl AffineTransform {
+    /// A translation by `offset`
+    pub fn translation(offset: Vector3<f64>) -> Self {
+        AffineTransform {
+            matrix: Matrix3::identity(),
+            translation: offset,
+        }
+    }
+
+    /// Transform from 3 values, a translation, or 12 values, the rows of the
+    /// 3×4 matrix `[A | t]`
+    pub fn from_values(values: &[f64]) -> Result<Self> {
+        match *values {
+            [x, y, z] => Ok(AffineTransform::translation(Vector3::new(x, y, z))),
+            [a, b, c, tx, d, e, f, ty, g, h, i, tz] => Ok(AffineTransform {
+                // cgmath matrices are built column by column
+                matrix: Matrix3::new(a, b, c, d, e, f, g, h, i).transpose(),
+                translation: Vector3::new(tx, ty, tz),
+            }),
+            _ => Err(Error::Io(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!(
+                    "An affine transform needs 3 or 12 values, got {}",
+                    values.len()
+                ),
+            ))),
+        }
+    }
+
+    /// Transform a point
+    pub fn apply(&self, point: Point3<f64>) -> Point3<f64> {
+        Point3::from_vec(self.matrix * point.to_vec() + self.translation)
+    }
+}
+
+/// Move the local origin of a model to `origin`, keeping its world coordinates
+pub fn set_origin(model: &mut Model, origin: Vector3<f64>) {
+    let shift = model.origin - origin;
+    for vertex in &mut model.vertices {
+        vertex.point += shift;
+    }
+    model.origin = origin;
+}
+
+/// Move the local origin of a model to the centre of its horizontal extent,
+/// rounded to metres, so the vertices keep their precision as `f32`.
+///
+/// Only x and y are recentred; z stays absolute, so heights such as the
+/// ground and roof z of the conversion keep their meaning.
+pub fn recentre(model: &mut Model) {
+    let (mut min, mut max) = ([f64::MAX; 2], [f64::MIN; 2]);
+    for vertex in &model.vertices {
+        let p = model.origin + vertex.point.to_vec();
+        min = [min[0].min(p.x), min[1].min(p.y)];
+        max = [max[0].max(p.x), max[1].max(p.y)];
+    }
+    if model.vertices.is_empty() {
+        return;
+    }
+    let centre = Vector3::new(
+        ((min[0] + max[0]) / 2.0).round(),
+        ((min[1] + max[1]) / 2.0).round(),
+        0.0,
+    );
+    set_origin(model, centre);
+}
+
+/// Apply an affine transform to the world coordinates of a model.
+///
+/// The transform is applied to the local coordinates and the origin apart, so
+/// large world coordinates lose no precision. Faces are reversed by
+/// transforms that mirror, so closed models keep facing outwards.
+pub fn transform_model(model: &mut Model, transform: &AffineTransform) {
+    let origin = transform.apply(Point3::from_vec(model.origin)).to_vec();
+    // The origin stays horizontal; its z moves into the vertices
+    let lift = Vector3::new(0.0, 0.0, origin.z);
+    for vertex in &mut model.vertices {
+        vertex.point = Point3::from_vec(transform.matrix * vertex.point.to_vec() + lift);
+    }
+    model.origin = Vector3::new(origin.x, origin.y, 0.0);
+
+    if transform.matrix.determinant() < 0.0 {
+        for face in &mut model.faces {
+            face.vertex_ids.reverse();
+        }
+    }
+}
+
+/// Reproject the world coordinates of a model from its CRS to the EPSG code
+/// `target`, see `reproject`, and recentre it.
+///
+/// Fails if the model has no CRS or either CRS is not supported.
+pub fn reproject_model(model: &mut Model, target: u32) -> Result<()> {
+    let source = model.crs.ok_or_else(|| {
+        Error::Io(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!(
+                "Building {} has no coordinate reference system to reproject from",
+                model.id.as_deref().unwrap_or("<unnamed>")
+            ),
+        ))
+    })?;
+    let (from, to) = (supported_crs(source)?, supported_crs(target)?);
+
+    let origin = model.origin;
+    for vertex in &mut model.vertices {
+        vertex.point = reproject(vertex.point + origin, from, to);
+    }
+    model.origin = Vector3::new(0.0, 0.0, 0.0);
+    model.crs = Some(target);
+    recentre(model);
+    Ok(())
+}
+
+/// The reference system of an EPSG code, or an error if it can't be reprojected
+pub fn supported_crs(code: u32) -> Result<Crs> {
+    Crs::from_epsg(code).ok_or_else(|| {
+        Error::Io(std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            format!(
+                "Reprojection of EPSG:{} is not supported, expected RD New (28992, 7415), \
+                 WGS84 or ETRS89 (4326, 4979, 4258, 4937), ECEF (4978, 4936) or UTM (326zz, 327zz, 258zz)",
+                code
+            ),
+        ))
+    })
+}
+
+/// EPSG code of a coordinate reference system written as `EPSG:7415`, a plain
+/// code, an OGC URN such as `urn:ogc:def:crs:EPSG::7415` or an OGC URI such as
+/// `https://www.opengis.net/def/crs/EPSG/0/7415`
+pub fn parse_epsg(text: &str) -> Option<u32> {
+    let text = text.trim();
+    if let Ok(code) = text.parse() {
+        return Some(code);
+    }
+    let upper = text.to_ascii_uppercase();
+    if !upper.contains("EPSG") {
+        return None;
+    }
+    upper.rsplit([':', '/']).next()?.parse().ok()
+}
+
+/// OGC URI of an EPSG coordinate reference system, as used by CityJSON 2.0
+pub fn epsg_uri(code: u32) -> String {
+    format!("{}{}", EPSG_URI, code)
+}
+
+/// OGC URN of an EPSG coordinate reference system, as used by GeoJSON `crs` members
+pub fn epsg_urn(code: u32) -> String {
+    format!("{}{}", EPSG_URN, code)
+}
@@ -0,0 +1,346 @@
+use crate::primitives::{Face, Vertex};
+use crate::EPSILON;
+use cgmath::{InnerSpace, Vector3};
+use std::collections::{HashMap, HashSet};
+
+/// Triangulate a face by ear clipping.
+///
+/// The face is projected onto the coordinate plane that best preserves its
+/// shape, so non-convex planar polygons are handled correctly. The returned
+/// triangles reference model vertex IDs and keep the winding of the face.
+pub fn triangulate_face(face: &Face, vertices: &[Vertex]) -> Vec<[usize; 3]> {
+    triangulate_ring(&face.vertex_ids, vertices)
+}
+
+/// Triangulate a ring of vertex IDs by ear clipping; see `triangulate_face`
+pub fn triangulate_ring(ring: &[usize], vertices: &[Vertex]) -> Vec<[usize; 3]> {
+    if ring.len() < 3 {
+        return Vec::new();
+    }
+    if ring.len() == 3 {
+        return vec![[ring[0], ring[1], ring[2]]];
+    }
+
+    let normal = newell_normal(ring, vertices);
+    if normal.magnitude() < EPSILON {
+        return fan(ring); // degenerate polygon, nothing better to do
+    }
+
+    // Drop the axis along which the polygon is most flat
+    let [ax, ay] = projection_axes(normal);
+    // Relative coordinates keep georeferenced polygons precise
+    let origin = vertices[ring[0]].point;
+    let points: Vec<[f64; 2]> = ring
+        .iter()
+        .map(|&id| {
+            let p = vertices[id].point - origin;
+            [p[ax], p[ay]]
+        })
+        .collect();
+
+    // Ear clipping below assumes a counter-clockwise ring
+    let reversed = signed_area(&points) < 0.0;
+    let mut order: Vec<usize> = (0..ring.len()).collect();
+    if reversed {
+        order.reverse();
+    }
+
+    let triangles = ear_clip(&points, order);
+    triangles
+        .into_iter()
+        .map(|[a, b, c]| {
+            if reversed {
+                [ring[a], ring[c], ring[b]]
+            } else {
+                [ring[a], ring[b], ring[c]]
+            }
+        })
+        .collect()
+}
+
+/// Triangulate a polygon with holes lying on the XY plane, e.g. a footprint.
+///
+/// The holes are joined to the exterior ring by bridge edges, see
+/// `bridge_holes`, and the resulting ring is triangulated by ear clipping.
+/// Triangles face up.
+pub fn triangulate_polygon(
+    exterior: &[usize],
+    holes: &[Vec<usize>],
+    vertices: &[Vertex],
+) -> Vec<[usize; 3]> {
+    if exterior.len() < 3 {
+        return Vec::new();
+    }
+    triangulate_ring(&bridge_holes(exterior, holes, [0, 1], vertices), vertices)
+}
+
+/// Join the holes of a planar polygon to its exterior ring by bridge edges.
+///
+/// The polygon is projected onto the coordinate `axes`, e.g. `[0, 1]` for the
+/// XY plane. The result is a single ring, counter-clockwise in that
+/// projection, that runs from the exterior along a bridge around every hole
+/// and back along the same bridge.
+pub fn bridge_holes(
+    exterior: &[usize],
+    holes: &[Vec<usize>],
+    axes: [usize; 2],
+    vertices: &[Vertex],
+) -> Vec<usize> {
+    if exterior.is_empty() {
+        return Vec::new();
+    }
+    let origin = vertices[exterior[0]].point;
+    let xy = |id: usize| {
+        let p = vertices[id].point - origin;
+        [p[axes[0]], p[axes[1]]]
+    };
+    let ring_area =
+        |ring: &[usize]| signed_area(&ring.iter().map(|&id| xy(id)).collect::<Vec<_>>());
+
+    // The exterior runs counter-clockwise and holes clockwise, so the bridged ring stays simple
+    let mut ring = exterior.to_vec();
+    if ring_area(&ring) < 0.0 {
+        ring.reverse();
+    }
+    let mut holes: Vec<Vec<usize>> = holes
+        .iter()
+        .filter(|hole| hole.len() >= 3)
+        .map(|hole| {
+            let mut hole = hole.clone();
+            if ring_area(&hole) > 0.0 {
+                hole.reverse();
+            }
+            hole
+        })
+        .collect();
+    // Bridge the rightmost hole first, as in Eberly's method
+    let max_x = |hole: &Vec<usize>| hole.iter().map(|&id| xy(id)[0]).fold(f64::MIN, f64::max);
+    holes.sort_by(|a, b| max_x(b).total_cmp(&max_x(a)));
+
+    for k in 0..holes.len() {
+        let hole = &holes[k];
+        let m = (0..hole.len())
+            .max_by(|&a, &b| xy(hole[a])[0].total_cmp(&xy(hole[b])[0]))
+            .expect("hole has vertices");
+        let from = xy(hole[m]);
+
+        // Connect to the nearest ring vertex whose bridge crosses no edge
+        let mut candidates: Vec<usize> = (0..ring.len()).collect();
+        let distance = |i: usize| {
+            let to = xy(ring[i]);
+            (to[0] - from[0]).hypot(to[1] - from[1])
+        };
+        candidates.sort_by(|&a, &b| distance(a).total_cmp(&distance(b)));
+        let obstacles = std::iter::once(&ring).chain(&holes[k..]);
+        let edges: Vec<([f64; 2], [f64; 2])> = obstacles
+            .flat_map(|r| (0..r.len()).map(move |i| (r[i], r[(i + 1) % r.len()])))
+            .map(|(a, b)| (xy(a), xy(b)))
+            .collect();
+        let bridge = candidates
+            .iter()
+            .copied()
+            .find(|&i| {
+                let to = xy(ring[i]);
+                !edges.iter().any(|&(a, b)| crosses(from, to, a, b))
+            })
+            .unwrap_or(candidates[0]);
+
+        // ring[..=bridge], the hole from m around back to m, then ring[bridge..]
+        let mut bridged = ring[..=bridge].to_vec();
+        bridged.extend(hole[m..].iter().chain(&hole[..=m]));
+        bridged.extend(&ring[bridge..]);
+        ring = bridged;
+    }
+
+    ring
+}
+
+/// Split a ring made by `bridge_holes` back into its exterior and its holes.
+///
+/// Bridges are the edges the ring runs along both ways; the rest of the ring
+/// falls apart into the exterior, first, and the holes, oriented against it. A ring without bridges is returned as is, and
+/// so is one whose parts aren't rings of at least three vertices.
+pub fn split_keyhole(ring: &[usize]) -> Vec<Vec<usize>> {
+    let n = ring.len();
+    let edge = |k: usize| (ring[k], ring[(k + 1) % n]);
+    let edges: HashSet<(usize, usize)> = (0..n).map(edge).collect();
+    let bridge: Vec<bool> = (0..n)
+        .map(|k| {
+            let (a, b) = edge(k);
+            a == b || edges.contains(&(b, a))
+        })
+        .collect();
+    if !bridge.iter().any(|&bridge| bridge) {
+        return vec![ring.to_vec()];
+    }
+
+    // Positions of the edges that are no bridges, by their first vertex
+    let mut outgoing: HashMap<usize, Vec<usize>> = HashMap::new();
+    for k in (0..n).filter(|&k| !bridge[k]) {
+        outgoing.entry(ring[k]).or_default().push(k);
+    }
+    let mut used = vec![false; n];
+    let mut rings = Vec::new();
+    for start in 0..n {
+        if bridge[start] || used[start] {
+            continue;
+        }
+        let mut part = Vec::new();
+        let mut k = start;
+        loop {
+            used[k] = true;
+            part.push(ring[k]);
+            let next = (k + 1) % n;
+            if ring[next] == ring[start] {
+                break;
+            }
+            // Follow the ring where it goes on, or else leave the vertex along another edge
+            let following = if !bridge[next] && !used[next] {
+                Some(next)
+            } else {
+                outgoing
+                    .get(&ring[next])
+                    .and_then(|edges| edges.iter().copied().find(|&j| !used[j]))
+            };
+            match following {
+                Some(j) => k = j,
+                None => return vec![ring.to_vec()],
+            }
+        }
+        if part.len() < 3 {
+            return vec![ring.to_vec()];
+        }
+        rings.push(part);
+    }
+    // The exterior holds the first vertex, where `bridge_holes` starts
+    rings.sort_by_key(|part| !part.contains(&ring[0]));
+    rings
+}
+
+/// Coordinate axes of the plane onto which a polygon with this normal
+/// projects with the least distortion, keeping its orientation
+pub fn projection_axes(normal: Vector3<f64>) -> [usize; 2] {
+    if normal.z.abs() >= normal.x.abs() && normal.z.abs() >= normal.y.abs() {
+        [0, 1]
+    } else if normal.x.abs() >= normal.y.abs() {
+        [1, 2]
+    } else {
+        [2, 0]
+    }
+}
+
+/// Whether segments pq and ab cross at a point inside both of them
+fn crosses(p: [f64; 2], q: [f64; 2], a: [f64; 2], b: [f64; 2]) -> bool {
+    if p == a || p == b || q == a || q == b {
+        return false; // segments sharing an endpoint only touch
+    }
+    let d1 = cross(p, q, a);
+    let d2 = cross(p, q, b);
+    let d3 = cross(a, b, p);
+    let d4 = cross(a, b, q);
+    ((d1 > 0.0 && d2 < 0.0) || (d1 < 0.0 && d2 > 0.0))
+        && ((d3 > 0.0 && d4 < 0.0) || (d3 < 0.0 && d4 > 0.0))
+}
+
+/// Normal of a polygon by Newell's method, not normalized
+pub fn newell_normal(ring: &[usize], vertices: &[Vertex]) -> Vector3<f64> {
+    let mut normal = Vector3::new(0.0, 0.0, 0.0);
+    if ring.is_empty() {
+        return normal;
+    }
+
+    // Relative coordinates keep georeferenced polygons precise
+    let origin = vertices[ring[0]].point;
+    for i in 0..ring.len() {
+        let p1 = vertices[ring[i]].point - origin;
+        let p2 = vertices[ring[(i + 1) % ring.len()]].point - origin;
+        normal += p1.cross(p2);
+    }
+    normal
+}
+
+/// Fan triangulation from the first vertex
+fn fan(ring: &[usize]) -> Vec<[usize; 3]> {
+    (1..ring.len() - 1)
+        .map(|i| [ring[0], ring[i], ring[i + 1]])
+        .collect()
+}
+
+/// Twice the signed area of a 2D polygon, positive when counter-clockwise
+fn signed_area(points: &[[f64; 2]]) -> f64 {
+    let mut area = 0.0;
+    for i in 0..points.len() {
+        let p1 = points[i];
+        let p2 = points[(i + 1) % points.len()];
+        area += p1[0] * p2[1] - p2[0] * p1[1];
+    }
+    area
+}
+
+/// Twice the signed area of triangle abc, positive when counter-clockwise
+fn cross(a: [f64; 2], b: [f64; 2], c: [f64; 2]) -> f64 {
+    (b[0] - a[0]) * (c[1] - a[1]) - (b[1] - a[1]) * (c[0] - a[0])
+}
+
+/// Whether p lies inside or on the counter-clockwise triangle abc
+fn in_triangle(p: [f64; 2], a: [f64; 2], b: [f64; 2], c: [f64; 2]) -> bool {
+    cross(a, b, p) >= 0.0 && cross(b, c, p) >= 0.0 && cross(c, a, p) >= 0.0
+}
+
+/// Relative tolerance on the sine of the turn angle below which a vertex is collinear
+const COLLINEAR_TOLERANCE: f64 = 1e-10;
+
+/// Clip ears off the counter-clockwise polygon `order` (indices into `points`)
+fn ear_clip(points: &[[f64; 2]], mut order: Vec<usize>) -> Vec<[usize; 3]> {
+    let mut triangles = Vec::with_capacity(order.len().saturating_sub(2));
+    let mut i = 0;
+    // Number of consecutive vertices tested without clipping an ear
+    let mut attempts = 0;
+
+    while order.len() > 3 {
+        let n = order.len();
+        i %= n;
+        let prev = order[(i + n - 1) % n];
+        let curr = order[i];
+        let next = order[(i + 1) % n];
+        let (a, b, c) = (points[prev], points[curr], points[next]);
+        let turn = cross(a, b, c);
+        let scale = (b[0] - a[0]).hypot(b[1] - a[1]) * (c[0] - b[0]).hypot(c[1] - b[1]);
+
+        let is_ear = if turn.abs() <= COLLINEAR_TOLERANCE * scale {
+            // Collinear vertex: drop it without emitting a degenerate triangle
+            order.remove(i);
+            attempts = 0;
+            continue;
+        } else if turn < 0.0 {
+            false // reflex vertex
+        } else {
+            // No other vertex may lie inside the candidate ear
+            !order.iter().any(|&other| {
+                other != prev
+                    && other != curr
+                    && other != next
+                    && points[other] != a
+                    && points[other] != b
+                    && points[other] != c
+                    && in_triangle(points[other], a, b, c)
+            })
+        };
+
+        if is_ear || attempts >= n {
+            // Self-intersecting input may have no ear left; clip anyway to terminate
+            triangles.push([prev, curr, next]);
+            order.remove(i);
+            i = (i + n - 2) % (n - 1);
+            attempts = 0;
+        } else {
+            i = (i + 1) % n;
+            attempts += 1;
+        }
+    }
+
+    if order.len() == 3 {
+        triangles.push([order[0], order[1], order[2]]);
+    }
+    triangles
+}
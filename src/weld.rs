@@ -0,0 +1,119 @@
+use crate::geodesy::units_per_metre;
+use crate::model::Model;
+use crate::EPSILON;
+use cgmath::Point3;
+use std::collections::HashMap;
+
+/// Merges nearly coincident points into shared indices.
+///
+/// Points are bucketed into a hash grid whose cell size equals the tolerance,
+/// so a lookup only has to inspect the 27 cells around the query point instead
+/// of every point seen so far. Indices are handed out in insertion order, which
+/// keeps the output deterministic for a deterministic input order.
+#[derive(Debug, Clone)]
+pub struct VertexWelder {
+    tolerance: [f64; 3],
+    cells: HashMap<(i64, i64, i64), Vec<usize>>,
+    points: Vec<Point3<f64>>,
+}
+
+impl VertexWelder {
+    /// Create a welder that merges points closer than `tolerance` on every axis
+    pub fn new(tolerance: f64) -> Self {
+        // A zero tolerance would make the grid cells degenerate
+        VertexWelder::with_axis_tolerances([tolerance.max(EPSILON); 3])
+    }
+
+    /// Create a welder with a tolerance per axis, e.g. for x and y in degrees
+    /// and z in metres
+    pub fn with_axis_tolerances(tolerance: [f64; 3]) -> Self {
+        VertexWelder {
+            tolerance,
+            cells: HashMap::new(),
+            points: Vec::new(),
+        }
+    }
+
+    /// Return the index of the welded point, inserting it if no point is within tolerance
+    pub fn weld(&mut self, point: Point3<f64>) -> usize {
+        let cell = self.cell_of(&point);
+
+        // Pick the earliest inserted match so the result doesn't depend on hash order
+        let mut found: Option<usize> = None;
+        for dx in -1..=1 {
+            for dy in -1..=1 {
+                for dz in -1..=1 {
+                    let key = (cell.0 + dx, cell.1 + dy, cell.2 + dz);
+                    let Some(candidates) = self.cells.get(&key) else {
+                        continue;
+                    };
+                    for &index in candidates {
+                        let existing = &self.points[index];
+                        if (existing.x - point.x).abs() < self.tolerance[0]
+                            && (existing.y - point.y).abs() < self.tolerance[1]
+                            && (existing.z - point.z).abs() < self.tolerance[2]
+                            && found.is_none_or(|f| index < f)
+                        {
+                            found = Some(index);
+                        }
+                    }
+                }
+            }
+        }
+
+        if let Some(index) = found {
+            return index;
+        }
+
+        let index = self.points.len();
+        self.points.push(point);
+        self.cells.entry(cell).or_default().push(index);
+        index
+    }
+
+    /// Welded points in insertion order
+    pub fn points(&self) -> &[Point3<f64>] {
+        &self.points
+    }
+
+    /// Consume the welder and return the welded points in insertion order
+    pub fn into_points(self) -> Vec<Point3<f64>> {
+        self.points
+    }
+
+    /// Quantize a point to its grid cell
+    fn cell_of(&self, point: &Point3<f64>) -> (i64, i64, i64) {
+        (
+            (point.x / self.tolerance[0]).floor() as i64,
+            (point.y / self.tolerance[1]).floor() as i64,
+            (point.z / self.tolerance[2]).floor() as i64,
+        )
+    }
+}
+
+/// Weld the vertices of a model, returning the welded points and the faces as
+/// indices into them. Points are in world coordinates and ordered by first use
+/// by the faces. The tolerance is in metres, also for models with x and y in
+/// degrees.
+pub(crate) fn weld_model(model: &Model, tolerance: f64) -> (Vec<Point3<f64>>, Vec<Vec<usize>>) {
+    let tolerance = tolerance.max(EPSILON);
+    let horizontal = tolerance * units_per_metre(model.crs);
+    let mut welder = VertexWelder::with_axis_tolerances([horizontal, horizontal, tolerance]);
+    // Each model vertex only needs to be welded once, however many faces use it
+    let mut welded_ids: Vec<Option<usize>> = vec![None; model.vertices.len()];
+    let faces = model
+        .faces
+        .iter()
+        .map(|face| {
+            face.vertex_ids
+                .iter()
+                .map(|&vertex_id| {
+                    *welded_ids[vertex_id].get_or_insert_with(|| {
+                        welder.weld(model.world_point(model.vertices[vertex_id].point))
+                    })
+                })
+                .collect()
+        })
+        .collect();
+    (welder.into_points(), faces)
+}
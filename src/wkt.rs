@@ -0,0 +1,221 @@
+use crate::error::{Error, Result};
+use crate::footprint::{footprint_model, polygon_from_rings, Footprint, Polygon};
+use crate::format::ReadOptions;
+use crate::model::Model;
+use cgmath::Point3;
+use std::io::BufRead;
+
+/// Names of the CSV column holding the WKT geometry, compared case-insensitively
+const GEOMETRY_COLUMNS: [&str; 4] = ["wkt", "geometry", "geom", "the_geom"];
+
+/// Reader for building footprints in a CSV file with a WKT geometry column.
+///
+/// The first row holds the column names, as written by e.g. `ogr2ogr -f CSV
+/// -lco GEOMETRY=AS_WKT`. The geometry column is named `WKT`, `geometry`,
+/// `geom` or `the_geom`, and holds a `POLYGON` or `MULTIPOLYGON`, optionally
+/// with z. Every row becomes a LoD1.2 model named after its `id` column and
+/// extruded as with `GeoJsonReader`, taking the ground z and height from the
+/// columns named in `ReadOptions`.
+pub struct WktReader<R: BufRead> {
+    reader: R,
+    options: ReadOptions,
+}
+
+impl<R: BufRead> WktReader<R> {
+    /// Create a reader over CSV content
+    pub fn new(reader: R, options: &ReadOptions) -> Self {
+        WktReader {
+            reader,
+            options: options.clone(),
+        }
+    }
+
+    /// Read every row as a separate model
+    pub fn read(self) -> Result<Vec<Model>> {
+        let mut lines = self.reader.lines().enumerate();
+        let header = match lines.next() {
+            Some((_, line)) => split_csv(&line.map_err(Error::Io)?),
+            None => return Ok(Vec::new()),
+        };
+        let column = |names: &[&str]| {
+            header
+                .iter()
+                .position(|column| names.iter().any(|name| column.eq_ignore_ascii_case(name)))
+        };
+        let geometry_column = column(&GEOMETRY_COLUMNS).ok_or_else(|| {
+            invalid_data(&format!(
+                "CSV has no geometry column, expected one of: {}",
+                GEOMETRY_COLUMNS.join(", ")
+            ))
+        })?;
+        let id_column = column(&["id"]);
+
+        let mut models = Vec::new();
+        for (index, line) in lines {
+            let line = line.map_err(Error::Io)?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let line_number = index + 1;
+            let fields = split_csv(&line);
+
+            let geometry = fields.get(geometry_column).ok_or_else(|| {
+                invalid_data(&format!("Missing geometry at line {}", line_number))
+            })?;
+            let polygons = parse_wkt(geometry)
+                .map_err(|e| invalid_data(&format!("{} at line {}", e, line_number)))?;
+            let id = id_column
+                .and_then(|column| fields.get(column))
+                .filter(|id| !id.is_empty())
+                .cloned();
+            let attribute = |name: &str| {
+                let column = header.iter().position(|column| column == name)?;
+                fields.get(column)?.trim().parse().ok()
+            };
+
+            models.push(footprint_model(
+                &Footprint { polygons },
+                id,
+                attribute,
+                &self.options,
+            )?);
+        }
+
+        Ok(models)
+    }
+}
+
+/// Split a CSV line on commas, honouring double-quoted fields with `""` escapes
+fn split_csv(line: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(std::mem::take(&mut field)),
+            _ => field.push(c),
+        }
+    }
+    fields.push(field);
+    fields
+}
+
+/// Nested parenthesised lists of a WKT geometry
+enum WktNode {
+    Position(Point3<f64>),
+    List(Vec<WktNode>),
+}
+
+/// Parse a WKT `POLYGON` or `MULTIPOLYGON`, with or without z
+fn parse_wkt(text: &str) -> std::result::Result<Vec<Polygon>, String> {
+    let text = text.trim();
+    let (tag, body) = text.split_at(text.find('(').unwrap_or(text.len()));
+    let tag = tag.trim().to_ascii_uppercase();
+    let kind = tag.split_whitespace().next().unwrap_or_default();
+    if tag.ends_with("EMPTY") {
+        return Ok(Vec::new());
+    }
+
+    let mut chars = body.chars().filter(|c| !c.is_control()).peekable();
+    let node = parse_list(&mut chars)?;
+    match kind {
+        "POLYGON" => Ok(vec![to_polygon(node)?]),
+        "MULTIPOLYGON" => match node {
+            WktNode::List(polygons) => polygons.into_iter().map(to_polygon).collect(),
+            WktNode::Position(_) => Err("MULTIPOLYGON must hold polygons".to_string()),
+        },
+        _ => Err(format!(
+            "Unsupported footprint geometry type {}, expected POLYGON or MULTIPOLYGON",
+            kind
+        )),
+    }
+}
+
+/// Parse a parenthesised, comma-separated list of positions or lists
+fn parse_list(
+    chars: &mut std::iter::Peekable<impl Iterator<Item = char>>,
+) -> std::result::Result<WktNode, String> {
+    skip_whitespace(chars);
+    if chars.next() != Some('(') {
+        return Err("Expected '(' in WKT".to_string());
+    }
+
+    let mut items = Vec::new();
+    loop {
+        skip_whitespace(chars);
+        if chars.peek() == Some(&'(') {
+            items.push(parse_list(chars)?);
+        } else {
+            // A position: numbers up to the next ',' or ')'
+            let mut position = String::new();
+            while let Some(&c) = chars.peek() {
+                if c == ',' || c == ')' {
+                    break;
+                }
+                position.push(c);
+                chars.next();
+            }
+            let coordinates = position
+                .split_whitespace()
+                .map(|value| value.parse::<f64>())
+                .collect::<std::result::Result<Vec<_>, _>>()
+                .map_err(|_| format!("Invalid WKT position: {}", position.trim()))?;
+            if coordinates.len() < 2 {
+                return Err(format!("Invalid WKT position: {}", position.trim()));
+            }
+            items.push(WktNode::Position(Point3::new(
+                coordinates[0],
+                coordinates[1],
+                coordinates.get(2).copied().unwrap_or(0.0),
+            )));
+        }
+
+        skip_whitespace(chars);
+        match chars.next() {
+            Some(',') => continue,
+            Some(')') => return Ok(WktNode::List(items)),
+            _ => return Err("Expected ',' or ')' in WKT".to_string()),
+        }
+    }
+}
+
+fn skip_whitespace(chars: &mut std::iter::Peekable<impl Iterator<Item = char>>) {
+    while chars.peek().is_some_and(|c| c.is_whitespace()) {
+        chars.next();
+    }
+}
+
+/// Polygon from a list of rings, each a list of positions
+fn to_polygon(node: WktNode) -> std::result::Result<Polygon, String> {
+    let WktNode::List(rings) = node else {
+        return Err("POLYGON must hold rings".to_string());
+    };
+    let rings = rings
+        .into_iter()
+        .map(|ring| match ring {
+            WktNode::List(positions) => positions
+                .into_iter()
+                .map(|position| match position {
+                    WktNode::Position(point) => Ok(point),
+                    WktNode::List(_) => Err("Ring must hold positions".to_string()),
+                })
+                .collect(),
+            WktNode::Position(_) => Err("POLYGON must hold rings".to_string()),
+        })
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    polygon_from_rings(rings).map_err(|e| e.to_string())
+}
+
+fn invalid_data(message: &str) -> Error {
+    Error::Io(std::io::Error::new(
+        std::io::ErrorKind::InvalidData,
+        message.to_string(),
+    ))
+}